@@ -1,4 +1,11 @@
-use crate::canvas_view::CanvasView;
+use crate::canvas_view::{CanvasView, REDO_SHORTCUT, UNDO_SHORTCUT};
+use crate::sketch_file::SketchDocument;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::svg_export::SvgExportSettings;
+
+// the sketch is stored on its own as the same versioned json that sketch files use,
+// so an old session goes through the same migrations as an old file
+const SKETCH_KEY: &str = "sketch";
 
 /// We derive Deserialize/Serialize so we can persist app state on shutdown.
 #[derive(serde::Deserialize, serde::Serialize)]
@@ -11,22 +18,192 @@ pub struct ConstraintApp {
     #[serde(skip)]
     value: f32,
 
+    // last path used by File -> Open / Save
+    #[cfg(not(target_arch = "wasm32"))]
+    sketch_path: String,
+
+    #[cfg(not(target_arch = "wasm32"))]
+    svg_path: String,
+
+    #[cfg(not(target_arch = "wasm32"))]
+    dxf_path: String,
+
+    #[cfg(not(target_arch = "wasm32"))]
+    svg_settings: SvgExportSettings,
+
     #[serde(skip)]
-    canvas_view: CanvasView,  
+    canvas_view: CanvasView,
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    file_prompt: Option<FilePrompt>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy, PartialEq)]
+enum FileAction {
+    Open,
+    Save,
+    ExportSvg,
+    ImportDxf,
+    ExportDxf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct FilePrompt {
+    action: FileAction,
+    path: String,
+    error: Option<String>,
 }
 
 
 impl ConstraintApp {
     /// Called once before the first frame.
-    pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
+    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+
+        let mut app: Self = match cc.storage {
+            Some(storage) => eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default(),
+            None => Default::default(),
+        };
+
+        // carry on with the last session's sketch, the test shapes are only for a first run
+        let restored = cc
+            .storage
+            .and_then(|storage| storage.get_string(SKETCH_KEY))
+            .map(|json| SketchDocument::from_json(&json).and_then(CanvasView::from_sketch));
+
+        match restored {
+            Some(Ok(canvas_view)) => app.canvas_view = canvas_view,
+            Some(Err(err)) => {
+                log::warn!("Could not restore the last sketch: {err}");
+                app.setup_test_values();
+            }
+            None => app.setup_test_values(),
+        }
+
+        app
+    }
+
+    fn setup_test_values(&mut self) {
+        self.canvas_view.setup_test_values_1();
+        self.canvas_view.setup_test_values_2();
+        self.canvas_view.setup_test_values_3();
+        self.canvas_view.setup_test_values_4();
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn open_file_prompt(&mut self, action: FileAction) {
+        let path = match action {
+            FileAction::Open | FileAction::Save => self.sketch_path.clone(),
+            FileAction::ExportSvg => self.svg_path.clone(),
+            FileAction::ImportDxf | FileAction::ExportDxf => self.dxf_path.clone(),
+        };
 
-        let mut default : Self = Default::default();
-        default.canvas_view.setup_test_values_1();
-        default.canvas_view.setup_test_values_2();
-        default.canvas_view.setup_test_values_3();
-        default.canvas_view.setup_test_values_4();
+        self.file_prompt = Some(FilePrompt {
+            action,
+            path,
+            error: None,
+        });
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn show_file_prompt(&mut self, ctx: &egui::Context) {
+        let Some(prompt) = &mut self.file_prompt else {
+            return;
+        };
 
-        default
+        let (title, button_text) = match prompt.action {
+            FileAction::Open => ("Open sketch", "Open"),
+            FileAction::Save => ("Save sketch", "Save"),
+            FileAction::ExportSvg => ("Export SVG", "Export"),
+            FileAction::ImportDxf => ("Import DXF", "Import"),
+            FileAction::ExportDxf => ("Export DXF", "Export"),
+        };
+        let svg_settings = &mut self.svg_settings;
+
+        let mut is_open = true;
+        let mut is_confirmed = false;
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut is_open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Path:");
+                    let response = ui.text_edit_singleline(&mut prompt.path);
+                    if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                        is_confirmed = true;
+                    }
+                });
+                if prompt.action == FileAction::ExportSvg {
+                    ui.horizontal(|ui| {
+                        ui.label("Stroke:");
+                        ui.add(
+                            egui::DragValue::new(&mut svg_settings.stroke_width)
+                                .range(0.1..=20.0)
+                                .speed(0.1),
+                        );
+                        ui.color_edit_button_srgba(&mut svg_settings.stroke_color);
+                    });
+                    ui.checkbox(&mut svg_settings.show_vertices, "Vertex markers");
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut svg_settings.show_constraints, "Constraint annotations");
+                        ui.color_edit_button_srgba(&mut svg_settings.annotation_color);
+                    });
+                }
+                if let Some(error) = &prompt.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                if ui.button(button_text).clicked() {
+                    is_confirmed = true;
+                }
+            });
+
+        if !is_open {
+            self.file_prompt = None;
+            return;
+        }
+        if !is_confirmed {
+            return;
+        }
+
+        let path = std::path::PathBuf::from(&prompt.path);
+        let result = match prompt.action {
+            FileAction::Open => SketchDocument::read_file(&path)
+                .and_then(CanvasView::from_sketch)
+                .map(|canvas_view| self.canvas_view = canvas_view)
+                .map_err(|err| err.to_string()),
+            FileAction::Save => self
+                .canvas_view
+                .to_sketch()
+                .write_file(&path)
+                .map_err(|err| err.to_string()),
+            FileAction::ExportSvg => {
+                let svg = self.canvas_view.export_svg(ctx, &self.svg_settings);
+                std::fs::write(&path, svg).map_err(|err| err.to_string())
+            }
+            FileAction::ImportDxf => std::fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| CanvasView::from_dxf(&text).map_err(|err| err.to_string()))
+                .map(|canvas_view| self.canvas_view = canvas_view),
+            FileAction::ExportDxf => {
+                std::fs::write(&path, self.canvas_view.to_dxf()).map_err(|err| err.to_string())
+            }
+        };
+
+        match result {
+            Ok(()) => {
+                match prompt.action {
+                    FileAction::Open | FileAction::Save => self.sketch_path = prompt.path.clone(),
+                    FileAction::ExportSvg => self.svg_path = prompt.path.clone(),
+                    FileAction::ImportDxf | FileAction::ExportDxf => {
+                        self.dxf_path = prompt.path.clone()
+                    }
+                }
+                self.file_prompt = None;
+            }
+            Err(err) => prompt.error = Some(err),
+        }
     }
 }
 
@@ -34,6 +211,11 @@ impl eframe::App for ConstraintApp {
     /// Called by the frame work to save state before shutdown.
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
         eframe::set_value(storage, eframe::APP_KEY, self);
+
+        match self.canvas_view.to_sketch().to_json() {
+            Ok(json) => storage.set_string(SKETCH_KEY, json),
+            Err(err) => log::warn!("Could not store the sketch: {err}"),
+        }
     }
 
     /// Called each time the UI needs repainting, which may be many times per second.
@@ -45,6 +227,31 @@ impl eframe::App for ConstraintApp {
                 let is_web = cfg!(target_arch = "wasm32");
                 if !is_web {
                     ui.menu_button("File", |ui| {
+                        #[cfg(not(target_arch = "wasm32"))]
+                        {
+                            if ui.button("Open…").clicked() {
+                                self.open_file_prompt(FileAction::Open);
+                                ui.close_menu();
+                            }
+                            if ui.button("Save…").clicked() {
+                                self.open_file_prompt(FileAction::Save);
+                                ui.close_menu();
+                            }
+                            ui.separator();
+                            if ui.button("Import DXF…").clicked() {
+                                self.open_file_prompt(FileAction::ImportDxf);
+                                ui.close_menu();
+                            }
+                            if ui.button("Export DXF…").clicked() {
+                                self.open_file_prompt(FileAction::ExportDxf);
+                                ui.close_menu();
+                            }
+                            if ui.button("Export SVG…").clicked() {
+                                self.open_file_prompt(FileAction::ExportSvg);
+                                ui.close_menu();
+                            }
+                            ui.separator();
+                        }
                         if ui.button("Quit").clicked() {
                             ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                         }
@@ -52,9 +259,35 @@ impl eframe::App for ConstraintApp {
                     ui.add_space(16.0);
                 }
 
+                ui.menu_button("Edit", |ui| {
+                    let undo_button = egui::Button::new("Undo")
+                        .shortcut_text(ctx.format_shortcut(&UNDO_SHORTCUT));
+                    if ui
+                        .add_enabled(self.canvas_view.can_undo(), undo_button)
+                        .clicked()
+                    {
+                        self.canvas_view.undo();
+                        ui.close_menu();
+                    }
+
+                    let redo_button = egui::Button::new("Redo")
+                        .shortcut_text(ctx.format_shortcut(&REDO_SHORTCUT));
+                    if ui
+                        .add_enabled(self.canvas_view.can_redo(), redo_button)
+                        .clicked()
+                    {
+                        self.canvas_view.redo();
+                        ui.close_menu();
+                    }
+                });
+                ui.add_space(16.0);
+
                 egui::widgets::global_theme_preference_buttons(ui);
             });
         });
+        #[cfg(not(target_arch = "wasm32"))]
+        self.show_file_prompt(ctx);
+
         egui::CentralPanel::default().show(ctx, |ui| {
             self.canvas_view.update(ui);
 
@@ -0,0 +1,140 @@
+use egui::{emath, Pos2, Rect, Vec2};
+
+const MIN_SCALE: f32 = 0.02;
+const MAX_SCALE: f32 = 50.0;
+
+// where the sketch sits on the canvas. a sketch position lands at
+// canvas origin + offset + position * scale, so offset is in screen points
+#[derive(Clone, Copy, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Camera {
+    pub offset: Vec2,
+    pub scale: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            offset: Vec2::ZERO,
+            scale: 1.0,
+        }
+    }
+}
+
+impl Camera {
+    // sketch coordinates to the screen, for a canvas filling screen_rect
+    pub fn screen_transform(&self, screen_rect: Rect) -> emath::RectTransform {
+        let visible = Rect::from_min_size(
+            Pos2::ZERO - self.offset / self.scale,
+            screen_rect.size() / self.scale,
+        );
+        emath::RectTransform::from_to(visible, screen_rect)
+    }
+
+    // screen distances, like hit radii, as a distance in the sketch
+    pub fn sketch_length(&self, screen_length: f32) -> f32 {
+        screen_length / self.scale
+    }
+
+    pub fn pan(&mut self, screen_delta: Vec2) {
+        self.offset += screen_delta;
+    }
+
+    // zooms by factor, keeping whatever is under screen_pos where it is
+    pub fn zoom_about(&mut self, screen_rect: Rect, screen_pos: Pos2, factor: f32) {
+        let sketch_pos = self
+            .screen_transform(screen_rect)
+            .inverse()
+            .transform_pos(screen_pos);
+
+        self.scale = (self.scale * factor).clamp(MIN_SCALE, MAX_SCALE);
+        self.offset = screen_pos - screen_rect.min - sketch_pos.to_vec2() * self.scale;
+    }
+
+    // centres the sketch bounds on the canvas as large as they fit, with margin
+    // screen points left around them
+    pub fn fit(&mut self, screen_rect: Rect, bounds: Rect, margin: f32) {
+        if !bounds.is_finite() {
+            return;
+        }
+
+        // a straight line has no size along one axis and is fitted along the other,
+        // a single point has no size at all and is only centred
+        if bounds.width() > f32::EPSILON || bounds.height() > f32::EPSILON {
+            let available = (screen_rect.size() - Vec2::splat(2.0 * margin)).max(Vec2::splat(1.0));
+            let scale_x = available.x / bounds.width().max(f32::EPSILON);
+            let scale_y = available.y / bounds.height().max(f32::EPSILON);
+
+            self.scale = scale_x.min(scale_y).clamp(MIN_SCALE, MAX_SCALE);
+        }
+        self.offset = screen_rect.size() / 2.0 - bounds.center().to_vec2() * self.scale;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen_rect() -> Rect {
+        Rect::from_min_size(Pos2::new(100.0, 50.0), Vec2::new(800.0, 600.0))
+    }
+
+    #[test]
+    fn zoom_keeps_the_point_under_the_cursor() {
+        let mut camera = Camera {
+            offset: Vec2::new(30.0, -20.0),
+            scale: 1.5,
+        };
+        let cursor = Pos2::new(420.0, 310.0);
+        let under_cursor = camera
+            .screen_transform(screen_rect())
+            .inverse()
+            .transform_pos(cursor);
+
+        camera.zoom_about(screen_rect(), cursor, 2.0);
+
+        assert_eq!(camera.scale, 3.0);
+        let back_on_screen = camera
+            .screen_transform(screen_rect())
+            .transform_pos(under_cursor);
+        assert!(back_on_screen.distance(cursor) < 0.001);
+
+        // and stops at the limits
+        camera.zoom_about(screen_rect(), cursor, 1000.0);
+        assert_eq!(camera.scale, MAX_SCALE);
+    }
+
+    #[test]
+    fn fit_centres_the_bounds_inside_the_margin() {
+        let mut camera = Camera::default();
+        let bounds = Rect::from_min_max(Pos2::new(-50.0, 0.0), Pos2::new(150.0, 50.0));
+
+        camera.fit(screen_rect(), bounds, 40.0);
+
+        // the width is what limits it, 720 available points across 200 units
+        assert!((camera.scale - 3.6).abs() < 0.001);
+        let to_screen = camera.screen_transform(screen_rect());
+        assert!(
+            to_screen
+                .transform_pos(bounds.center())
+                .distance(screen_rect().center())
+                < 0.001
+        );
+        assert!((to_screen.transform_pos(bounds.min).x - 140.0).abs() < 0.001);
+
+        // a lone point is only centred
+        camera.fit(
+            screen_rect(),
+            Rect::from_min_max(Pos2::new(7.0, 7.0), Pos2::new(7.0, 7.0)),
+            40.0,
+        );
+        assert!((camera.scale - 3.6).abs() < 0.001);
+        let to_screen = camera.screen_transform(screen_rect());
+        assert!(
+            to_screen
+                .transform_pos(Pos2::new(7.0, 7.0))
+                .distance(screen_rect().center())
+                < 0.001
+        );
+    }
+}
@@ -1,17 +1,27 @@
-use egui::{Frame, Pos2, Sense, Vec2};
+use egui::{Frame, Key, KeyboardShortcut, Modifiers, Pos2, Sense, Vec2};
 
-use crate::constraint_manager::ConstraintManager;
-use crate::display_manager::DisplayManager;
+use crate::constraint_manager::{ConstraintManager, SolverMode};
+use crate::display_manager::{ConstraintKind, DisplayManager, DisplayManagerRecord, Tool};
 use crate::drawing_manager::DrawingManager;
+use crate::dxf::{self, DxfError};
+use crate::sketch_file::{SketchDocument, SketchFileError};
+use crate::svg_export::SvgExportSettings;
 
 use std::cell::RefCell;
 use std::rc::Rc;
 
+pub const UNDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+pub const REDO_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::Z);
+
 pub struct CanvasView {
     drawing_manager: Rc<RefCell<DrawingManager>>,
     display_manager: Rc<RefCell<DisplayManager>>,
     constraint_manager: Rc<RefCell<ConstraintManager>>,
     //network : DrawingNetwork
+
+    // why the last constraint asked for from the selection couldn't be made
+    constraint_error: Option<String>,
 }
 
 impl Default for CanvasView {
@@ -37,18 +47,73 @@ impl Default for CanvasView {
             display_manager,
             drawing_manager,
             constraint_manager,
+            constraint_error: None,
         }
     }
 }
 
 impl CanvasView {
+    pub fn to_sketch(&self) -> SketchDocument {
+        SketchDocument::new(
+            self.drawing_manager.borrow().to_record(),
+            self.constraint_manager.borrow().to_record(),
+            self.display_manager.borrow().to_record(),
+        )
+    }
+
+    // builds a fresh view from the sketch so that a bad document leaves the current one alone
+    pub fn from_sketch(sketch: SketchDocument) -> Result<Self, SketchFileError> {
+        let canvas_view = Self::default();
+
+        canvas_view
+            .drawing_manager
+            .borrow_mut()
+            .load_record(sketch.drawing)?;
+        canvas_view
+            .constraint_manager
+            .borrow_mut()
+            .load_record(sketch.constraints)?;
+        canvas_view
+            .display_manager
+            .borrow_mut()
+            .load_record(sketch.display);
+
+        Ok(canvas_view)
+    }
+
+    pub fn to_dxf(&self) -> String {
+        dxf::write_dxf(
+            &self.drawing_manager.borrow(),
+            &self.constraint_manager.borrow(),
+        )
+    }
+
+    pub fn from_dxf(text: &str) -> Result<Self, DxfError> {
+        let canvas_view = Self::default();
+
+        dxf::read_dxf(
+            text,
+            dxf::DEFAULT_MERGE_TOLERANCE,
+            &canvas_view.drawing_manager,
+            &canvas_view.constraint_manager,
+        )?;
+        let mut display_manager_mut = canvas_view.display_manager.borrow_mut();
+        display_manager_mut.load_record(DisplayManagerRecord::default());
+        // drawings from elsewhere can be anywhere at any size
+        display_manager_mut.zoom_to_fit();
+        drop(display_manager_mut);
+
+        Ok(canvas_view)
+    }
+
+    pub fn export_svg(&self, ctx: &egui::Context, settings: &SvgExportSettings) -> String {
+        self.display_manager.borrow().export_svg(ctx, settings)
+    }
+
     pub fn setup_test_values_1(&mut self) {
-        #[allow(unused_assignments)]
-        let mut edge_handle_1 = 0;
-        #[allow(unused_assignments)]
-        let mut edge_handle_2 = 0;
-        #[allow(unused_assignments)]
-        let mut edge_handle_3 = 0;
+        let edge_handle_1;
+        let edge_handle_2;
+        let edge_handle_3;
 
         let mut display_manager_mut = self.display_manager.borrow_mut();
         {
@@ -90,12 +155,9 @@ impl CanvasView {
         display_manager_mut.add_constraint(ch_2);
     }
     pub fn setup_test_values_2(&mut self) {
-        #[allow(unused_assignments)]
-        let mut edge_handle_1 = 0;
-        #[allow(unused_assignments)]
-        let mut edge_handle_2 = 0;
-        #[allow(unused_assignments)]
-        let mut edge_handle_3 = 0;
+        let edge_handle_1;
+        let edge_handle_2;
+        let edge_handle_3;
 
         let mut display_manager_mut = self.display_manager.borrow_mut();
         {
@@ -136,14 +198,10 @@ impl CanvasView {
         display_manager_mut.add_constraint(ch_1);
     }
     pub fn setup_test_values_3(&mut self) {
-        #[allow(unused_assignments)]
-        let mut edge_handle_1 = 0;
-        #[allow(unused_assignments)]
-        let mut edge_handle_2 = 0;
-        #[allow(unused_assignments)]
-        let mut edge_handle_3 = 0; 
-        #[allow(unused_assignments)]
-        let mut edge_handle_4 = 0;
+        let edge_handle_1;
+        let edge_handle_2;
+        let edge_handle_3;
+        let edge_handle_4;
 
         let mut display_manager_mut = self.display_manager.borrow_mut();
         {
@@ -183,14 +241,10 @@ impl CanvasView {
     }
 
     pub fn setup_test_values_4(&mut self) {
-        #[allow(unused_assignments)]
-        let mut edge_handle_1 = 0;
-        #[allow(unused_assignments)]
-        let mut edge_handle_2 = 0;
-        #[allow(unused_assignments)]
-        let mut edge_handle_3 = 0; 
-        #[allow(unused_assignments)]
-        let mut edge_handle_4 = 0;
+        let edge_handle_1;
+        let edge_handle_2;
+        let edge_handle_3;
+        let edge_handle_4;
 
         let mut display_manager_mut = self.display_manager.borrow_mut();
         {
@@ -229,20 +283,121 @@ impl CanvasView {
         display_manager_mut.add_constraint(ch_2);
     }
 
+    pub fn can_undo(&self) -> bool {
+        self.display_manager.borrow().can_undo()
+    }
+    pub fn can_redo(&self) -> bool {
+        self.display_manager.borrow().can_redo()
+    }
+    pub fn undo(&mut self) {
+        self.display_manager.borrow_mut().undo();
+    }
+    pub fn redo(&mut self) {
+        self.display_manager.borrow_mut().redo();
+    }
+
     pub fn update(&mut self, ui: &mut egui::Ui) {
+        // the shifted shortcut has to be checked first, the plain one would match it too.
+        // a focused text field keeps them for its own undo
+        if !ui.ctx().wants_keyboard_input() {
+            if ui.input_mut(|i| i.consume_shortcut(&REDO_SHORTCUT)) {
+                self.redo();
+            } else if ui.input_mut(|i| i.consume_shortcut(&UNDO_SHORTCUT)) {
+                self.undo();
+            }
+        }
+
+        ui.horizontal(|ui| {
+            let mut constraint_manager_mut = self.constraint_manager.borrow_mut();
+            let mut solver_mode = constraint_manager_mut.get_solver_mode();
+
+            ui.label("Solver:");
+            ui.radio_value(&mut solver_mode, SolverMode::Analytic, "Analytic");
+            ui.radio_value(&mut solver_mode, SolverMode::Global, "Global");
+
+            constraint_manager_mut.set_solver_mode(solver_mode);
+
+            ui.separator();
+
+            let mut display_manager_mut = self.display_manager.borrow_mut();
+            let mut tool = display_manager_mut.get_tool();
+
+            ui.label("Tool:");
+            ui.radio_value(&mut tool, Tool::Select, "Select");
+            ui.radio_value(&mut tool, Tool::Point, "Point");
+            ui.radio_value(&mut tool, Tool::Line, "Line");
+
+            display_manager_mut.set_tool(tool);
+
+            ui.separator();
+
+            if ui.button("Zoom to fit").clicked() {
+                display_manager_mut.zoom_to_fit();
+            }
+        });
+
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Constrain:");
+            for kind in ConstraintKind::ALL {
+                if ui.button(kind.label()).clicked() {
+                    self.add_constraint_from_selection(kind);
+                }
+            }
+        });
+
+        if let Some(err) = &self.constraint_error {
+            ui.colored_label(ui.visuals().error_fg_color, err);
+        }
+
         let display_manager = Rc::clone(&self.display_manager);
         Frame::canvas(ui.style()).show(ui, |ui| {
-            let (response, painter) =
-                ui.allocate_painter(Vec2::new(ui.available_width(), 600.0), Sense::hover());
+            let (response, painter) = ui.allocate_painter(
+                Vec2::new(ui.available_width(), 600.0),
+                Sense::click_and_drag(),
+            );
 
-            display_manager
+            let item_responses = display_manager
                 .borrow_mut()
                 .update_interaction(ui, &response);
 
+            // right-clicking the sketch or anything on it offers the same constraints as the
+            // toolbar, the drawing tools use the right-click to finish a polyline instead
+            if display_manager.borrow().get_tool() == Tool::Select {
+                for menu_response in std::iter::once(&response).chain(&item_responses) {
+                    menu_response.context_menu(|ui| self.constraint_menu(ui));
+                }
+            }
+
             display_manager.borrow().draw(&response, &painter);
         });
     }
 
+    fn constraint_menu(&mut self, ui: &mut egui::Ui) {
+        let (selected_vertices, selected_edges) = self.display_manager.borrow().get_selection();
+        ui.label(format!(
+            "{} vertices, {} edges selected",
+            selected_vertices.len(),
+            selected_edges.len()
+        ));
+        ui.separator();
+
+        for kind in ConstraintKind::ALL {
+            if ui.button(kind.label()).clicked() {
+                self.add_constraint_from_selection(kind);
+                ui.close_menu();
+            }
+        }
+    }
+
+    fn add_constraint_from_selection(&mut self, kind: ConstraintKind) {
+        self.constraint_error = self
+            .display_manager
+            .borrow_mut()
+            .add_constraint_from_selection(kind)
+            .err()
+            .map(|err| err.to_string());
+    }
+
     pub fn print_values(&self) {
         let edges_ref = self.drawing_manager.borrow();
         let edges = edges_ref.get_all_edges();
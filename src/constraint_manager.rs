@@ -1,22 +1,30 @@
 use core::f32;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use thiserror::Error;
 
-use crate::drawing_manager::{DrawingManager, Edge};
+use crate::drawing_manager::{DrawingManager, Edge, Vertex};
+use crate::global_solver::{self, DofAnalysis, GlobalSolverResponse, GlobalSolverSettings};
+use crate::handles::{ConstraintHandle, EdgeHandle, HandleAllocator, VertexHandle};
 
 use egui::{Pos2, Vec2};
 
 use std::cell::RefCell;
 use std::rc::Rc;
 
-type EdgeHandle = i32;
-type VertexHandle = i32;
-type ConstraintHandle = i32;
+// halvings of a group move that the rest of the sketch can't follow all the way,
+// each one costs a global solve
+const GROUP_BISECTION_STEPS: usize = 6;
 
 #[derive(Default)]
 pub struct ConstraintManager {
     drawing_manager: Option<Rc<RefCell<DrawingManager>>>,
     constraint_map: BTreeMap<ConstraintHandle, Constraint>,
+    constraint_allocator: HandleAllocator<ConstraintHandle>,
+    // constraints touching each vertex and edge, so a drag only has to look at its
+    // neighbours. a vertex also lists the constraints on any edge it is an end of
+    vertex_constraints: BTreeMap<VertexHandle, Vec<ConstraintHandle>>,
+    edge_constraints: BTreeMap<EdgeHandle, Vec<ConstraintHandle>>,
+    solver_mode: SolverMode,
 }
 
 impl ConstraintManager {
@@ -27,16 +35,24 @@ impl ConstraintManager {
         self.drawing_manager = Some(drawing_manager);
     }
 
+    pub fn get_solver_mode(&self) -> SolverMode {
+        self.solver_mode
+    }
+    pub fn set_solver_mode(&mut self, solver_mode: SolverMode) {
+        self.solver_mode = solver_mode;
+    }
+
     pub fn get_constraint(&self, ch: ConstraintHandle) -> Result<&Constraint, ConstraintError> {
         self.constraint_map
             .get(&ch)
             .ok_or(ConstraintError::ConstraintNotFound(ch))
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn solve_for_edge(
         &self,
         eh: EdgeHandle,
-        _fixed_pos: &Pos2,
+        fixed_pos: &Pos2,
         try_pos: &Pos2,
         v1_fixed_pos: &Pos2,
         v1_try_pos: &Pos2,
@@ -51,7 +67,63 @@ impl ConstraintManager {
 
         let vh_1 = dm_shared.borrow().get_edge(eh).unwrap().start_point_vh;
         let vh_2 = dm_shared.borrow().get_edge(eh).unwrap().end_point_vh;
-        let edge_consts = dm_shared.borrow().get_edge(eh).unwrap().constraints.clone();
+
+        // constraints on the dragged edge that a translation keeps satisfied. angles and
+        // points on the edge also depend on vertices outside of it, so they still apply
+        let edge_consts: Vec<ConstraintHandle> = self
+            .get_edge_constraints(eh)
+            .iter()
+            .filter(|ch| {
+                !matches!(
+                    self.constraint_map.get(ch),
+                    Some(Constraint::ANGLE(_) | Constraint::POINT_ON_EDGE(_))
+                )
+            })
+            .copied()
+            .collect();
+
+        // a fixed endpoint turns the drag into a rotation around it
+        match (self.is_vertex_fixed(vh_1), self.is_vertex_fixed(vh_2)) {
+            (true, true) => return EdgeSolverResponse::locked(),
+            (true, false) => {
+                let pivot = dm_shared.borrow().get_vertex(vh_1).unwrap().position;
+                let new_pt_2 =
+                    self.solve_edge_rotation(vh_2, &pivot, fixed_pos, try_pos, v2_fixed_pos);
+
+                return match new_pt_2 {
+                    Some((new_pt_2, valid_path)) => EdgeSolverResponse {
+                        state: SolverState::Partial,
+                        valid_paths: Some(valid_path.into_iter().collect()),
+                        new_pos: Some([pivot, new_pt_2]),
+                    },
+                    None => EdgeSolverResponse::locked(),
+                };
+            }
+            (false, true) => {
+                let pivot = dm_shared.borrow().get_vertex(vh_2).unwrap().position;
+                let new_pt_1 =
+                    self.solve_edge_rotation(vh_1, &pivot, fixed_pos, try_pos, v1_fixed_pos);
+
+                return match new_pt_1 {
+                    Some((new_pt_1, valid_path)) => EdgeSolverResponse {
+                        state: SolverState::Partial,
+                        valid_paths: Some(valid_path.into_iter().collect()),
+                        new_pos: Some([new_pt_1, pivot]),
+                    },
+                    None => EdgeSolverResponse::locked(),
+                };
+            }
+            (false, false) => (),
+        }
+
+        // an axis constraint on the dragged edge itself keeps it axis aligned while it moves
+        let axis_direction = edge_consts
+            .iter()
+            .find_map(|ch| match self.constraint_map.get(ch) {
+                Some(Constraint::HORIZONTAL(_)) => Some(Vec2::X),
+                Some(Constraint::VERTICAL(_)) => Some(Vec2::Y),
+                _ => None,
+            });
 
         // solve endpoint vertices to get valid paths
 
@@ -80,7 +152,16 @@ impl ConstraintManager {
         }
 
         if is_v1_free && is_v2_free {
-            return EdgeSolverResponse::default();
+            return EdgeSolverResponse {
+                state: SolverState::Free,
+                valid_paths: axis_direction.map(|direction| {
+                    vec![ConstraintPath::Line(Line {
+                        origin: *v1_try_pos,
+                        direction,
+                    })]
+                }),
+                new_pos: None,
+            };
         }
 
         // handle cases partial-partial, and partial-free
@@ -90,8 +171,8 @@ impl ConstraintManager {
         let line_pt_2 = dm_shared.borrow().get_vertex(vh_2).unwrap().position;
 
         let edge_line = Line {
-            origin: try_pos.clone(),
-            direction: (line_pt_2 - line_pt_1).normalized(),
+            origin: *try_pos,
+            direction: axis_direction.unwrap_or((line_pt_2 - line_pt_1).normalized()),
         };
 
         // we have valid paths, now do an intersection of the line created by the edge
@@ -113,11 +194,7 @@ impl ConstraintManager {
                         },
                     );
 
-                    if let Some(inter) = inter_result {
-                        Some(inter.origin)
-                    } else {
-                        None
-                    }
+                    inter_result.map(|inter| inter.origin)
                 }
                 ConstraintPath::Ray(r) => {
                     // Handle Line-Ray or Ray-Line case
@@ -130,11 +207,7 @@ impl ConstraintManager {
                         r,
                     );
 
-                    if let Some(inter) = inter_result {
-                        Some(inter.origin)
-                    } else {
-                        None
-                    }
+                    inter_result.map(|inter| inter.origin)
                 }
                 ConstraintPath::Circle(c) => {
                     let adjusted_origin = edge_line.origin + -edge_line.direction * 500.0;
@@ -151,12 +224,30 @@ impl ConstraintManager {
                         Some(valid_points[0].origin)
                     }
                 }
-                _ => None,
+                ConstraintPath::Segment(_) => {
+                    let (_, valid_points) = intersect_path_pair(
+                        &ConstraintPath::Line(edge_line.clone()),
+                        constraint_path,
+                    );
+                    valid_points.first().map(|p| p.origin)
+                }
+                ConstraintPath::Point(p) => {
+                    // the vertex can only stay put, which works if the edge still passes through it
+                    if edge_line.closest_point(&p.origin).distance(p.origin) < 0.01 {
+                        Some(p.origin)
+                    } else {
+                        None
+                    }
+                }
             }
         };
 
         let mut all_valid_paths: Vec<ConstraintPath> = vec![];
 
+        if axis_direction.is_some() {
+            all_valid_paths.push(ConstraintPath::Line(edge_line.clone()));
+        }
+
         let mut new_pt_1 = Pos2::default();
         let mut new_pt_2 = Pos2::default();
 
@@ -171,8 +262,8 @@ impl ConstraintManager {
             all_valid_paths.push(valid_path_2);
 
             // since vert 1 is free, we need to set it with vert 2's delta
-            let delta = new_pt_2 - v2_fixed_pos.clone();
-            new_pt_1 = v1_fixed_pos.clone() + delta;
+            let delta = new_pt_2 - *v2_fixed_pos;
+            new_pt_1 = *v1_fixed_pos + delta;
         } else if is_v2_free {
             // vert 2 is free, calculate intersection for vert 1
             let valid_path_1 = vert_response_1.valid_path.unwrap().clone();
@@ -184,21 +275,21 @@ impl ConstraintManager {
             all_valid_paths.push(valid_path_1);
 
             // since vert 2 is free, we need to set it with vert 1's delta
-            let delta = new_pt_1 - v1_fixed_pos.clone();
-            new_pt_2 = v2_fixed_pos.clone() + delta;
+            let delta = new_pt_1 - *v1_fixed_pos;
+            new_pt_2 = *v2_fixed_pos + delta;
         } else {
             // both verts are partially locked
             let valid_path_1 = vert_response_1.valid_path.unwrap().clone();
             let inter_1 = intersect_func(&valid_path_1);
 
-            if let None = inter_1 {
+            if inter_1.is_none() {
                 return EdgeSolverResponse::locked();
             }
 
             let valid_path_2 = vert_response_2.valid_path.unwrap().clone();
             let inter_2 = intersect_func(&valid_path_2);
 
-            if let None = inter_2 {
+            if inter_2.is_none() {
                 return EdgeSolverResponse::locked();
             }
 
@@ -215,10 +306,185 @@ impl ConstraintManager {
         }
     }
 
+    // rotates the free end of an edge around its fixed end by the angle the cursor swept,
+    // then lets the free end's own constraints adjust it
+    fn solve_edge_rotation(
+        &self,
+        free_vh: VertexHandle,
+        pivot: &Pos2,
+        fixed_pos: &Pos2,
+        try_pos: &Pos2,
+        free_fixed_pos: &Pos2,
+    ) -> Option<(Pos2, Option<ConstraintPath>)> {
+        let grab_dir = *fixed_pos - *pivot;
+        let try_dir = *try_pos - *pivot;
+
+        // grabbed right on the pivot, there is no angle to follow
+        if grab_dir.length() < 0.001 || try_dir.length() < 0.001 {
+            return None;
+        }
+
+        let rotation = signed_angle(grab_dir, try_dir);
+        let rotated_pt = *pivot + rotate_vec2(*free_fixed_pos - *pivot, rotation);
+
+        // rotating changes the edge direction, so none of its constraints can be ignored
+        let vert_response = self.solve_for_vertex(free_vh, free_fixed_pos, &rotated_pt, vec![]);
+
+        match vert_response.state {
+            SolverState::Locked => None,
+            SolverState::Free => Some((rotated_pt, None)),
+            SolverState::Partial => Some((vert_response.new_pos?, vert_response.valid_path)),
+        }
+    }
+
+    pub fn is_vertex_fixed(&self, vh: VertexHandle) -> bool {
+        self.get_vertex_constraints(vh).iter().any(|ch| {
+            matches!(self.constraint_map.get(ch), Some(Constraint::FIXED(fc)) if fc.vertex_handle == vh)
+        })
+    }
+
+    pub fn get_vertex_constraints(&self, vh: VertexHandle) -> &[ConstraintHandle] {
+        self.vertex_constraints
+            .get(&vh)
+            .map(|chs| chs.as_slice())
+            .unwrap_or_default()
+    }
+
+    // every constraint on any of the vertices, once each
+    fn get_constraints_on_vertices<'a>(
+        &self,
+        vhs: impl Iterator<Item = &'a VertexHandle>,
+    ) -> BTreeSet<ConstraintHandle> {
+        vhs.flat_map(|vh| self.get_vertex_constraints(*vh))
+            .copied()
+            .collect()
+    }
+
+    pub fn get_edge_constraints(&self, eh: EdgeHandle) -> &[ConstraintHandle] {
+        self.edge_constraints
+            .get(&eh)
+            .map(|chs| chs.as_slice())
+            .unwrap_or_default()
+    }
+
+    // mirrored partners of vertices that moved this frame, or of both vertices when their
+    // axis edge moved. `previous_positions` holds where the moved vertices were before
+    pub fn solve_symmetric_followers(
+        &self,
+        previous_positions: &BTreeMap<VertexHandle, Pos2>,
+    ) -> Vec<(VertexHandle, Pos2)> {
+        let dm_shared = if let Some(v) = &self.drawing_manager {
+            v
+        } else {
+            return vec![];
+        };
+        let dm_borrow = dm_shared.borrow();
+
+        let pos = |vh: VertexHandle| dm_borrow.get_vertex(vh).unwrap().position;
+        let has_moved = |vh: VertexHandle| {
+            previous_positions
+                .get(&vh)
+                .is_some_and(|previous| previous.distance(pos(vh)) > 0.001)
+        };
+
+        let mut followers = vec![];
+
+        // the index has the axis end points too, so this finds the constraints whose axis moved
+        for ch in self.get_constraints_on_vertices(previous_positions.keys()) {
+            let sc = if let Constraint::SYMMETRIC(sc) = &self.constraint_map[&ch] {
+                sc
+            } else {
+                continue;
+            };
+
+            let axis = dm_borrow.get_edge(sc.axis_edge_handle).unwrap();
+            let axis_start = pos(axis.start_point_vh);
+            let axis_end = pos(axis.end_point_vh);
+
+            if has_moved(sc.vertex_1_handle) {
+                followers.push((
+                    sc.vertex_2_handle,
+                    reflect_across_line(pos(sc.vertex_1_handle), axis_start, axis_end),
+                ));
+            } else if has_moved(sc.vertex_2_handle) {
+                followers.push((
+                    sc.vertex_1_handle,
+                    reflect_across_line(pos(sc.vertex_2_handle), axis_start, axis_end),
+                ));
+            } else if has_moved(axis.start_point_vh) || has_moved(axis.end_point_vh) {
+                // carry the first vertex along with the axis, keeping its spot relative
+                // to it, then mirror the second one off of it
+                let prev_start = *previous_positions
+                    .get(&axis.start_point_vh)
+                    .unwrap_or(&axis_start);
+                let prev_end = *previous_positions
+                    .get(&axis.end_point_vh)
+                    .unwrap_or(&axis_end);
+
+                let prev_dir = prev_end - prev_start;
+                let new_dir = axis_end - axis_start;
+                if prev_dir.length() < 0.001 || new_dir.length() < 0.001 {
+                    continue;
+                }
+
+                let rotation = signed_angle(prev_dir, new_dir);
+                let new_pos_1 =
+                    axis_start + rotate_vec2(pos(sc.vertex_1_handle) - prev_start, rotation);
+
+                followers.push((sc.vertex_1_handle, new_pos_1));
+                followers.push((
+                    sc.vertex_2_handle,
+                    reflect_across_line(new_pos_1, axis_start, axis_end),
+                ));
+            }
+        }
+
+        followers
+    }
+
+    // coincident partners of vertices that moved this frame, they go wherever the moved
+    // vertex went. `previous_positions` holds where the moved vertices were before
+    pub fn solve_coincident_followers(
+        &self,
+        previous_positions: &BTreeMap<VertexHandle, Pos2>,
+    ) -> Vec<(VertexHandle, Pos2)> {
+        let dm_shared = if let Some(v) = &self.drawing_manager {
+            v
+        } else {
+            return vec![];
+        };
+        let dm_borrow = dm_shared.borrow();
+
+        let pos = |vh: VertexHandle| dm_borrow.get_vertex(vh).unwrap().position;
+        let has_moved = |vh: VertexHandle| {
+            previous_positions
+                .get(&vh)
+                .is_some_and(|previous| previous.distance(pos(vh)) > 0.001)
+        };
+
+        let mut followers = vec![];
+
+        for ch in self.get_constraints_on_vertices(previous_positions.keys()) {
+            let cc = if let Constraint::COINCIDENT(cc) = &self.constraint_map[&ch] {
+                cc
+            } else {
+                continue;
+            };
+
+            if has_moved(cc.vertex_1_handle) {
+                followers.push((cc.vertex_2_handle, pos(cc.vertex_1_handle)));
+            } else if has_moved(cc.vertex_2_handle) {
+                followers.push((cc.vertex_1_handle, pos(cc.vertex_2_handle)));
+            }
+        }
+
+        followers
+    }
+
     pub fn solve_for_vertex(
         &self,
         vh: VertexHandle,
-        fixed_pos: &Pos2,
+        _fixed_pos: &Pos2,
         try_pos: &Pos2,
         constraints_to_ignore: Vec<ConstraintHandle>,
     ) -> SolverResponse {
@@ -228,18 +494,32 @@ impl ConstraintManager {
             return SolverResponse::default();
         };
 
+        if self.is_vertex_fixed(vh) {
+            return SolverResponse::locked();
+        }
+
         let dm_borrow = dm_shared.borrow();
 
         let mut length_end_constraints: Vec<&LengthConstraint> = vec![];
         let mut angle_center_constraints: Vec<&AngleConstraint> = vec![];
         let mut angle_end_constraints: Vec<&AngleConstraint> = vec![];
         let mut parallel_end_constraints: Vec<&ParallelConstraint> = vec![];
+        let mut perpendicular_end_constraints: Vec<&PerpendicularConstraint> = vec![];
+        let mut axis_end_constraints: Vec<(&AxisConstraint, Vec2)> = vec![];
+        let mut coincident_constraints: Vec<&CoincidentConstraint> = vec![];
+        let mut point_on_edge_constraints: Vec<&PointOnEdgeConstraint> = vec![];
+        let mut equal_length_end_constraints: Vec<&EqualLengthConstraint> = vec![];
+        let mut symmetric_constraints: Vec<&SymmetricConstraint> = vec![];
+        let mut distance_constraints: Vec<&DistanceConstraint> = vec![];
+        let mut edge_distance_point_constraints: Vec<&EdgeDistanceConstraint> = vec![];
+        let mut edge_distance_end_constraints: Vec<&EdgeDistanceConstraint> = vec![];
 
         //find constraints associated with vertex
-        for (ch, constraint) in &self.constraint_map {
+        for ch in self.get_vertex_constraints(vh) {
             if constraints_to_ignore.contains(ch) {
                 continue;
             }
+            let constraint = &self.constraint_map[ch];
             match constraint {
                 Constraint::LENGTH(length_constraint) => {
                     let edge = dm_borrow.get_edge(length_constraint.edge_handle).unwrap();
@@ -273,6 +553,96 @@ impl ConstraintManager {
                         parallel_end_constraints.push(parallel_constraint);
                     }
                 }
+                Constraint::PERPENDICULAR(perpendicular_constraint) => {
+                    let edge_1 = dm_borrow
+                        .get_edge(perpendicular_constraint.edge_1_handle)
+                        .unwrap();
+                    let edge_2 = dm_borrow
+                        .get_edge(perpendicular_constraint.edge_2_handle)
+                        .unwrap();
+
+                    if edge_1.end_point_vh == vh
+                        || edge_1.start_point_vh == vh
+                        || edge_2.end_point_vh == vh
+                        || edge_2.start_point_vh == vh
+                    {
+                        perpendicular_end_constraints.push(perpendicular_constraint);
+                    }
+                }
+                // already handled before the search, the vertex is locked
+                Constraint::FIXED(_) => (),
+                Constraint::EQUAL_LENGTH(equal_length_constraint) => {
+                    let edge_1 = dm_borrow
+                        .get_edge(equal_length_constraint.edge_1_handle)
+                        .unwrap();
+                    let edge_2 = dm_borrow
+                        .get_edge(equal_length_constraint.edge_2_handle)
+                        .unwrap();
+
+                    if edge_1.end_point_vh == vh
+                        || edge_1.start_point_vh == vh
+                        || edge_2.end_point_vh == vh
+                        || edge_2.start_point_vh == vh
+                    {
+                        equal_length_end_constraints.push(equal_length_constraint);
+                    }
+                }
+                Constraint::DISTANCE(distance_constraint) => {
+                    if distance_constraint.vertex_1_handle == vh
+                        || distance_constraint.vertex_2_handle == vh
+                    {
+                        distance_constraints.push(distance_constraint);
+                    }
+                }
+                Constraint::EDGE_DISTANCE(edge_distance_constraint) => {
+                    let edge = dm_borrow
+                        .get_edge(edge_distance_constraint.edge_handle)
+                        .unwrap();
+
+                    if edge_distance_constraint.vertex_handle == vh {
+                        edge_distance_point_constraints.push(edge_distance_constraint);
+                    } else if edge.end_point_vh == vh || edge.start_point_vh == vh {
+                        edge_distance_end_constraints.push(edge_distance_constraint);
+                    }
+                }
+                Constraint::SYMMETRIC(symmetric_constraint) => {
+                    if symmetric_constraint.vertex_1_handle == vh
+                        || symmetric_constraint.vertex_2_handle == vh
+                    {
+                        symmetric_constraints.push(symmetric_constraint);
+                    }
+                }
+                Constraint::COINCIDENT(coincident_constraint) => {
+                    if coincident_constraint.vertex_1_handle == vh
+                        || coincident_constraint.vertex_2_handle == vh
+                    {
+                        coincident_constraints.push(coincident_constraint);
+                    }
+                }
+                Constraint::POINT_ON_EDGE(point_on_edge_constraint) => {
+                    let edge = dm_borrow
+                        .get_edge(point_on_edge_constraint.edge_handle)
+                        .unwrap();
+
+                    if point_on_edge_constraint.vertex_handle == vh
+                        || edge.end_point_vh == vh
+                        || edge.start_point_vh == vh
+                    {
+                        point_on_edge_constraints.push(point_on_edge_constraint);
+                    }
+                }
+                Constraint::HORIZONTAL(axis_constraint) | Constraint::VERTICAL(axis_constraint) => {
+                    let edge = dm_borrow.get_edge(axis_constraint.edge_handle).unwrap();
+
+                    if edge.end_point_vh == vh || edge.start_point_vh == vh {
+                        let direction = if let Constraint::HORIZONTAL(_) = constraint {
+                            Vec2::X
+                        } else {
+                            Vec2::Y
+                        };
+                        axis_end_constraints.push((axis_constraint, direction));
+                    }
+                }
             }
         }
 
@@ -280,11 +650,20 @@ impl ConstraintManager {
             && angle_center_constraints.is_empty()
             && angle_end_constraints.is_empty()
             && parallel_end_constraints.is_empty()
+            && perpendicular_end_constraints.is_empty()
+            && axis_end_constraints.is_empty()
+            && coincident_constraints.is_empty()
+            && point_on_edge_constraints.is_empty()
+            && equal_length_end_constraints.is_empty()
+            && symmetric_constraints.is_empty()
+            && distance_constraints.is_empty()
+            && edge_distance_point_constraints.is_empty()
+            && edge_distance_end_constraints.is_empty()
         {
             return SolverResponse {
                 state: SolverState::Free,
                 valid_path: None,
-                new_pos: Some(try_pos.clone()),
+                new_pos: Some(*try_pos),
             };
         }
 
@@ -292,25 +671,20 @@ impl ConstraintManager {
 
         // 1 - individual archetype lock cases (full lock regardless of other constraints)
 
-        //If angle-center archetype, return Locked if two arms are not 0 or 180 degrees
-        if !angle_center_constraints.is_empty() {
-            for acc in &angle_center_constraints {
-                let dir_1 = Edge::direction_from_handle(&dm_borrow, acc.edge_1_handle);
-                let dir_2 = Edge::direction_from_handle(&dm_borrow, acc.edge_2_handle);
-
-                let delta = dir_2 - dir_1;
-                let angle = delta.y.atan2(delta.x); // [-pi to pi]
-
-                if angle.abs() > 0.001 || //effectively 0 degs
-                (f32::consts::PI - angle).abs() > 0.001
-                // effectively 180 degs
-                {
-                    return SolverResponse {
-                        state: SolverState::Locked,
-                        valid_path: None,
-                        new_pos: None,
-                    };
-                }
+        //If angle-center archetype, return Locked if both outer vertices sit on top of each other
+        // since every pivot position would then see the same (undefined) angle
+        for acc in &angle_center_constraints {
+            let outer_1 = dm_borrow
+                .get_vertex(acc.edge_1_outer_vert_handle)
+                .unwrap()
+                .position;
+            let outer_2 = dm_borrow
+                .get_vertex(acc.edge_2_outer_vert_handle)
+                .unwrap()
+                .position;
+
+            if outer_1.distance(outer_2) < 0.001 {
+                return SolverResponse::locked();
             }
         }
 
@@ -329,16 +703,18 @@ impl ConstraintManager {
             };
 
             let other_vertex = dm_borrow.get_vertex(other_vh).unwrap();
-            let path_radius = fixed_pos.distance(other_vertex.position);
 
             let path = Circle {
                 origin: other_vertex.position,
-                radius: path_radius,
+                radius: lc.length,
             };
 
             constraint_paths.push(ConstraintPath::Circle(path));
         }
 
+        // None for an edge that has collapsed to a point, it has no direction to follow.
+        // a drag can easily pull one endpoint onto the other, so the paths built from such
+        // an edge are skipped until it opens up again
         let line_data_generator = |center_vh: VertexHandle, end_vh: VertexHandle| {
             let end_vertex = dm_borrow.get_vertex(end_vh).unwrap();
             let center_vertex = dm_borrow.get_vertex(center_vh).unwrap();
@@ -346,30 +722,76 @@ impl ConstraintManager {
             let line_dir = end_vertex.position - center_vertex.position;
 
             if line_dir.length() < 0.01 {
-                panic!()
+                return None;
             }
 
             let line_dir = line_dir.normalized();
-            (center_vertex.position + line_dir * 10.0, line_dir)
+            Some((center_vertex.position + line_dir * 10.0, line_dir))
         };
 
         // 2b - angle path (ray)
         for ac in angle_end_constraints {
             // we assume here that one of the two is guaranteed
             // since we only pushed to angle_end_constraints if it was one of them
-            let (origin, direction) = line_data_generator(ac.pivot_vert_handle, vh);
-            constraint_paths.push(ConstraintPath::Ray(Ray { origin, direction }));
-        }
+            // the ray is the other arm rotated by the target angle
+            let (other_vh, rotation) = if ac.edge_2_outer_vert_handle == vh {
+                (ac.edge_1_outer_vert_handle, ac.angle)
+            } else {
+                (ac.edge_2_outer_vert_handle, -ac.angle)
+            };
+
+            let pivot = dm_borrow.get_vertex(ac.pivot_vert_handle).unwrap().position;
+            let other_arm = dm_borrow.get_vertex(other_vh).unwrap().position - pivot;
+
+            // a collapsed arm has no direction to measure the angle from, so it can't
+            // hold this vertex anywhere until it opens up again
+            if other_arm.length() < 0.01 {
+                continue;
+            }
 
-        // 2c - angle path, but only when it's 180 degrees (line)
+            let direction = rotate_vec2(other_arm.normalized(), rotation);
 
-        for ac in angle_center_constraints {
-            // we assume here that the a endpoint_1 and the pivot point
-            // are on the same line as endpoint_2 and the pivot point since we
-            // force any other condition to return locked earlier
-            let (origin, direction) = line_data_generator(ac.pivot_vert_handle, vh);
+            constraint_paths.push(ConstraintPath::Ray(Ray {
+                origin: pivot + direction * 10.0,
+                direction,
+            }));
+        }
 
-            constraint_paths.push(ConstraintPath::Line(Line { origin, direction }));
+        // 2c - angle path at the pivot
+        // with both outer vertices held, the pivot keeps its angle anywhere on the
+        // inscribed angle circle through them (a line when the angle is 0 or 180 degrees)
+
+        for ac in &angle_center_constraints {
+            let outer_1 = dm_borrow
+                .get_vertex(ac.edge_1_outer_vert_handle)
+                .unwrap()
+                .position;
+            let outer_2 = dm_borrow
+                .get_vertex(ac.edge_2_outer_vert_handle)
+                .unwrap()
+                .position;
+
+            let chord = outer_2 - outer_1;
+            let half_chord = chord.length() * 0.5;
+            let chord_dir = chord.normalized();
+
+            let sin_angle = ac.angle.sin();
+
+            if sin_angle.abs() < 0.001 {
+                constraint_paths.push(ConstraintPath::Line(Line {
+                    origin: outer_1,
+                    direction: chord_dir,
+                }));
+            } else {
+                // center sits on the perpendicular bisector of the chord
+                let perp_dir = rotate_vec2(chord_dir, f32::consts::FRAC_PI_2);
+                let center_offset = half_chord * ac.angle.cos() / sin_angle;
+
+                constraint_paths.push(ConstraintPath::Circle(Circle {
+                    origin: outer_1 + chord * 0.5 + perp_dir * center_offset,
+                    radius: half_chord / sin_angle.abs(),
+                }));
+            }
         }
 
         // 2d - Parallel path (Line)
@@ -378,135 +800,942 @@ impl ConstraintManager {
             let edge_1 = dm_borrow.get_edge(pc.edge_1_handle).unwrap();
             let edge_2 = dm_borrow.get_edge(pc.edge_2_handle).unwrap();
 
-            if edge_1.start_point_vh == vh || edge_1.end_point_vh == vh {
-                let (origin, direction) =
-                    line_data_generator(edge_1.start_point_vh, edge_1.end_point_vh);
-                constraint_paths.push(ConstraintPath::Line(Line { origin, direction }));
+            let line_data = if edge_1.start_point_vh == vh || edge_1.end_point_vh == vh {
+                line_data_generator(edge_1.start_point_vh, edge_1.end_point_vh)
             } else {
-                let (origin, direction) =
-                    line_data_generator(edge_2.start_point_vh, edge_2.end_point_vh);
+                line_data_generator(edge_2.start_point_vh, edge_2.end_point_vh)
+            };
+
+            if let Some((origin, direction)) = line_data {
                 constraint_paths.push(ConstraintPath::Line(Line { origin, direction }));
             }
         }
 
-        // 3 calculate path intersections
-        let valid_path = intersect_paths(constraint_paths);
+        // 2e - Perpendicular path (Line, or Circle when both edges meet at the vertex)
 
-        match valid_path {
-            Some(vp) => {
-                let adjusted_pt = vp.closest_point(&try_pos);
-                return SolverResponse {
-                    state: SolverState::Partial,
-                    valid_path: Some(vp),
-                    new_pos: Some(adjusted_pt),
-                };
+        let other_end_vh = |edge: &Edge| {
+            if edge.start_point_vh == vh {
+                edge.end_point_vh
+            } else {
+                edge.start_point_vh
             }
-            None => {
-                return SolverResponse {
-                    state: SolverState::Locked,
-                    valid_path: None,
-                    new_pos: None,
+        };
+
+        for pc in perpendicular_end_constraints {
+            let edge_1 = dm_borrow.get_edge(pc.edge_1_handle).unwrap();
+            let edge_2 = dm_borrow.get_edge(pc.edge_2_handle).unwrap();
+
+            let on_edge_1 = edge_1.start_point_vh == vh || edge_1.end_point_vh == vh;
+            let on_edge_2 = edge_2.start_point_vh == vh || edge_2.end_point_vh == vh;
+
+            if on_edge_1 && on_edge_2 {
+                // corner vertex -- every point on the circle over the two outer
+                // vertices sees them at a right angle (Thales)
+                let outer_1 = dm_borrow.get_vertex(other_end_vh(edge_1)).unwrap().position;
+                let outer_2 = dm_borrow.get_vertex(other_end_vh(edge_2)).unwrap().position;
+
+                constraint_paths.push(ConstraintPath::Circle(Circle {
+                    origin: outer_1.lerp(outer_2, 0.5),
+                    radius: outer_1.distance(outer_2) * 0.5,
+                }));
+            } else {
+                // the vertex slides along the normal of the other edge,
+                // through the far end of its own edge
+                let (own_edge, other_edge) = if on_edge_1 {
+                    (edge_1, edge_2)
+                } else {
+                    (edge_2, edge_1)
                 };
+
+                let other_direction =
+                    match line_data_generator(other_edge.start_point_vh, other_edge.end_point_vh) {
+                        Some((_, direction)) => direction,
+                        None => continue,
+                    };
+                let anchor = dm_borrow
+                    .get_vertex(other_end_vh(own_edge))
+                    .unwrap()
+                    .position;
+
+                constraint_paths.push(ConstraintPath::Line(Line {
+                    origin: anchor,
+                    direction: rotate_vec2(other_direction, f32::consts::FRAC_PI_2),
+                }));
             }
         }
-    }
 
-    // pub fn generate_vertex_paths(
-    //     &self,
-    //     vh: VertexHandle,
-    //     constraints_to_ignore: &Vec<ConstraintHandle>,
-    // ) -> Vec<ConstraintPath> {
+        // 2f - Horizontal / Vertical path (axis aligned Line through the other endpoint)
 
-    //     let dm_shared = if let Some(v) = &self.drawing_manager {
-    //         v
-    //     } else {
-    //         panic!();
-    //     };
+        for (ac, direction) in axis_end_constraints {
+            let edge = dm_borrow.get_edge(ac.edge_handle).unwrap();
+            let anchor = dm_borrow.get_vertex(other_end_vh(edge)).unwrap().position;
 
-    //     let dm_borrow = dm_shared.borrow();
+            constraint_paths.push(ConstraintPath::Line(Line {
+                origin: anchor,
+                direction,
+            }));
+        }
 
-    //     let mut length_end_constraints: Vec<&LengthConstraint> = vec![];
-    //     let mut angle_center_constraints: Vec<&AngleConstraint> = vec![];
-    //     let mut angle_end_constraints: Vec<&AngleConstraint> = vec![];
-    //     let mut parallel_end_constraints: Vec<&ParallelConstraint> = vec![];
+        // 2g - Coincident path. The partner normally just follows (see
+        // solve_coincident_followers), only a fixed partner pins this vertex onto it
 
-    //     //find constraints associated with vertex
-    //     for (ch, constraint) in &self.constraint_map {
-    //         if constraints_to_ignore.contains(&ch) {
-    //             continue;
-    //         }
-    //         match constraint {
-    //             Constraint::LENGTH(length_constraint) => {
-    //                 let edge = dm_borrow.get_edge(length_constraint.edge_handle).unwrap();
+        for cc in coincident_constraints {
+            let other_vh = if cc.vertex_1_handle == vh {
+                cc.vertex_2_handle
+            } else {
+                cc.vertex_1_handle
+            };
 
-    //                 if edge.end_point_vh == vh || edge.start_point_vh == vh {
-    //                     length_end_constraints.push(length_constraint);
-    //                 }
-    //             }
-    //             Constraint::ANGLE(angle_constraint) => {
-    //                 if angle_constraint.pivot_vert_handle == vh {
-    //                     angle_center_constraints.push(angle_constraint);
-    //                 } else if angle_constraint.edge_1_outer_vert_handle == vh
-    //                     || angle_constraint.edge_2_outer_vert_handle == vh
-    //                 {
-    //                     angle_end_constraints.push(angle_constraint);
-    //                 }
-    //             }
-    //             Constraint::PARALLEL(parallel_constraint) => {
-    //                 let edge_1 = dm_borrow
-    //                     .get_edge(parallel_constraint.edge_1_handle)
-    //                     .unwrap();
-    //                 let edge_2 = dm_borrow
-    //                     .get_edge(parallel_constraint.edge_2_handle)
-    //                     .unwrap();
+            if !self.is_vertex_fixed(other_vh) {
+                continue;
+            }
 
-    //                 if edge_1.end_point_vh == vh
-    //                     || edge_1.start_point_vh == vh
-    //                     || edge_2.end_point_vh == vh
-    //                     || edge_2.start_point_vh == vh
-    //                 {
-    //                     parallel_end_constraints.push(parallel_constraint);
-    //                 }
-    //             }
-    //         }
-    //     }
+            let origin = dm_borrow.get_vertex(other_vh).unwrap().position;
+            constraint_paths.push(ConstraintPath::Point(Point { origin }));
+        }
 
-    //     if length_end_constraints.is_empty()
-    //         && angle_center_constraints.is_empty()
-    //         && angle_end_constraints.is_empty()
-    //         && parallel_end_constraints.is_empty()
-    //     {
-    //         return SolverResponse {
-    //             state: SolverState::Free,
-    //             valid_path: None,
-    //             new_pos: Some(try_pos.clone()),
-    //         };
-    //     }
-    // }
+        // 2h - Point on edge path (Segment when dragging the point, Ray when dragging an endpoint)
 
-    // TODO add solver check for collision on existing constraints
-    pub fn add_length_constraint(
-        &mut self,
-        eh: EdgeHandle,
+        for pc in point_on_edge_constraints {
+            let edge = dm_borrow.get_edge(pc.edge_handle).unwrap();
+            let start_pt = dm_borrow.get_vertex(edge.start_point_vh).unwrap().position;
+            let end_pt = dm_borrow.get_vertex(edge.end_point_vh).unwrap().position;
+
+            if pc.vertex_handle == vh {
+                constraint_paths.push(ConstraintPath::Segment(Segment {
+                    start: start_pt,
+                    end: end_pt,
+                }));
+            } else {
+                // the endpoint can only move away from the point along the edge's line,
+                // past the point so that it stays within the segment
+                let anchor = dm_borrow.get_vertex(other_end_vh(edge)).unwrap().position;
+                let point = dm_borrow.get_vertex(pc.vertex_handle).unwrap().position;
+
+                let direction = point - anchor;
+                if direction.length() < 0.001 {
+                    // the point sits on the other endpoint, any position keeps it on the edge
+                    continue;
+                }
+
+                constraint_paths.push(ConstraintPath::Ray(Ray {
+                    origin: point,
+                    direction: direction.normalized(),
+                }));
+            }
+        }
+
+        // 2i - Equal length path (Circle sized by the other edge, or the
+        // perpendicular bisector Line when the vertex is shared by both edges)
+
+        let edge_has_vertex = |edge: &Edge| edge.start_point_vh == vh || edge.end_point_vh == vh;
+
+        for elc in equal_length_end_constraints {
+            let edge_1 = dm_borrow.get_edge(elc.edge_1_handle).unwrap();
+            let edge_2 = dm_borrow.get_edge(elc.edge_2_handle).unwrap();
+
+            if edge_has_vertex(edge_1) && edge_has_vertex(edge_2) {
+                let outer_1 = dm_borrow.get_vertex(other_end_vh(edge_1)).unwrap().position;
+                let outer_2 = dm_borrow.get_vertex(other_end_vh(edge_2)).unwrap().position;
+
+                if outer_1.distance(outer_2) < 0.001 {
+                    // both far ends sit together, every position gives equal lengths
+                    continue;
+                }
+
+                constraint_paths.push(ConstraintPath::Line(Line {
+                    origin: outer_1.lerp(outer_2, 0.5),
+                    direction: rotate_vec2(
+                        (outer_2 - outer_1).normalized(),
+                        f32::consts::FRAC_PI_2,
+                    ),
+                }));
+                continue;
+            }
+
+            let (own_edge, other_edge) = if edge_has_vertex(edge_1) {
+                (edge_1, edge_2)
+            } else {
+                (edge_2, edge_1)
+            };
+
+            let origin = dm_borrow
+                .get_vertex(other_end_vh(own_edge))
+                .unwrap()
+                .position;
+            let radius = dm_borrow
+                .get_vertex(other_edge.start_point_vh)
+                .unwrap()
+                .position
+                .distance(
+                    dm_borrow
+                        .get_vertex(other_edge.end_point_vh)
+                        .unwrap()
+                        .position,
+                );
+
+            constraint_paths.push(ConstraintPath::Circle(Circle { origin, radius }));
+        }
+
+        // 2j - Symmetric path. The mirrored partner normally just follows (see
+        // solve_symmetric_followers), only a fixed partner pins this vertex to its reflection
+
+        for sc in symmetric_constraints {
+            let partner_vh = if sc.vertex_1_handle == vh {
+                sc.vertex_2_handle
+            } else {
+                sc.vertex_1_handle
+            };
+
+            if !self.is_vertex_fixed(partner_vh) {
+                continue;
+            }
+
+            let axis = dm_borrow.get_edge(sc.axis_edge_handle).unwrap();
+            let origin = reflect_across_line(
+                dm_borrow.get_vertex(partner_vh).unwrap().position,
+                dm_borrow.get_vertex(axis.start_point_vh).unwrap().position,
+                dm_borrow.get_vertex(axis.end_point_vh).unwrap().position,
+            );
+
+            constraint_paths.push(ConstraintPath::Point(Point { origin }));
+        }
+
+        // 2k - Point to point distance path (Circle around the other vertex)
+
+        for dc in distance_constraints {
+            let other_vh = if dc.vertex_1_handle == vh {
+                dc.vertex_2_handle
+            } else {
+                dc.vertex_1_handle
+            };
+
+            constraint_paths.push(ConstraintPath::Circle(Circle {
+                origin: dm_borrow.get_vertex(other_vh).unwrap().position,
+                radius: dc.distance,
+            }));
+        }
+
+        // 2l - Point to edge distance path, offset Line when dragging the point
+
+        for edc in edge_distance_point_constraints {
+            let edge = dm_borrow.get_edge(edc.edge_handle).unwrap();
+            let start_pt = dm_borrow.get_vertex(edge.start_point_vh).unwrap().position;
+            let end_pt = dm_borrow.get_vertex(edge.end_point_vh).unwrap().position;
+
+            if start_pt.distance(end_pt) < 0.001 {
+                return SolverResponse::locked();
+            }
+
+            let direction = (end_pt - start_pt).normalized();
+            let normal = rotate_vec2(direction, f32::consts::FRAC_PI_2);
+
+            constraint_paths.push(ConstraintPath::Line(Line {
+                origin: start_pt + normal * edc.distance,
+                direction,
+            }));
+        }
+
+        // 2m - Point to edge distance path when dragging an edge endpoint. The edge has to
+        // stay tangent to the circle around the point, on the same side, so the endpoint
+        // runs along one of the tangent rays out of the other endpoint
+
+        for edc in edge_distance_end_constraints {
+            let edge = dm_borrow.get_edge(edc.edge_handle).unwrap();
+            let anchor_vh = other_end_vh(edge);
+            let anchor = dm_borrow.get_vertex(anchor_vh).unwrap().position;
+            let point = dm_borrow.get_vertex(edc.vertex_handle).unwrap().position;
+
+            let to_point = point - anchor;
+            let radius = edc.distance.abs();
+
+            if to_point.length() < radius {
+                // the anchor sits inside the circle, no edge through it can keep the distance
+                return SolverResponse::locked();
+            }
+
+            let tangent_angle = if to_point.length() < 0.001 {
+                0.0
+            } else {
+                (radius / to_point.length()).asin()
+            };
+            let to_point_dir = to_point.normalized();
+
+            let signed_distance_from = |vertex_pos: Pos2| {
+                let (start_pt, end_pt) = if edge.start_point_vh == vh {
+                    (vertex_pos, anchor)
+                } else {
+                    (anchor, vertex_pos)
+                };
+                let direction = (end_pt - start_pt).normalized();
+                direction.x * (point - start_pt).y - direction.y * (point - start_pt).x
+            };
+
+            // keep the tangent ray on the constrained side that lands closest to the cursor
+            let tangent_ray = [tangent_angle, -tangent_angle]
+                .iter()
+                .flat_map(|angle| {
+                    let direction = rotate_vec2(to_point_dir, *angle);
+                    [direction, -direction]
+                })
+                .filter(|direction| {
+                    (signed_distance_from(anchor + *direction * 10.0) - edc.distance).abs() < 0.01
+                })
+                .map(|direction| Ray {
+                    origin: anchor,
+                    direction,
+                })
+                .min_by(|r1, r2| {
+                    r1.closest_point(try_pos)
+                        .distance_sq(*try_pos)
+                        .total_cmp(&r2.closest_point(try_pos).distance_sq(*try_pos))
+                });
+
+            match tangent_ray {
+                Some(ray) => constraint_paths.push(ConstraintPath::Ray(ray)),
+                None => return SolverResponse::locked(),
+            }
+        }
+
+        // partners that follow along and degenerate cases leave no path, nothing holds
+        // the vertex back then
+        if constraint_paths.is_empty() {
+            return SolverResponse {
+                state: SolverState::Free,
+                valid_path: None,
+                new_pos: Some(*try_pos),
+            };
+        }
+
+        // 3 calculate path intersections
+        let valid_path = intersect_paths(constraint_paths, try_pos);
+
+        match valid_path {
+            Some(vp) => {
+                let adjusted_pt = vp.closest_point(try_pos);
+
+                // the pivot circle also holds the positions that flip the angle to the
+                // other side, so only accept the arc that keeps the signed angle
+                for ac in &angle_center_constraints {
+                    let outer_1 = dm_borrow
+                        .get_vertex(ac.edge_1_outer_vert_handle)
+                        .unwrap()
+                        .position;
+                    let outer_2 = dm_borrow
+                        .get_vertex(ac.edge_2_outer_vert_handle)
+                        .unwrap()
+                        .position;
+
+                    let angle_error = signed_angle(
+                        rotate_vec2(outer_1 - adjusted_pt, ac.angle),
+                        outer_2 - adjusted_pt,
+                    );
+
+                    if angle_error.abs() > 0.01 {
+                        return SolverResponse::locked();
+                    }
+                }
+
+                SolverResponse {
+                    state: SolverState::Partial,
+                    valid_path: Some(vp),
+                    new_pos: Some(adjusted_pt),
+                }
+            }
+            None => SolverResponse {
+                state: SolverState::Locked,
+                valid_path: None,
+                new_pos: None,
+            },
+        }
+    }
+
+    // solves every constraint at once, holding the pinned vertices at the given positions
+    // and letting the rest of the sketch move to keep the constraints satisfied
+    pub fn solve_global(&self, pinned: &[(VertexHandle, Pos2)]) -> GlobalSolverResponse {
+        let dm_shared = if let Some(v) = &self.drawing_manager {
+            v
+        } else {
+            return GlobalSolverResponse::default();
+        };
+
+        let dm_borrow = dm_shared.borrow();
+
+        // fixed vertices are pinned where they are, and can't be dragged away
+        let mut all_pinned = pinned.to_vec();
+        for constraint in self.constraint_map.values() {
+            if let Constraint::FIXED(fc) = constraint {
+                let position = dm_borrow.get_vertex(fc.vertex_handle).unwrap().position;
+
+                match pinned.iter().find(|(vh, _)| *vh == fc.vertex_handle) {
+                    Some((_, p)) if p.distance(position) > 0.001 => {
+                        return GlobalSolverResponse::default();
+                    }
+                    Some(_) => (),
+                    None => all_pinned.push((fc.vertex_handle, position)),
+                }
+            }
+        }
+
+        global_solver::solve(
+            self.constraint_map.values(),
+            &dm_borrow,
+            &all_pinned,
+            &GlobalSolverSettings::default(),
+        )
+    }
+
+    // moves the group as one rigid body, every vertex in it by the same delta from where
+    // it started. that keeps every constraint within the group satisfied, so only the ones
+    // reaching outside it need solving, and the rest of the sketch moves to keep those.
+    // when the rest can't follow all the way the group stops as far along as it can get
+    pub fn solve_for_group(
+        &self,
+        start_positions: &BTreeMap<VertexHandle, Pos2>,
+        delta: Vec2,
+    ) -> GroupSolverResponse {
+        if start_positions.keys().any(|vh| self.is_vertex_fixed(*vh)) {
+            return GroupSolverResponse::locked();
+        }
+
+        let moved_group = |t: f32| -> Vec<(VertexHandle, Pos2)> {
+            start_positions
+                .iter()
+                .map(|(vh, pos)| (*vh, *pos + delta * t))
+                .collect()
+        };
+
+        if !self.reaches_outside(start_positions) {
+            return GroupSolverResponse {
+                state: SolverState::Free,
+                new_positions: moved_group(1.0).into_iter().collect(),
+            };
+        }
+
+        // the solver only reports the vertices its constraints touch
+        let solve_moved = |t: f32| -> Option<BTreeMap<VertexHandle, Pos2>> {
+            let pinned = moved_group(t);
+            let global_response = self.solve_global(&pinned);
+            if !global_response.converged {
+                return None;
+            }
+
+            let mut new_positions: BTreeMap<VertexHandle, Pos2> = pinned.into_iter().collect();
+            new_positions.extend(global_response.new_positions);
+            Some(new_positions)
+        };
+
+        if let Some(new_positions) = solve_moved(1.0) {
+            return GroupSolverResponse {
+                state: SolverState::Free,
+                new_positions,
+            };
+        }
+
+        // bisect for the largest part of the move the rest of the sketch can follow
+        let mut best_positions = None;
+        let (mut low, mut high) = (0.0, 1.0);
+        for _ in 0..GROUP_BISECTION_STEPS {
+            let t = (low + high) / 2.0;
+            match solve_moved(t) {
+                Some(new_positions) => {
+                    low = t;
+                    best_positions = Some(new_positions);
+                }
+                None => high = t,
+            }
+        }
+
+        match best_positions {
+            Some(new_positions) => GroupSolverResponse {
+                state: SolverState::Partial,
+                new_positions,
+            },
+            None => GroupSolverResponse::locked(),
+        }
+    }
+
+    // whether any constraint on the group also involves vertices outside of it
+    fn reaches_outside(&self, group: &BTreeMap<VertexHandle, Pos2>) -> bool {
+        let dm_borrowed = self.drawing_manager.as_ref().unwrap().borrow();
+
+        group.keys().any(|vh| {
+            self.get_vertex_constraints(*vh).iter().any(|ch| {
+                indexed_vertices(&self.constraint_map[ch], &dm_borrowed)
+                    .iter()
+                    .any(|other_vh| !group.contains_key(other_vh))
+            })
+        })
+    }
+
+    // a constraint can only be added if the sketch can still satisfy it along with every
+    // existing constraint, and if the geometry it constrains isn't already locked.
+    // returns where that puts every vertex the solve touched
+    fn check_conflicts(
+        &self,
+        candidate: &Constraint,
+        dm: &DrawingManager,
+    ) -> Result<BTreeMap<VertexHandle, Pos2>, ConstraintError> {
+        let pinned: Vec<(VertexHandle, Pos2)> = self
+            .constraint_map
+            .values()
+            .filter_map(|c| match c {
+                Constraint::FIXED(fc) => Some((
+                    fc.vertex_handle,
+                    dm.get_vertex(fc.vertex_handle).unwrap().position,
+                )),
+                _ => None,
+            })
+            .collect();
+
+        let existing: Vec<(&ConstraintHandle, &Constraint)> = self.constraint_map.iter().collect();
+
+        match global_solver::find_conflicts(
+            &existing,
+            candidate,
+            dm,
+            &pinned,
+            &GlobalSolverSettings::default(),
+        ) {
+            Ok(global_response) => Ok(global_response.new_positions),
+            // nothing else to blame, the solver just couldn't get there
+            Err(clashing) if clashing.is_empty() => Err(ConstraintError::Unsolvable),
+            Err(clashing) => Err(ConstraintError::Conflict(clashing)),
+        }
+    }
+
+    // stores a checked constraint and records it against every vertex and edge it touches
+    fn insert_constraint(&mut self, constraint: Constraint) -> ConstraintHandle {
+        let ch = self.constraint_allocator.allocate();
+        self.store_constraint(ch, constraint);
+        ch
+    }
+
+    fn store_constraint(&mut self, ch: ConstraintHandle, constraint: Constraint) {
+        let dm_shared = self.drawing_manager.as_ref().unwrap();
+        let dm_borrowed = dm_shared.borrow();
+
+        for eh in constraint.edge_handles() {
+            self.edge_constraints.entry(eh).or_default().push(ch);
+        }
+        for vh in indexed_vertices(&constraint, &dm_borrowed) {
+            self.vertex_constraints.entry(vh).or_default().push(ch);
+        }
+
+        self.constraint_map.insert(ch, constraint);
+    }
+
+    pub fn get_all_constraint_handles(&self) -> Vec<ConstraintHandle> {
+        self.constraint_map.keys().copied().collect()
+    }
+
+    pub fn to_record(&self) -> ConstraintManagerRecord {
+        ConstraintManagerRecord {
+            constraints: self
+                .constraint_map
+                .iter()
+                .map(|(ch, constraint)| ConstraintRecord {
+                    handle: *ch,
+                    constraint: constraint.clone(),
+                })
+                .collect(),
+            constraint_allocator: self.constraint_allocator.clone(),
+            solver_mode: self.solver_mode,
+        }
+    }
+
+    // replaces every constraint with the saved ones. the drawing manager has to be loaded
+    // first, and the constraints go back in as they were without being checked or solved
+    pub fn load_record(&mut self, record: ConstraintManagerRecord) -> Result<(), ConstraintError> {
+        if !record.constraint_allocator.is_valid() {
+            return Err(ConstraintError::ConstraintNotAdded);
+        }
+
+        {
+            let dm_borrowed = self.drawing_manager.as_ref().unwrap().borrow();
+
+            let mut seen = BTreeSet::new();
+            for cr in &record.constraints {
+                if !record.constraint_allocator.is_live(cr.handle) || !seen.insert(cr.handle) {
+                    return Err(ConstraintError::ConstraintNotAdded);
+                }
+                check_references(&cr.constraint, &dm_borrowed)?;
+            }
+        }
+
+        self.constraint_map.clear();
+        self.vertex_constraints.clear();
+        self.edge_constraints.clear();
+
+        for cr in record.constraints {
+            self.store_constraint(cr.handle, cr.constraint);
+        }
+        self.constraint_allocator = record.constraint_allocator;
+        self.solver_mode = record.solver_mode;
+
+        Ok(())
+    }
+
+    // puts a constraint in exactly as it is, without checking it against the others or
+    // moving any geometry to suit it. for constraints coming back from a file
+    pub fn restore_constraint(
+        &mut self,
+        constraint: Constraint,
+    ) -> Result<ConstraintHandle, ConstraintError> {
+        check_references(
+            &constraint,
+            &self.drawing_manager.as_ref().unwrap().borrow(),
+        )?;
+        Ok(self.insert_constraint(constraint))
+    }
+
+    // puts a removed constraint back under its old handle, for undoing the removal
+    pub fn reinstate_constraint(
+        &mut self,
+        ch: ConstraintHandle,
+        constraint: Constraint,
+    ) -> Result<(), ConstraintError> {
+        check_references(
+            &constraint,
+            &self.drawing_manager.as_ref().unwrap().borrow(),
+        )?;
+        if !self.constraint_allocator.restore(ch) {
+            return Err(ConstraintError::ConstraintNotAdded);
+        }
+        self.store_constraint(ch, constraint);
+        Ok(())
+    }
+
+    pub fn remove_constraint(
+        &mut self,
+        ch: ConstraintHandle,
+    ) -> Result<DeletionReport, ConstraintError> {
+        let dm_shared = self.drawing_manager.as_ref().unwrap();
+        let dm_borrowed = dm_shared.borrow();
+
+        let constraint = self
+            .constraint_map
+            .remove(&ch)
+            .ok_or(ConstraintError::ConstraintNotFound(ch))?;
+        self.constraint_allocator.release(ch);
+
+        for eh in constraint.edge_handles() {
+            unindex(&mut self.edge_constraints, eh, ch);
+        }
+        for vh in indexed_vertices(&constraint, &dm_borrowed) {
+            unindex(&mut self.vertex_constraints, vh, ch);
+        }
+
+        Ok(DeletionReport {
+            constraints: vec![(ch, constraint)],
+            ..Default::default()
+        })
+    }
+
+    // removes the edge and every constraint that refers to it
+    pub fn remove_edge(&mut self, eh: EdgeHandle) -> Result<DeletionReport, ConstraintError> {
+        if !self
+            .drawing_manager
+            .as_ref()
+            .unwrap()
+            .borrow()
+            .has_edge(&eh)
+        {
+            return Err(ConstraintError::EdgeNotFound(eh));
+        }
+
+        let mut report = self.remove_constraints(self.get_edge_constraints(eh).to_vec());
+
+        let dm_shared = self.drawing_manager.as_ref().unwrap();
+        let edge = dm_shared.borrow_mut().remove_edge(eh).unwrap();
+        report.edges.push((eh, edge));
+
+        Ok(report)
+    }
+
+    // removes the vertex, its edges and every constraint that refers to any of them
+    pub fn remove_vertex(&mut self, vh: VertexHandle) -> Result<DeletionReport, ConstraintError> {
+        if !self
+            .drawing_manager
+            .as_ref()
+            .unwrap()
+            .borrow()
+            .has_vertex(&vh)
+        {
+            return Err(ConstraintError::VertexNotFound(vh));
+        }
+
+        // the vertex index already covers the constraints on its edges
+        let mut report = self.remove_constraints(self.get_vertex_constraints(vh).to_vec());
+
+        let dm_shared = self.drawing_manager.as_ref().unwrap();
+        let (vertex, edges) = dm_shared.borrow_mut().remove_vertex(vh).unwrap();
+        report.edges.extend(edges);
+        report.vertices.push((vh, vertex));
+
+        Ok(report)
+    }
+
+    fn remove_constraints(&mut self, handles: Vec<ConstraintHandle>) -> DeletionReport {
+        let mut report = DeletionReport::default();
+        for ch in handles {
+            report
+                .constraints
+                .extend(self.remove_constraint(ch).unwrap().constraints);
+        }
+
+        report
+    }
+
+    // remaining degrees of freedom per vertex and for the whole sketch,
+    // along with the constraints that are redundant or conflicting
+    pub fn analyze_dof(&self) -> DofAnalysis {
+        let dm_shared = if let Some(v) = &self.drawing_manager {
+            v
+        } else {
+            return DofAnalysis::default();
+        };
+
+        global_solver::analyze(
+            self.constraint_map.iter(),
+            &dm_shared.borrow(),
+            &GlobalSolverSettings::default(),
+        )
+    }
+
+    // pub fn generate_vertex_paths(
+    //     &self,
+    //     vh: VertexHandle,
+    //     constraints_to_ignore: &Vec<ConstraintHandle>,
+    // ) -> Vec<ConstraintPath> {
+
+    //     let dm_shared = if let Some(v) = &self.drawing_manager {
+    //         v
+    //     } else {
+    //         panic!();
+    //     };
+
+    //     let dm_borrow = dm_shared.borrow();
+
+    //     let mut length_end_constraints: Vec<&LengthConstraint> = vec![];
+    //     let mut angle_center_constraints: Vec<&AngleConstraint> = vec![];
+    //     let mut angle_end_constraints: Vec<&AngleConstraint> = vec![];
+    //     let mut parallel_end_constraints: Vec<&ParallelConstraint> = vec![];
+
+    //     //find constraints associated with vertex
+    //     for (ch, constraint) in &self.constraint_map {
+    //         if constraints_to_ignore.contains(&ch) {
+    //             continue;
+    //         }
+    //         match constraint {
+    //             Constraint::LENGTH(length_constraint) => {
+    //                 let edge = dm_borrow.get_edge(length_constraint.edge_handle).unwrap();
+
+    //                 if edge.end_point_vh == vh || edge.start_point_vh == vh {
+    //                     length_end_constraints.push(length_constraint);
+    //                 }
+    //             }
+    //             Constraint::ANGLE(angle_constraint) => {
+    //                 if angle_constraint.pivot_vert_handle == vh {
+    //                     angle_center_constraints.push(angle_constraint);
+    //                 } else if angle_constraint.edge_1_outer_vert_handle == vh
+    //                     || angle_constraint.edge_2_outer_vert_handle == vh
+    //                 {
+    //                     angle_end_constraints.push(angle_constraint);
+    //                 }
+    //             }
+    //             Constraint::PARALLEL(parallel_constraint) => {
+    //                 let edge_1 = dm_borrow
+    //                     .get_edge(parallel_constraint.edge_1_handle)
+    //                     .unwrap();
+    //                 let edge_2 = dm_borrow
+    //                     .get_edge(parallel_constraint.edge_2_handle)
+    //                     .unwrap();
+
+    //                 if edge_1.end_point_vh == vh
+    //                     || edge_1.start_point_vh == vh
+    //                     || edge_2.end_point_vh == vh
+    //                     || edge_2.start_point_vh == vh
+    //                 {
+    //                     parallel_end_constraints.push(parallel_constraint);
+    //                 }
+    //             }
+    //         }
+    //     }
+
+    //     if length_end_constraints.is_empty()
+    //         && angle_center_constraints.is_empty()
+    //         && angle_end_constraints.is_empty()
+    //         && parallel_end_constraints.is_empty()
+    //     {
+    //         return SolverResponse {
+    //             state: SolverState::Free,
+    //             valid_path: None,
+    //             new_pos: Some(try_pos.clone()),
+    //         };
+    //     }
+    // }
+
+    // sets the target length and moves the sketch to match it,
+    // the old value is kept if the sketch can't reach the new one
+    pub fn set_length_value(
+        &mut self,
+        ch: ConstraintHandle,
+        length: f32,
+    ) -> Result<(), ConstraintError> {
+        if length <= 0.0 {
+            return Err(ConstraintError::InvalidValue);
+        }
+
+        let previous = match self.constraint_map.get_mut(&ch) {
+            Some(Constraint::LENGTH(lc)) => std::mem::replace(&mut lc.length, length),
+            _ => return Err(ConstraintError::ConstraintNotFound(ch)),
+        };
+
+        self.drive_geometry().inspect_err(|_| {
+            if let Some(Constraint::LENGTH(lc)) = self.constraint_map.get_mut(&ch) {
+                lc.length = previous;
+            }
+        })
+    }
+
+    // same as set_length_value, for point to point and point to edge distances.
+    // a point to edge distance keeps the vertex on the side of the edge it's on now
+    pub fn set_distance_value(
+        &mut self,
+        ch: ConstraintHandle,
+        distance: f32,
+    ) -> Result<(), ConstraintError> {
+        if distance <= 0.0 {
+            return Err(ConstraintError::InvalidValue);
+        }
+
+        let previous = match self.constraint_map.get_mut(&ch) {
+            Some(Constraint::DISTANCE(dc)) => std::mem::replace(&mut dc.distance, distance),
+            Some(Constraint::EDGE_DISTANCE(edc)) => {
+                let signed_distance = if edc.distance < 0.0 {
+                    -distance
+                } else {
+                    distance
+                };
+                std::mem::replace(&mut edc.distance, signed_distance)
+            }
+            _ => return Err(ConstraintError::ConstraintNotFound(ch)),
+        };
+
+        self.drive_geometry()
+            .inspect_err(|_| match self.constraint_map.get_mut(&ch) {
+                Some(Constraint::DISTANCE(dc)) => dc.distance = previous,
+                Some(Constraint::EDGE_DISTANCE(edc)) => edc.distance = previous,
+                _ => (),
+            })
+    }
+
+    // same as set_length_value, angle is in radians counterclockwise from edge_1 to edge_2
+    pub fn set_angle_value(
+        &mut self,
+        ch: ConstraintHandle,
+        angle: f32,
+    ) -> Result<(), ConstraintError> {
+        let previous = match self.constraint_map.get_mut(&ch) {
+            Some(Constraint::ANGLE(ac)) => std::mem::replace(&mut ac.angle, angle),
+            _ => return Err(ConstraintError::ConstraintNotFound(ch)),
+        };
+
+        self.drive_geometry().inspect_err(|_| {
+            if let Some(Constraint::ANGLE(ac)) = self.constraint_map.get_mut(&ch) {
+                ac.angle = previous;
+            }
+        })
+    }
+
+    pub fn set_angle_value_degrees(
+        &mut self,
+        ch: ConstraintHandle,
+        degrees: f32,
+    ) -> Result<(), ConstraintError> {
+        self.set_angle_value(ch, degrees.to_radians())
+    }
+
+    // the value of a length, distance or angle constraint as it is stored, a point to
+    // edge distance keeps its sign
+    pub fn get_constraint_value(&self, ch: ConstraintHandle) -> Result<f32, ConstraintError> {
+        match self.constraint_map.get(&ch) {
+            Some(Constraint::LENGTH(lc)) => Ok(lc.length),
+            Some(Constraint::ANGLE(ac)) => Ok(ac.angle),
+            Some(Constraint::DISTANCE(dc)) => Ok(dc.distance),
+            Some(Constraint::EDGE_DISTANCE(edc)) => Ok(edc.distance),
+            _ => Err(ConstraintError::ConstraintNotFound(ch)),
+        }
+    }
+
+    // puts back a value from get_constraint_value without moving any geometry, for
+    // undoing and redoing a value change together with the moves it made
+    pub fn restore_constraint_value(
+        &mut self,
+        ch: ConstraintHandle,
+        value: f32,
+    ) -> Result<(), ConstraintError> {
+        let stored = match self.constraint_map.get_mut(&ch) {
+            Some(Constraint::LENGTH(lc)) => &mut lc.length,
+            Some(Constraint::ANGLE(ac)) => &mut ac.angle,
+            Some(Constraint::DISTANCE(dc)) => &mut dc.distance,
+            Some(Constraint::EDGE_DISTANCE(edc)) => &mut edc.distance,
+            _ => return Err(ConstraintError::ConstraintNotFound(ch)),
+        };
+        *stored = value;
+        Ok(())
+    }
+
+    // runs the global solver with nothing pinned so the geometry
+    // catches up to changed constraint values
+    fn drive_geometry(&self) -> Result<(), ConstraintError> {
+        let global_response = self.solve_global(&[]);
+
+        if !global_response.converged {
+            return Err(ConstraintError::ValueNotReachable);
+        }
+
+        let dm_shared = self.drawing_manager.as_ref().unwrap();
+        let mut dm_borrowed = dm_shared.borrow_mut();
+
+        for (vh, pos) in global_response.new_positions {
+            dm_borrowed.get_vertex_mut(vh).unwrap().position = pos;
+        }
+
+        Ok(())
+    }
+
+    pub fn add_length_constraint(
+        &mut self,
+        eh: EdgeHandle,
     ) -> Result<ConstraintHandle, ConstraintError> {
         let dm_shared = self.drawing_manager.as_ref().unwrap();
         let dm_borrowed = dm_shared.borrow();
 
         if !dm_borrowed.has_edge(&eh) {
-            return Err(ConstraintError::ConstraintNotAdded);
+            return Err(ConstraintError::EdgeNotFound(eh));
         }
 
-        let length_constraint = LengthConstraint { edge_handle: eh };
+        let edge = dm_borrowed.get_edge(eh).unwrap();
+        let length = dm_borrowed
+            .get_vertex(edge.start_point_vh)
+            .unwrap()
+            .position
+            .distance(dm_borrowed.get_vertex(edge.end_point_vh).unwrap().position);
 
-        let next_id = get_next_id(&self.constraint_map);
+        let length_constraint = LengthConstraint {
+            edge_handle: eh,
+            length,
+        };
 
-        self.constraint_map
-            .insert(next_id, Constraint::LENGTH(length_constraint));
+        let constraint = Constraint::LENGTH(length_constraint);
+        self.check_conflicts(&constraint, &dm_borrowed)?;
 
-        Ok(next_id)
+        drop(dm_borrowed);
+        Ok(self.insert_constraint(constraint))
     }
 
-    // TODO add solver check for collision on existing constraints
     pub fn add_angle_constraint(
         &mut self,
         eh_1: EdgeHandle,
@@ -516,10 +1745,10 @@ impl ConstraintManager {
         let dm_borrowed = dm_shared.borrow();
 
         if !dm_borrowed.has_edge(&eh_1) {
-            return Err(ConstraintError::ConstraintNotAdded);
+            return Err(ConstraintError::EdgeNotFound(eh_1));
         }
         if !dm_borrowed.has_edge(&eh_2) {
-            return Err(ConstraintError::ConstraintNotAdded);
+            return Err(ConstraintError::EdgeNotFound(eh_2));
         }
 
         let edge_1 = dm_borrowed.get_edge(eh_1).unwrap();
@@ -532,22 +1761,26 @@ impl ConstraintManager {
             edge_2.end_point_vh,
         )?; // forward error
 
+        let pivot_pos = dm_borrowed.get_vertex(verts.0).unwrap().position;
+        let dir_1 = dm_borrowed.get_vertex(verts.1 .0).unwrap().position - pivot_pos;
+        let dir_2 = dm_borrowed.get_vertex(verts.1 .1).unwrap().position - pivot_pos;
+
         let angle_constraint = AngleConstraint {
             pivot_vert_handle: verts.0,
             edge_1_handle: eh_1,
             edge_1_outer_vert_handle: verts.1 .0,
             edge_2_handle: eh_2,
             edge_2_outer_vert_handle: verts.1 .1,
+            angle: signed_angle(dir_1, dir_2),
         };
 
-        let next_id = get_next_id(&self.constraint_map);
-        self.constraint_map
-            .insert(next_id, Constraint::ANGLE(angle_constraint));
+        let constraint = Constraint::ANGLE(angle_constraint);
+        self.check_conflicts(&constraint, &dm_borrowed)?;
 
-        Ok(next_id)
+        drop(dm_borrowed);
+        Ok(self.insert_constraint(constraint))
     }
 
-    // TODO add solver check for collision on existing constraints
     pub fn add_parallel_constraint(
         &mut self,
         edge_1_handle: EdgeHandle,
@@ -557,45 +1790,454 @@ impl ConstraintManager {
         let mut dm_borrowed = dm_shared.borrow_mut();
 
         if !dm_borrowed.has_edge(&edge_1_handle) {
-            return Err(ConstraintError::ConstraintNotAdded);
+            return Err(ConstraintError::EdgeNotFound(edge_1_handle));
         }
         if !dm_borrowed.has_edge(&edge_2_handle) {
-            return Err(ConstraintError::ConstraintNotAdded);
+            return Err(ConstraintError::EdgeNotFound(edge_2_handle));
         }
 
-        // get next Id to use
         let parallel_constraint = ParallelConstraint {
             edge_1_handle,
             edge_2_handle,
         };
 
-        let next_id = get_next_id(&self.constraint_map);
+        let constraint = Constraint::PARALLEL(parallel_constraint);
+        let new_positions = self.check_conflicts(&constraint, &dm_borrowed)?;
+
+        // turn the edges parallel the way the solver found, keeping every other constraint
+        apply_positions(&mut dm_borrowed, new_positions);
+
+        drop(dm_borrowed);
+        Ok(self.insert_constraint(constraint))
+    }
+
+    pub fn add_equal_length_constraint(
+        &mut self,
+        edge_1_handle: EdgeHandle,
+        edge_2_handle: EdgeHandle,
+    ) -> Result<ConstraintHandle, ConstraintError> {
+        let dm_shared = self.drawing_manager.as_ref().unwrap();
+        let mut dm_borrowed = dm_shared.borrow_mut();
+
+        if !dm_borrowed.has_edge(&edge_1_handle) {
+            return Err(ConstraintError::EdgeNotFound(edge_1_handle));
+        }
+        if !dm_borrowed.has_edge(&edge_2_handle) {
+            return Err(ConstraintError::EdgeNotFound(edge_2_handle));
+        }
+        if edge_1_handle == edge_2_handle {
+            return Err(ConstraintError::FullOverlap);
+        }
+
+        let edge_1 = dm_borrowed.get_edge(edge_1_handle).unwrap();
+        let (e1_start_vh, e1_end_vh) = (edge_1.start_point_vh, edge_1.end_point_vh);
+        let edge_2 = dm_borrowed.get_edge(edge_2_handle).unwrap();
+        let (e2_start_vh, e2_end_vh) = (edge_2.start_point_vh, edge_2.end_point_vh);
+
+        // two edges over the same pair of vertices are the same length already
+        let edge_1_has_vertex = |vh: VertexHandle| e1_start_vh == vh || e1_end_vh == vh;
+        if edge_1_has_vertex(e2_start_vh) && edge_1_has_vertex(e2_end_vh) {
+            return Err(ConstraintError::FullOverlap);
+        }
+
+        let e2_start = dm_borrowed.get_vertex(e2_start_vh).unwrap().position;
+        let e2_end = dm_borrowed.get_vertex(e2_end_vh).unwrap().position;
+
+        if e2_start.distance(e2_end) < 0.001 {
+            return Err(ConstraintError::DegenerateEdge);
+        }
+
+        let equal_length_constraint = EqualLengthConstraint {
+            edge_1_handle,
+            edge_2_handle,
+        };
+
+        let constraint = Constraint::EQUAL_LENGTH(equal_length_constraint);
+        let new_positions = self.check_conflicts(&constraint, &dm_borrowed)?;
+
+        // resize the edges the way the solver found, keeping every other constraint on them
+        apply_positions(&mut dm_borrowed, new_positions);
+
+        drop(dm_borrowed);
+        Ok(self.insert_constraint(constraint))
+    }
+
+    pub fn add_symmetric_constraint(
+        &mut self,
+        vh_1: VertexHandle,
+        vh_2: VertexHandle,
+        axis_edge_handle: EdgeHandle,
+    ) -> Result<ConstraintHandle, ConstraintError> {
+        let dm_shared = self.drawing_manager.as_ref().unwrap();
+        let mut dm_borrowed = dm_shared.borrow_mut();
+
+        if !dm_borrowed.has_vertex(&vh_1) {
+            return Err(ConstraintError::VertexNotFound(vh_1));
+        }
+        if !dm_borrowed.has_vertex(&vh_2) {
+            return Err(ConstraintError::VertexNotFound(vh_2));
+        }
+        if !dm_borrowed.has_edge(&axis_edge_handle) {
+            return Err(ConstraintError::EdgeNotFound(axis_edge_handle));
+        }
+        if vh_1 == vh_2 {
+            return Err(ConstraintError::FullOverlap);
+        }
+
+        let axis = dm_borrowed.get_edge(axis_edge_handle).unwrap();
+        let (axis_start_vh, axis_end_vh) = (axis.start_point_vh, axis.end_point_vh);
+
+        // the axis' own endpoints can't be mirrored across it
+        if [vh_1, vh_2].contains(&axis_start_vh) || [vh_1, vh_2].contains(&axis_end_vh) {
+            return Err(ConstraintError::FullOverlap);
+        }
+
+        let axis_start = dm_borrowed.get_vertex(axis_start_vh).unwrap().position;
+        let axis_end = dm_borrowed.get_vertex(axis_end_vh).unwrap().position;
+
+        if axis_start.distance(axis_end) < 0.001 {
+            return Err(ConstraintError::DegenerateEdge);
+        }
+
+        let symmetric_constraint = SymmetricConstraint {
+            vertex_1_handle: vh_1,
+            vertex_2_handle: vh_2,
+            axis_edge_handle,
+        };
+
+        let constraint = Constraint::SYMMETRIC(symmetric_constraint);
+        let new_positions = self.check_conflicts(&constraint, &dm_borrowed)?;
+
+        // mirror the vertices the way the solver found, a fixed one stays put and the other
+        // constraints on them and on the axis are kept
+        apply_positions(&mut dm_borrowed, new_positions);
+
+        drop(dm_borrowed);
+        Ok(self.insert_constraint(constraint))
+    }
+
+    pub fn add_distance_constraint(
+        &mut self,
+        vh_1: VertexHandle,
+        vh_2: VertexHandle,
+    ) -> Result<ConstraintHandle, ConstraintError> {
+        let dm_shared = self.drawing_manager.as_ref().unwrap();
+        let dm_borrowed = dm_shared.borrow();
+
+        if !dm_borrowed.has_vertex(&vh_1) {
+            return Err(ConstraintError::VertexNotFound(vh_1));
+        }
+        if !dm_borrowed.has_vertex(&vh_2) {
+            return Err(ConstraintError::VertexNotFound(vh_2));
+        }
+        if vh_1 == vh_2 {
+            return Err(ConstraintError::FullOverlap);
+        }
+
+        // vertices joined by an edge get a length constraint instead
+        let shares_edge = dm_borrowed
+            .get_vertex(vh_1)
+            .unwrap()
+            .edge_handles
+            .iter()
+            .any(|eh| {
+                let edge = dm_borrowed.get_edge(*eh).unwrap();
+                edge.start_point_vh == vh_2 || edge.end_point_vh == vh_2
+            });
+        if shares_edge {
+            return Err(ConstraintError::VerticesShareEdge);
+        }
+
+        let distance = dm_borrowed
+            .get_vertex(vh_1)
+            .unwrap()
+            .position
+            .distance(dm_borrowed.get_vertex(vh_2).unwrap().position);
+
+        if distance < 0.001 {
+            return Err(ConstraintError::DegenerateEdge);
+        }
+
+        let distance_constraint = DistanceConstraint {
+            vertex_1_handle: vh_1,
+            vertex_2_handle: vh_2,
+            distance,
+        };
+
+        let constraint = Constraint::DISTANCE(distance_constraint);
+        self.check_conflicts(&constraint, &dm_borrowed)?;
+
+        drop(dm_borrowed);
+        Ok(self.insert_constraint(constraint))
+    }
+
+    pub fn add_edge_distance_constraint(
+        &mut self,
+        vh: VertexHandle,
+        eh: EdgeHandle,
+    ) -> Result<ConstraintHandle, ConstraintError> {
+        let dm_shared = self.drawing_manager.as_ref().unwrap();
+        let dm_borrowed = dm_shared.borrow();
+
+        if !dm_borrowed.has_vertex(&vh) {
+            return Err(ConstraintError::VertexNotFound(vh));
+        }
+        if !dm_borrowed.has_edge(&eh) {
+            return Err(ConstraintError::EdgeNotFound(eh));
+        }
+
+        let edge = dm_borrowed.get_edge(eh).unwrap();
+        if edge.start_point_vh == vh || edge.end_point_vh == vh {
+            return Err(ConstraintError::DegenerateEdge);
+        }
+
+        let start_pt = dm_borrowed
+            .get_vertex(edge.start_point_vh)
+            .unwrap()
+            .position;
+        let end_pt = dm_borrowed.get_vertex(edge.end_point_vh).unwrap().position;
+
+        if start_pt.distance(end_pt) < 0.001 {
+            return Err(ConstraintError::DegenerateEdge);
+        }
+
+        let point = dm_borrowed.get_vertex(vh).unwrap().position;
+        let direction = (end_pt - start_pt).normalized();
+        let distance = direction.x * (point - start_pt).y - direction.y * (point - start_pt).x;
+
+        if distance.abs() < 0.001 {
+            return Err(ConstraintError::DegenerateEdge);
+        }
+
+        let edge_distance_constraint = EdgeDistanceConstraint {
+            vertex_handle: vh,
+            edge_handle: eh,
+            distance,
+        };
+
+        let constraint = Constraint::EDGE_DISTANCE(edge_distance_constraint);
+        self.check_conflicts(&constraint, &dm_borrowed)?;
+
+        drop(dm_borrowed);
+        Ok(self.insert_constraint(constraint))
+    }
+
+    pub fn add_fixed_constraint(
+        &mut self,
+        vh: VertexHandle,
+    ) -> Result<ConstraintHandle, ConstraintError> {
+        let dm_shared = self.drawing_manager.as_ref().unwrap();
+        let dm_borrowed = dm_shared.borrow();
+
+        if !dm_borrowed.has_vertex(&vh) {
+            return Err(ConstraintError::VertexNotFound(vh));
+        }
+
+        let fixed_constraint = FixedConstraint { vertex_handle: vh };
+
+        let constraint = Constraint::FIXED(fixed_constraint);
+        self.check_conflicts(&constraint, &dm_borrowed)?;
+
+        drop(dm_borrowed);
+        Ok(self.insert_constraint(constraint))
+    }
+
+    pub fn add_coincident_constraint(
+        &mut self,
+        vh_1: VertexHandle,
+        vh_2: VertexHandle,
+    ) -> Result<ConstraintHandle, ConstraintError> {
+        let dm_shared = self.drawing_manager.as_ref().unwrap();
+        let mut dm_borrowed = dm_shared.borrow_mut();
+
+        if !dm_borrowed.has_vertex(&vh_1) {
+            return Err(ConstraintError::VertexNotFound(vh_1));
+        }
+        if !dm_borrowed.has_vertex(&vh_2) {
+            return Err(ConstraintError::VertexNotFound(vh_2));
+        }
+        if vh_1 == vh_2 {
+            return Err(ConstraintError::FullOverlap);
+        }
+
+        let coincident_constraint = CoincidentConstraint {
+            vertex_1_handle: vh_1,
+            vertex_2_handle: vh_2,
+        };
+
+        let constraint = Constraint::COINCIDENT(coincident_constraint);
+        let new_positions = self.check_conflicts(&constraint, &dm_borrowed)?;
+
+        // bring the vertices together the way the solver found, a fixed one stays put and
+        // the other constraints on either of them are kept
+        apply_positions(&mut dm_borrowed, new_positions);
+
+        drop(dm_borrowed);
+        Ok(self.insert_constraint(constraint))
+    }
+
+    pub fn add_point_on_edge_constraint(
+        &mut self,
+        vh: VertexHandle,
+        eh: EdgeHandle,
+    ) -> Result<ConstraintHandle, ConstraintError> {
+        let dm_shared = self.drawing_manager.as_ref().unwrap();
+        let mut dm_borrowed = dm_shared.borrow_mut();
+
+        if !dm_borrowed.has_vertex(&vh) {
+            return Err(ConstraintError::VertexNotFound(vh));
+        }
+        if !dm_borrowed.has_edge(&eh) {
+            return Err(ConstraintError::EdgeNotFound(eh));
+        }
+
+        let edge = dm_borrowed.get_edge(eh).unwrap();
+        if edge.start_point_vh == vh || edge.end_point_vh == vh {
+            return Err(ConstraintError::DegenerateEdge);
+        }
+
+        let point_on_edge_constraint = PointOnEdgeConstraint {
+            vertex_handle: vh,
+            edge_handle: eh,
+        };
+
+        let constraint = Constraint::POINT_ON_EDGE(point_on_edge_constraint);
+        let new_positions = self.check_conflicts(&constraint, &dm_borrowed)?;
+
+        // the vertex and the edge meet wherever the solver puts them, so the constraint
+        // starts out satisfied without breaking any other
+        apply_positions(&mut dm_borrowed, new_positions);
+
+        drop(dm_borrowed);
+        Ok(self.insert_constraint(constraint))
+    }
+
+    pub fn add_horizontal_constraint(
+        &mut self,
+        eh: EdgeHandle,
+    ) -> Result<ConstraintHandle, ConstraintError> {
+        self.add_axis_constraint(eh, true)
+    }
+
+    pub fn add_vertical_constraint(
+        &mut self,
+        eh: EdgeHandle,
+    ) -> Result<ConstraintHandle, ConstraintError> {
+        self.add_axis_constraint(eh, false)
+    }
+
+    fn add_axis_constraint(
+        &mut self,
+        eh: EdgeHandle,
+        is_horizontal: bool,
+    ) -> Result<ConstraintHandle, ConstraintError> {
+        let dm_shared = self.drawing_manager.as_ref().unwrap();
+        let mut dm_borrowed = dm_shared.borrow_mut();
+
+        if !dm_borrowed.has_edge(&eh) {
+            return Err(ConstraintError::EdgeNotFound(eh));
+        }
+
+        let axis_constraint = AxisConstraint { edge_handle: eh };
+
+        let constraint = if is_horizontal {
+            Constraint::HORIZONTAL(axis_constraint)
+        } else {
+            Constraint::VERTICAL(axis_constraint)
+        };
+        let new_positions = self.check_conflicts(&constraint, &dm_borrowed)?;
+
+        // level the edge the way the solver found, keeping every other constraint on it
+        apply_positions(&mut dm_borrowed, new_positions);
+
+        drop(dm_borrowed);
+        Ok(self.insert_constraint(constraint))
+    }
+
+    pub fn add_perpendicular_constraint(
+        &mut self,
+        edge_1_handle: EdgeHandle,
+        edge_2_handle: EdgeHandle,
+    ) -> Result<ConstraintHandle, ConstraintError> {
+        let dm_shared = self.drawing_manager.as_ref().unwrap();
+        let mut dm_borrowed = dm_shared.borrow_mut();
+
+        if !dm_borrowed.has_edge(&edge_1_handle) {
+            return Err(ConstraintError::EdgeNotFound(edge_1_handle));
+        }
+        if !dm_borrowed.has_edge(&edge_2_handle) {
+            return Err(ConstraintError::EdgeNotFound(edge_2_handle));
+        }
+
+        let perpendicular_constraint = PerpendicularConstraint {
+            edge_1_handle,
+            edge_2_handle,
+        };
+
+        let constraint = Constraint::PERPENDICULAR(perpendicular_constraint);
+        let new_positions = self.check_conflicts(&constraint, &dm_borrowed)?;
+
+        // turn the edges square the way the solver found, keeping every other constraint
+        apply_positions(&mut dm_borrowed, new_positions);
+
+        drop(dm_borrowed);
+        Ok(self.insert_constraint(constraint))
+    }
+}
+
+fn apply_positions(dm: &mut DrawingManager, new_positions: BTreeMap<VertexHandle, Pos2>) {
+    for (vh, pos) in new_positions {
+        dm.get_vertex_mut(vh).unwrap().position = pos;
+    }
+}
 
-        self.constraint_map
-            .insert(next_id, Constraint::PARALLEL(parallel_constraint));
+fn check_references(constraint: &Constraint, dm: &DrawingManager) -> Result<(), ConstraintError> {
+    for eh in constraint.edge_handles() {
+        if !dm.has_edge(&eh) {
+            return Err(ConstraintError::EdgeNotFound(eh));
+        }
+    }
+    for vh in constraint.vertex_handles() {
+        if !dm.has_vertex(&vh) {
+            return Err(ConstraintError::VertexNotFound(vh));
+        }
+    }
+    Ok(())
+}
 
-        dm_borrowed
-            .get_edge_mut(edge_1_handle)
-            .unwrap()
-            .constraints
-            .push(next_id);
-        dm_borrowed
-            .get_edge_mut(edge_2_handle)
-            .unwrap()
-            .constraints
-            .push(next_id);
+// every vertex a constraint can move, its own vertices plus the ends of its edges
+fn indexed_vertices(constraint: &Constraint, dm: &DrawingManager) -> Vec<VertexHandle> {
+    let mut vhs = constraint.vertex_handles();
+    for eh in constraint.edge_handles() {
+        if let Ok(edge) = dm.get_edge(eh) {
+            vhs.push(edge.start_point_vh);
+            vhs.push(edge.end_point_vh);
+        }
+    }
+    vhs.sort();
+    vhs.dedup();
+    vhs
+}
 
-        Ok(next_id)
+fn unindex<K: Ord>(index: &mut BTreeMap<K, Vec<ConstraintHandle>>, key: K, ch: ConstraintHandle) {
+    if let Some(chs) = index.get_mut(&key) {
+        chs.retain(|h| *h != ch);
+        if chs.is_empty() {
+            index.remove(&key);
+        }
     }
 }
 
-fn intersect_paths(constraint_paths: Vec<ConstraintPath>) -> Option<ConstraintPath> {
+fn intersect_paths(
+    constraint_paths: Vec<ConstraintPath>,
+    try_pos: &Pos2,
+) -> Option<ConstraintPath> {
     // 3 calculate path intersections
     let mut valid_path: Option<ConstraintPath> = None;
 
-    // intentionally unsused right now -- points not supported yet
-    // may show up as a type of path
-    let mut _valid_points: Vec<Point> = vec![];
+    // once two paths cross, the solution collapses to a discrete set of points
+    // that the remaining paths can only filter down further
+    let mut valid_points: Vec<Point> = vec![];
 
     if !constraint_paths.is_empty() {
         valid_path = Some(constraint_paths[0].clone());
@@ -603,138 +2245,192 @@ fn intersect_paths(constraint_paths: Vec<ConstraintPath>) -> Option<ConstraintPa
 
     // once valid analytical paths are not produced, exit
 
-    for i in 0..constraint_paths.len().saturating_sub(1) {
-        if valid_path.is_none() {
-            break;
+    for next in constraint_paths.iter().skip(1) {
+        if !valid_points.is_empty() {
+            valid_points.retain(|p| next.contains_point(&p.origin));
+            continue;
         }
 
-        let current_raw = valid_path.clone().unwrap();
-        let current = &current_raw;
-        let next = &constraint_paths[i + 1];
-
-        match (current, next) {
-            (ConstraintPath::Circle(_c1), ConstraintPath::Circle(_c2)) => {
-                // TODO Handle Circle-Circle case
-            }
-            (ConstraintPath::Line(l1), ConstraintPath::Line(l2)) => {
-                let adjusted_origin_1 = l1.origin + -l1.direction * 500.0;
-                let adjusted_origin_2 = l2.origin + -l2.direction * 500.0;
-
-                let inter_result = ray_ray_intersection(
-                    &Ray {
-                        origin: adjusted_origin_1,
-                        direction: l1.direction,
-                    },
-                    &Ray {
-                        origin: adjusted_origin_2,
-                        direction: l2.direction,
-                    },
-                );
+        let current = match &valid_path {
+            Some(vp) => vp.clone(),
+            None => break,
+        };
 
-                if let Some(inter) = inter_result {
-                    _valid_points = vec![inter];
-                    valid_path = None;
-                } else {
-                    // check if they are coincident
+        (valid_path, valid_points) = intersect_path_pair(&current, next);
+    }
 
-                    let cp = l2.closest_point(&l1.origin);
-                    if cp.distance(l1.origin) < 0.001 {
-                        // on line, overlapping
-                        //naive approach -- full one would take the smallest ray, OR line segment (which isn't supported)
-                        valid_path = Some(ConstraintPath::Line(l1.clone()));
-                    } else {
-                        valid_path = None;
-                    }
-                }
-            }
-            (ConstraintPath::Ray(r1), ConstraintPath::Ray(r2)) => {
-                let inter_result = ray_ray_intersection(r1, r2);
+    if valid_path.is_some() {
+        return valid_path;
+    }
 
-                if let Some(inter) = inter_result {
-                    _valid_points = vec![inter];
-                    valid_path = None;
-                } else {
-                    // check if they are coincident
+    // snap to the surviving point closest to where the user is dragging
+    valid_points
+        .into_iter()
+        .min_by(|p1, p2| {
+            p1.origin
+                .distance_sq(*try_pos)
+                .total_cmp(&p2.origin.distance_sq(*try_pos))
+        })
+        .map(ConstraintPath::Point)
+}
 
-                    let temp_line = Line {
-                        origin: r2.origin,
-                        direction: r2.direction,
-                    };
+// intersects two paths, giving either a path that both share (overlap)
+// or the discrete points where they cross
+fn intersect_path_pair(
+    current: &ConstraintPath,
+    next: &ConstraintPath,
+) -> (Option<ConstraintPath>, Vec<Point>) {
+    let mut valid_path: Option<ConstraintPath> = None;
+    let mut valid_points: Vec<Point> = vec![];
 
-                    let cp = temp_line.closest_point(&r1.origin);
-                    if cp.distance(r1.origin) < 0.001 {
-                        // on line, overlapping
-                        //naive approach -- full one would take the smallest ray, OR line segment (which isn't supported)
-                        valid_path = Some(ConstraintPath::Ray(Ray {
-                            origin: r1.origin,
-                            direction: r1.direction,
-                        }));
-                    } else {
-                        valid_path = None;
-                    }
-                }
+    match (current, next) {
+        (ConstraintPath::Point(p), other) | (other, ConstraintPath::Point(p)) => {
+            if other.contains_point(&p.origin) {
+                valid_points = vec![p.clone()];
+            }
+        }
+        (ConstraintPath::Segment(seg), other) | (other, ConstraintPath::Segment(seg)) => {
+            // intersect with the supporting line, then keep what lands on the segment
+            let (line_path, line_points) =
+                intersect_path_pair(&ConstraintPath::Line(seg.to_line()), other);
+
+            // overlapping paths stay on the segment -- naive like the other overlap cases
+            valid_path = line_path.map(|_| ConstraintPath::Segment(seg.clone()));
+            valid_points = line_points
+                .into_iter()
+                .filter(|p| seg.closest_point(&p.origin).distance(p.origin) < 0.01)
+                .collect();
+        }
+        (ConstraintPath::Circle(c1), ConstraintPath::Circle(c2)) => {
+            let center_dist = c1.origin.distance(c2.origin);
 
-                // Handle Ray-Ray case
+            if center_dist < 0.001 && (c1.radius - c2.radius).abs() < 0.001 {
+                // coincident circles, every point on the circle is still valid
+                valid_path = Some(ConstraintPath::Circle(c1.clone()));
+            } else {
+                // 0, 1 (tangent) or 2 points
+                valid_path = None;
+                valid_points = circle_circle_intersection(c1, c2);
             }
-            (ConstraintPath::Circle(c), ConstraintPath::Line(l))
-            | (ConstraintPath::Line(l), ConstraintPath::Circle(c)) => {
-                // This case will never return paths
+        }
+        (ConstraintPath::Line(l1), ConstraintPath::Line(l2)) => {
+            let adjusted_origin_1 = l1.origin + -l1.direction * 500.0;
+            let adjusted_origin_2 = l2.origin + -l2.direction * 500.0;
+
+            let inter_result = ray_ray_intersection(
+                &Ray {
+                    origin: adjusted_origin_1,
+                    direction: l1.direction,
+                },
+                &Ray {
+                    origin: adjusted_origin_2,
+                    direction: l2.direction,
+                },
+            );
+
+            if let Some(inter) = inter_result {
+                valid_points = vec![inter];
                 valid_path = None;
+            } else {
+                // check if they are coincident
 
-                let adjusted_origin = l.origin + -l.direction * 500.0;
-                _valid_points = ray_circle_intersection(
-                    &Ray {
-                        origin: adjusted_origin,
-                        direction: l.direction,
-                    },
-                    c,
-                );
+                let cp = l2.closest_point(&l1.origin);
+                if cp.distance(l1.origin) < 0.001 {
+                    // on line, overlapping
+                    //naive approach -- full one would take the smallest ray, OR line segment (which isn't supported)
+                    valid_path = Some(ConstraintPath::Line(l1.clone()));
+                } else {
+                    valid_path = None;
+                }
             }
-            (ConstraintPath::Circle(c), ConstraintPath::Ray(r))
-            | (ConstraintPath::Ray(r), ConstraintPath::Circle(c)) => {
-                // This case will never return paths
+        }
+        (ConstraintPath::Ray(r1), ConstraintPath::Ray(r2)) => {
+            let inter_result = ray_ray_intersection(r1, r2);
+
+            if let Some(inter) = inter_result {
+                valid_points = vec![inter];
                 valid_path = None;
+            } else {
+                // check if they are coincident
 
-                _valid_points = ray_circle_intersection(
-                    &Ray {
-                        origin: r.origin,
-                        direction: r.direction,
-                    },
-                    c,
-                );
-            }
-            (ConstraintPath::Line(l), ConstraintPath::Ray(r))
-            | (ConstraintPath::Ray(r), ConstraintPath::Line(l)) => {
-                // Handle Line-Ray or Ray-Line case
-                let adjusted_origin = l.origin + -l.direction * 500.0;
-                let inter_result = ray_ray_intersection(
-                    &Ray {
-                        origin: adjusted_origin,
-                        direction: l.direction,
-                    },
-                    r,
-                );
+                let temp_line = Line {
+                    origin: r2.origin,
+                    direction: r2.direction,
+                };
 
-                if let Some(inter) = inter_result {
-                    _valid_points = vec![inter];
-                    valid_path = None;
+                let cp = temp_line.closest_point(&r1.origin);
+                if cp.distance(r1.origin) < 0.001 {
+                    // on line, overlapping
+                    //naive approach -- full one would take the smallest ray, OR line segment (which isn't supported)
+                    valid_path = Some(ConstraintPath::Ray(Ray {
+                        origin: r1.origin,
+                        direction: r1.direction,
+                    }));
                 } else {
-                    // check if they are coincident
+                    valid_path = None;
+                }
+            }
 
-                    let cp = l.closest_point(&r.origin);
-                    if cp.distance(r.origin) < 0.001 {
-                        // on line, overlapping
-                        //niave approact -- full one would take the smallest ray, OR line segment (which isn't supported)
-                        valid_path = Some(ConstraintPath::Ray(r.clone()));
-                    } else {
-                        valid_path = None;
-                    }
+            // Handle Ray-Ray case
+        }
+        (ConstraintPath::Circle(c), ConstraintPath::Line(l))
+        | (ConstraintPath::Line(l), ConstraintPath::Circle(c)) => {
+            // This case will never return paths
+            valid_path = None;
+
+            let adjusted_origin = l.origin + -l.direction * 500.0;
+            valid_points = ray_circle_intersection(
+                &Ray {
+                    origin: adjusted_origin,
+                    direction: l.direction,
+                },
+                c,
+            );
+        }
+        (ConstraintPath::Circle(c), ConstraintPath::Ray(r))
+        | (ConstraintPath::Ray(r), ConstraintPath::Circle(c)) => {
+            // This case will never return paths
+            valid_path = None;
+
+            valid_points = ray_circle_intersection(
+                &Ray {
+                    origin: r.origin,
+                    direction: r.direction,
+                },
+                c,
+            );
+        }
+        (ConstraintPath::Line(l), ConstraintPath::Ray(r))
+        | (ConstraintPath::Ray(r), ConstraintPath::Line(l)) => {
+            // Handle Line-Ray or Ray-Line case
+            let adjusted_origin = l.origin + -l.direction * 500.0;
+            let inter_result = ray_ray_intersection(
+                &Ray {
+                    origin: adjusted_origin,
+                    direction: l.direction,
+                },
+                r,
+            );
+
+            if let Some(inter) = inter_result {
+                valid_points = vec![inter];
+                valid_path = None;
+            } else {
+                // check if they are coincident
+
+                let cp = l.closest_point(&r.origin);
+                if cp.distance(r.origin) < 0.001 {
+                    // on line, overlapping
+                    //niave approact -- full one would take the smallest ray, OR line segment (which isn't supported)
+                    valid_path = Some(ConstraintPath::Ray(r.clone()));
+                } else {
+                    valid_path = None;
                 }
             }
-            _ => {}
         }
     }
-    valid_path
+
+    (valid_path, valid_points)
 }
 
 #[derive(Clone, Debug)]
@@ -745,7 +2441,7 @@ pub struct Circle {
 
 impl Circle {
     pub fn closest_point(&self, point: &Pos2) -> Pos2 {
-        let mut dir = point.clone() - self.origin;
+        let mut dir = *point - self.origin;
 
         if dir.length() < 0.001 {
             // just get the up direction since all points on the circle would be closest
@@ -766,7 +2462,7 @@ pub struct Line {
 
 impl Line {
     pub fn closest_point(&self, point: &Pos2) -> Pos2 {
-        let origin_to_point = point.clone() - self.origin;
+        let origin_to_point = *point - self.origin;
         let projection_length = origin_to_point.dot(self.direction);
         self.origin + self.direction * projection_length
     }
@@ -779,12 +2475,39 @@ pub struct Ray {
 impl Ray {
     pub fn closest_point(&self, point: &Pos2) -> Pos2 {
         // same as line, except we clamp the param to 0 if it's negative
-        let origin_to_point = point.clone() - self.origin;
+        let origin_to_point = *point - self.origin;
         let projection_length = origin_to_point.dot(self.direction);
         self.origin + self.direction * projection_length.max(0.0)
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct Segment {
+    pub start: Pos2,
+    pub end: Pos2,
+}
+impl Segment {
+    pub fn closest_point(&self, point: &Pos2) -> Pos2 {
+        // same as line, except we clamp the param to the segment's extent
+        let segment_vector = self.end - self.start;
+        let length_sq = segment_vector.length_sq();
+
+        if length_sq < 0.001 {
+            return self.start;
+        }
+
+        let t = ((*point - self.start).dot(segment_vector) / length_sq).clamp(0.0, 1.0);
+        self.start + segment_vector * t
+    }
+
+    pub fn to_line(&self) -> Line {
+        Line {
+            origin: self.start,
+            direction: (self.end - self.start).normalized(),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Point {
     pub origin: Pos2,
@@ -801,6 +2524,7 @@ pub enum ConstraintPath {
     Circle(Circle),
     Line(Line),
     Ray(Ray),
+    Segment(Segment),
     Point(Point),
 }
 
@@ -810,22 +2534,33 @@ impl ConstraintPath {
             ConstraintPath::Circle(c) => c.closest_point(point),
             ConstraintPath::Line(l) => l.closest_point(point),
             ConstraintPath::Ray(r) => r.closest_point(point),
+            ConstraintPath::Segment(s) => s.closest_point(point),
             ConstraintPath::Point(p) => p.closest_point(),
         }
     }
+
+    // looser than the coincidence checks since intersection points carry float error
+    pub fn contains_point(&self, point: &Pos2) -> bool {
+        self.closest_point(point).distance(*point) < 0.01
+    }
 }
 
-#[derive(Debug)]
+// Analytic intersects the constraint paths around the dragged vertex only,
+// Global moves the whole sketch through the simultaneous solver
+#[derive(Debug, Default, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum SolverMode {
+    #[default]
+    Analytic,
+    Global,
+}
+
+#[derive(Debug, Default)]
 pub enum SolverState {
     Locked,
     Partial,
+    #[default]
     Free,
 }
-impl Default for SolverState {
-    fn default() -> Self {
-        SolverState::Free
-    }
-}
 
 #[derive(Default)]
 pub struct SolverResponse {
@@ -860,39 +2595,261 @@ impl EdgeSolverResponse {
     }
 }
 
+#[derive(Default)]
+pub struct GroupSolverResponse {
+    pub state: SolverState,
+    // the group and every vertex outside it that had to move along with it
+    pub new_positions: BTreeMap<VertexHandle, Pos2>,
+}
+impl GroupSolverResponse {
+    pub fn locked() -> Self {
+        Self {
+            state: SolverState::Locked,
+            new_positions: BTreeMap::new(),
+        }
+    }
+}
+
+// the saved form of the constraints, see DrawingManagerRecord
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct ConstraintManagerRecord {
+    pub constraints: Vec<ConstraintRecord>,
+    pub constraint_allocator: HandleAllocator<ConstraintHandle>,
+    #[serde(default)]
+    pub solver_mode: SolverMode,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct ConstraintRecord {
+    pub handle: ConstraintHandle,
+    pub constraint: Constraint,
+}
+
+// everything a remove call took out of the sketch, in the order it was removed,
+// so that it can be put back again
+#[derive(Clone, Default)]
+pub struct DeletionReport {
+    pub vertices: Vec<(VertexHandle, Vertex)>,
+    pub edges: Vec<(EdgeHandle, Edge)>,
+    pub constraints: Vec<(ConstraintHandle, Constraint)>,
+}
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+#[allow(clippy::upper_case_acronyms, non_camel_case_types)]
 pub enum Constraint {
     LENGTH(LengthConstraint),
     ANGLE(AngleConstraint),
     PARALLEL(ParallelConstraint),
+    PERPENDICULAR(PerpendicularConstraint),
+    HORIZONTAL(AxisConstraint),
+    VERTICAL(AxisConstraint),
+    FIXED(FixedConstraint),
+    COINCIDENT(CoincidentConstraint),
+    POINT_ON_EDGE(PointOnEdgeConstraint),
+    EQUAL_LENGTH(EqualLengthConstraint),
+    SYMMETRIC(SymmetricConstraint),
+    DISTANCE(DistanceConstraint),
+    EDGE_DISTANCE(EdgeDistanceConstraint),
+}
+
+impl Constraint {
+    // edges the constraint refers to directly
+    pub fn edge_handles(&self) -> Vec<EdgeHandle> {
+        match self {
+            Constraint::LENGTH(c) => vec![c.edge_handle],
+            Constraint::ANGLE(c) => vec![c.edge_1_handle, c.edge_2_handle],
+            Constraint::PARALLEL(c) => vec![c.edge_1_handle, c.edge_2_handle],
+            Constraint::PERPENDICULAR(c) => vec![c.edge_1_handle, c.edge_2_handle],
+            Constraint::HORIZONTAL(c) | Constraint::VERTICAL(c) => vec![c.edge_handle],
+            Constraint::FIXED(_) => vec![],
+            Constraint::COINCIDENT(_) => vec![],
+            Constraint::POINT_ON_EDGE(c) => vec![c.edge_handle],
+            Constraint::EQUAL_LENGTH(c) => vec![c.edge_1_handle, c.edge_2_handle],
+            Constraint::SYMMETRIC(c) => vec![c.axis_edge_handle],
+            Constraint::DISTANCE(_) => vec![],
+            Constraint::EDGE_DISTANCE(c) => vec![c.edge_handle],
+        }
+    }
+
+    // the same constraint on other vertices and edges, for when the sketch is rebuilt under
+    // new handles. None if any of them has no counterpart
+    pub fn map_handles(
+        &self,
+        vertex: impl Fn(VertexHandle) -> Option<VertexHandle>,
+        edge: impl Fn(EdgeHandle) -> Option<EdgeHandle>,
+    ) -> Option<Constraint> {
+        let mut constraint = self.clone();
+        match &mut constraint {
+            Constraint::LENGTH(c) => c.edge_handle = edge(c.edge_handle)?,
+            Constraint::ANGLE(c) => {
+                c.pivot_vert_handle = vertex(c.pivot_vert_handle)?;
+                c.edge_1_handle = edge(c.edge_1_handle)?;
+                c.edge_1_outer_vert_handle = vertex(c.edge_1_outer_vert_handle)?;
+                c.edge_2_handle = edge(c.edge_2_handle)?;
+                c.edge_2_outer_vert_handle = vertex(c.edge_2_outer_vert_handle)?;
+            }
+            Constraint::PARALLEL(c) => {
+                c.edge_1_handle = edge(c.edge_1_handle)?;
+                c.edge_2_handle = edge(c.edge_2_handle)?;
+            }
+            Constraint::PERPENDICULAR(c) => {
+                c.edge_1_handle = edge(c.edge_1_handle)?;
+                c.edge_2_handle = edge(c.edge_2_handle)?;
+            }
+            Constraint::HORIZONTAL(c) | Constraint::VERTICAL(c) => {
+                c.edge_handle = edge(c.edge_handle)?
+            }
+            Constraint::FIXED(c) => c.vertex_handle = vertex(c.vertex_handle)?,
+            Constraint::COINCIDENT(c) => {
+                c.vertex_1_handle = vertex(c.vertex_1_handle)?;
+                c.vertex_2_handle = vertex(c.vertex_2_handle)?;
+            }
+            Constraint::POINT_ON_EDGE(c) => {
+                c.vertex_handle = vertex(c.vertex_handle)?;
+                c.edge_handle = edge(c.edge_handle)?;
+            }
+            Constraint::EQUAL_LENGTH(c) => {
+                c.edge_1_handle = edge(c.edge_1_handle)?;
+                c.edge_2_handle = edge(c.edge_2_handle)?;
+            }
+            Constraint::SYMMETRIC(c) => {
+                c.vertex_1_handle = vertex(c.vertex_1_handle)?;
+                c.vertex_2_handle = vertex(c.vertex_2_handle)?;
+                c.axis_edge_handle = edge(c.axis_edge_handle)?;
+            }
+            Constraint::DISTANCE(c) => {
+                c.vertex_1_handle = vertex(c.vertex_1_handle)?;
+                c.vertex_2_handle = vertex(c.vertex_2_handle)?;
+            }
+            Constraint::EDGE_DISTANCE(c) => {
+                c.vertex_handle = vertex(c.vertex_handle)?;
+                c.edge_handle = edge(c.edge_handle)?;
+            }
+        }
+        Some(constraint)
+    }
+
+    // vertices the constraint refers to directly, not counting the ends of its edges
+    pub fn vertex_handles(&self) -> Vec<VertexHandle> {
+        match self {
+            Constraint::ANGLE(c) => vec![
+                c.pivot_vert_handle,
+                c.edge_1_outer_vert_handle,
+                c.edge_2_outer_vert_handle,
+            ],
+            Constraint::FIXED(c) => vec![c.vertex_handle],
+            Constraint::COINCIDENT(c) => vec![c.vertex_1_handle, c.vertex_2_handle],
+            Constraint::POINT_ON_EDGE(c) => vec![c.vertex_handle],
+            Constraint::SYMMETRIC(c) => vec![c.vertex_1_handle, c.vertex_2_handle],
+            Constraint::DISTANCE(c) => vec![c.vertex_1_handle, c.vertex_2_handle],
+            Constraint::EDGE_DISTANCE(c) => vec![c.vertex_handle],
+            Constraint::LENGTH(_)
+            | Constraint::PARALLEL(_)
+            | Constraint::PERPENDICULAR(_)
+            | Constraint::HORIZONTAL(_)
+            | Constraint::VERTICAL(_)
+            | Constraint::EQUAL_LENGTH(_) => vec![],
+        }
+    }
 }
 
 // Length Constraint is primarily around an edge only
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
 pub struct LengthConstraint {
     pub edge_handle: EdgeHandle,
+    pub length: f32,
 }
 // Angle is relative to edge_1_handle counterclockwise
 // pivot_vert_handle must refer to a vertex that both edges share
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
 pub struct AngleConstraint {
     pub pivot_vert_handle: VertexHandle,
     pub edge_1_handle: EdgeHandle,
     pub edge_1_outer_vert_handle: VertexHandle,
     pub edge_2_handle: EdgeHandle,
     pub edge_2_outer_vert_handle: VertexHandle,
+    // radians, [-pi to pi]
+    pub angle: f32,
 }
 
 // Parallel constraint between two edges
 // order does not matter here
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
 pub struct ParallelConstraint {
     pub edge_1_handle: EdgeHandle,
     pub edge_2_handle: EdgeHandle,
 }
 
-//Utilities
+// Fixed constraint pins a vertex where it currently is
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct FixedConstraint {
+    pub vertex_handle: VertexHandle,
+}
+
+// Equal length keeps two edges the same length, whatever that length is
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct EqualLengthConstraint {
+    pub edge_1_handle: EdgeHandle,
+    pub edge_2_handle: EdgeHandle,
+}
+
+// Distance between two vertices that aren't joined by an edge
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct DistanceConstraint {
+    pub vertex_1_handle: VertexHandle,
+    pub vertex_2_handle: VertexHandle,
+    pub distance: f32,
+}
+
+// Perpendicular distance from a vertex to the line through an edge
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct EdgeDistanceConstraint {
+    pub vertex_handle: VertexHandle,
+    pub edge_handle: EdgeHandle,
+    pub distance: f32, // signed, positive is counterclockwise from the edge's start to end
+}
+
+// Symmetric constraint mirrors two vertices across the line through an edge
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct SymmetricConstraint {
+    pub vertex_1_handle: VertexHandle,
+    pub vertex_2_handle: VertexHandle,
+    pub axis_edge_handle: EdgeHandle,
+}
+
+// Coincident constraint keeps two separate vertices on top of each other
+// order does not matter here
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct CoincidentConstraint {
+    pub vertex_1_handle: VertexHandle,
+    pub vertex_2_handle: VertexHandle,
+}
+
+// Point on edge keeps a vertex somewhere between the edge's endpoints
+// the vertex can't be one of the edge's own endpoints
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct PointOnEdgeConstraint {
+    pub vertex_handle: VertexHandle,
+    pub edge_handle: EdgeHandle,
+}
+
+// Horizontal / Vertical constraint on a single edge,
+// the Constraint variant decides which axis it follows
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct AxisConstraint {
+    pub edge_handle: EdgeHandle,
+}
 
-fn get_next_id<V>(map: &BTreeMap<i32, V>) -> i32 {
-    map.last_key_value().map_or(0, |(k, _)| k + 1)
+// Perpendicular constraint between two edges
+// the edges don't need to share a vertex, order does not matter here
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct PerpendicularConstraint {
+    pub edge_1_handle: EdgeHandle,
+    pub edge_2_handle: EdgeHandle,
 }
 
+//Utilities
+
 #[derive(Debug, Error)]
 pub enum ConstraintError {
     #[error("Constraint could not be added")]
@@ -905,6 +2862,51 @@ pub enum ConstraintError {
     DegenerateEdge,
     #[error("Full overlap detected")]
     FullOverlap,
+    #[error("Invalid constraint value")]
+    InvalidValue,
+    #[error("Sketch cannot reach the constraint value")]
+    ValueNotReachable,
+    #[error("Sketch cannot satisfy the constraint")]
+    Unsolvable,
+    #[error("Vertices are already joined by an edge")]
+    VerticesShareEdge,
+    #[error("Constraint conflicts with existing constraints {0:?}")]
+    Conflict(Vec<ConstraintHandle>),
+    #[error("Edge {0} not found")]
+    EdgeNotFound(EdgeHandle),
+    #[error("Vertex {0} not found")]
+    VertexNotFound(VertexHandle),
+    #[error("Select {0} for this constraint")]
+    InvalidSelection(&'static str),
+}
+
+pub fn rotate_vec2(vec: Vec2, angle: f32) -> Vec2 {
+    let cos_angle = angle.cos();
+    let sin_angle = angle.sin();
+
+    Vec2 {
+        x: vec.x * cos_angle - vec.y * sin_angle,
+        y: vec.x * sin_angle + vec.y * cos_angle,
+    }
+}
+
+// counterclockwise angle from vec_1 to vec_2, [-pi to pi]
+pub fn signed_angle(vec_1: Vec2, vec_2: Vec2) -> f32 {
+    vec_1
+        .x
+        .mul_add(vec_2.y, -vec_1.y * vec_2.x)
+        .atan2(vec_1.dot(vec_2))
+}
+
+// mirror image of a point across the infinite line through two others
+pub fn reflect_across_line(point: Pos2, line_start: Pos2, line_end: Pos2) -> Pos2 {
+    let foot = Line {
+        origin: line_start,
+        direction: (line_end - line_start).normalized(),
+    }
+    .closest_point(&point);
+
+    foot + (foot - point)
 }
 
 fn find_shared_and_unmatched_vertices(
@@ -989,6 +2991,52 @@ fn ray_circle_intersection(ray: &Ray, circle: &Circle) -> Vec<Point> {
     }
 }
 
+fn circle_circle_intersection(circle_1: &Circle, circle_2: &Circle) -> Vec<Point> {
+    let r1 = circle_1.radius;
+    let r2 = circle_2.radius;
+
+    let delta = circle_2.origin - circle_1.origin;
+    let d = delta.length();
+
+    // concentric circles either fully overlap or never meet, neither gives points
+    if d < 0.001 {
+        return Vec::new();
+    }
+
+    // too far apart, or one circle is contained inside the other
+    if d > r1 + r2 + 0.001 || d < (r1 - r2).abs() - 0.001 {
+        return Vec::new();
+    }
+
+    // distance from circle_1's origin to the chord joining the intersection points
+    let a = (r1 * r1 - r2 * r2 + d * d) / (2.0 * d);
+
+    // half length of the chord, clamped so near-tangent circles resolve to a single point
+    let h_sq = r1 * r1 - a * a;
+    let h = if h_sq > 0.0 { h_sq.sqrt() } else { 0.0 };
+
+    let dir = delta / d;
+    let chord_center = circle_1.origin + dir * a;
+
+    if h < 0.001 {
+        // tangent circles
+        return vec![Point {
+            origin: chord_center,
+        }];
+    }
+
+    let perp = Vec2::new(-dir.y, dir.x);
+
+    vec![
+        Point {
+            origin: chord_center + perp * h,
+        },
+        Point {
+            origin: chord_center - perp * h,
+        },
+    ]
+}
+
 fn ray_ray_intersection(ray1: &Ray, ray2: &Ray) -> Option<Point> {
     let x1 = ray1.origin.x;
     let y1 = ray1.origin.y;
@@ -1026,3 +3074,491 @@ fn ray_ray_intersection(ray1: &Ray, ray2: &Ray) -> Option<Point> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn managers() -> (Rc<RefCell<DrawingManager>>, ConstraintManager) {
+        let dm = Rc::new(RefCell::new(DrawingManager::new()));
+        let mut cm = ConstraintManager::new();
+        cm.set_drawing_manager(Rc::clone(&dm));
+        (dm, cm)
+    }
+
+    fn add_edge(dm: &RefCell<DrawingManager>, start: Pos2, end: Pos2) -> EdgeHandle {
+        let mut dm = dm.borrow_mut();
+        let vh_1 = dm.add_vertex(start);
+        let vh_2 = dm.add_vertex(end);
+        dm.add_edge(vh_1, vh_2).unwrap()
+    }
+
+    fn edge_vector(dm: &RefCell<DrawingManager>, eh: EdgeHandle) -> Vec2 {
+        let dm = dm.borrow();
+        let edge = dm.get_edge(eh).unwrap();
+        dm.get_vertex(edge.end_point_vh).unwrap().position
+            - dm.get_vertex(edge.start_point_vh).unwrap().position
+    }
+
+    #[test]
+    fn axis_constraints_level_the_edge() {
+        let (dm, mut cm) = managers();
+        let sloped = add_edge(&dm, Pos2::new(0.0, 0.0), Pos2::new(10.0, 3.0));
+        let steep = add_edge(&dm, Pos2::new(20.0, 0.0), Pos2::new(22.0, 10.0));
+
+        cm.add_horizontal_constraint(sloped).unwrap();
+        cm.add_vertical_constraint(steep).unwrap();
+
+        assert!(edge_vector(&dm, sloped).y.abs() < 0.01);
+        assert!(edge_vector(&dm, steep).x.abs() < 0.01);
+    }
+
+    #[test]
+    fn parallel_and_perpendicular_turn_the_edges() {
+        let (dm, mut cm) = managers();
+        let edge_1 = add_edge(&dm, Pos2::new(0.0, 0.0), Pos2::new(10.0, 0.0));
+        let edge_2 = add_edge(&dm, Pos2::new(0.0, 5.0), Pos2::new(10.0, 15.0));
+        let edge_3 = add_edge(&dm, Pos2::new(20.0, 0.0), Pos2::new(30.0, 10.0));
+
+        cm.add_parallel_constraint(edge_1, edge_2).unwrap();
+        let (dir_1, dir_2) = (edge_vector(&dm, edge_1), edge_vector(&dm, edge_2));
+        assert!(dir_1.normalized().dot(dir_2.normalized()).abs() > 0.999);
+
+        cm.add_perpendicular_constraint(edge_1, edge_3).unwrap();
+        let (dir_1, dir_3) = (edge_vector(&dm, edge_1), edge_vector(&dm, edge_3));
+        assert!(dir_1.normalized().dot(dir_3.normalized()).abs() < 0.001);
+    }
+
+    #[test]
+    fn degenerate_starts_are_not_empty_conflicts() {
+        let (dm, mut cm) = managers();
+        let edge_1 = add_edge(&dm, Pos2::new(0.0, 0.0), Pos2::new(10.0, 0.0));
+        let edge_2 = add_edge(&dm, Pos2::new(0.0, 5.0), Pos2::new(10.0, 5.0));
+        let edge_3 = add_edge(&dm, Pos2::new(20.0, 0.0), Pos2::new(20.0, 10.0));
+
+        // perpendicular on parallel edges and parallel on perpendicular ones used to
+        // come back as a conflict with nothing
+        cm.add_perpendicular_constraint(edge_1, edge_2).unwrap();
+        cm.add_parallel_constraint(edge_1, edge_3).unwrap();
+
+        let (dir_1, dir_2) = (edge_vector(&dm, edge_1), edge_vector(&dm, edge_2));
+        let dir_3 = edge_vector(&dm, edge_3);
+        assert!(dir_1.normalized().dot(dir_2.normalized()).abs() < 0.001);
+        assert!(dir_1.normalized().dot(dir_3.normalized()).abs() > 0.999);
+    }
+
+    #[test]
+    fn set_length_value_drives_the_edge() {
+        let (dm, mut cm) = managers();
+        let a = dm.borrow_mut().add_vertex(Pos2::new(0.0, 0.0));
+        let b = dm.borrow_mut().add_vertex(Pos2::new(10.0, 0.0));
+        let c = dm.borrow_mut().add_vertex(Pos2::new(4.0, 7.0));
+        let ab = dm.borrow_mut().add_edge(a, b).unwrap();
+        let bc = dm.borrow_mut().add_edge(b, c).unwrap();
+        let ca = dm.borrow_mut().add_edge(c, a).unwrap();
+
+        let ab_length = cm.add_length_constraint(ab).unwrap();
+        cm.add_length_constraint(bc).unwrap();
+        cm.add_length_constraint(ca).unwrap();
+
+        cm.set_length_value(ab_length, 12.0).unwrap();
+        assert!((edge_vector(&dm, ab).length() - 12.0).abs() < 0.01);
+
+        // the other two sides can't reach across 30, the sketch and value stay as they were
+        assert!(matches!(
+            cm.set_length_value(ab_length, 30.0),
+            Err(ConstraintError::ValueNotReachable)
+        ));
+        assert!((edge_vector(&dm, ab).length() - 12.0).abs() < 0.01);
+        assert!(matches!(
+            cm.get_constraint(ab_length),
+            Ok(Constraint::LENGTH(LengthConstraint { length, .. })) if *length == 12.0
+        ));
+    }
+
+    #[test]
+    fn followers_come_from_the_moved_vertices() {
+        let (dm, mut cm) = managers();
+        let axis = add_edge(&dm, Pos2::new(0.0, -10.0), Pos2::new(0.0, 10.0));
+        let a = dm.borrow_mut().add_vertex(Pos2::new(-5.0, 2.0));
+        let b = dm.borrow_mut().add_vertex(Pos2::new(5.0, 2.0));
+        let c = dm.borrow_mut().add_vertex(Pos2::new(-5.0, 2.0));
+        cm.add_symmetric_constraint(a, b, axis).unwrap();
+        cm.add_coincident_constraint(a, c).unwrap();
+
+        let position = |vh| dm.borrow().get_vertex(vh).unwrap().position;
+        let previous_positions = BTreeMap::from([(a, position(a))]);
+        dm.borrow_mut().get_vertex_mut(a).unwrap().position = Pos2::new(-6.0, 4.0);
+
+        assert_eq!(
+            cm.solve_coincident_followers(&previous_positions),
+            vec![(c, Pos2::new(-6.0, 4.0))]
+        );
+        let symmetric_followers = cm.solve_symmetric_followers(&previous_positions);
+        assert_eq!(symmetric_followers.len(), 1);
+        assert_eq!(symmetric_followers[0].0, b);
+        assert!(symmetric_followers[0].1.distance(Pos2::new(6.0, 4.0)) < 0.001);
+
+        // moving the axis carries the mirrored pair with it
+        let axis_end = dm.borrow().get_edge(axis).unwrap().end_point_vh;
+        let previous_positions = BTreeMap::from([(axis_end, position(axis_end))]);
+        dm.borrow_mut().get_vertex_mut(axis_end).unwrap().position = Pos2::new(20.0, -10.0);
+
+        let moved: Vec<VertexHandle> = cm
+            .solve_symmetric_followers(&previous_positions)
+            .into_iter()
+            .map(|(vh, _)| vh)
+            .collect();
+        assert_eq!(moved, vec![a, b]);
+    }
+
+    fn circle(x: f32, y: f32, radius: f32) -> Circle {
+        Circle {
+            origin: Pos2::new(x, y),
+            radius,
+        }
+    }
+
+    #[test]
+    fn circle_circle_intersection_cases() {
+        let crossing = circle_circle_intersection(&circle(0.0, 0.0, 5.0), &circle(6.0, 0.0, 5.0));
+        assert_eq!(crossing.len(), 2);
+        for point in &crossing {
+            assert!((point.origin.x - 3.0).abs() < 0.001);
+            assert!((point.origin.y.abs() - 4.0).abs() < 0.001);
+        }
+
+        // touching from outside and from inside meet at a single point
+        let outside = circle_circle_intersection(&circle(0.0, 0.0, 5.0), &circle(8.0, 0.0, 3.0));
+        assert_eq!(outside.len(), 1);
+        assert!(outside[0].origin.distance(Pos2::new(5.0, 0.0)) < 0.001);
+        let inside = circle_circle_intersection(&circle(0.0, 0.0, 5.0), &circle(2.0, 0.0, 3.0));
+        assert_eq!(inside.len(), 1);
+        assert!(inside[0].origin.distance(Pos2::new(5.0, 0.0)) < 0.001);
+
+        // apart, nested and concentric circles never cross
+        assert!(
+            circle_circle_intersection(&circle(0.0, 0.0, 1.0), &circle(5.0, 0.0, 1.0)).is_empty()
+        );
+        assert!(
+            circle_circle_intersection(&circle(0.0, 0.0, 5.0), &circle(1.0, 0.0, 1.0)).is_empty()
+        );
+        assert!(
+            circle_circle_intersection(&circle(0.0, 0.0, 5.0), &circle(0.0, 0.0, 5.0)).is_empty()
+        );
+    }
+
+    #[test]
+    fn vertex_between_two_lengths_snaps_to_the_nearer_crossing() {
+        let (dm, mut cm) = managers();
+        let a = dm.borrow_mut().add_vertex(Pos2::new(0.0, 0.0));
+        let b = dm.borrow_mut().add_vertex(Pos2::new(3.0, 4.0));
+        let c = dm.borrow_mut().add_vertex(Pos2::new(6.0, 0.0));
+        let ab = dm.borrow_mut().add_edge(a, b).unwrap();
+        let bc = dm.borrow_mut().add_edge(b, c).unwrap();
+        cm.add_length_constraint(ab).unwrap();
+        cm.add_length_constraint(bc).unwrap();
+
+        // b can only be at (3, 4) or (3, -4), dragging below the base picks the lower one
+        let response = cm.solve_for_vertex(b, &Pos2::new(3.0, 4.0), &Pos2::new(2.0, -3.0), vec![]);
+        assert!(matches!(response.state, SolverState::Partial));
+        assert!(response.new_pos.unwrap().distance(Pos2::new(3.0, -4.0)) < 0.001);
+    }
+
+    fn line(x: f32, y: f32, dir_x: f32, dir_y: f32) -> ConstraintPath {
+        ConstraintPath::Line(Line {
+            origin: Pos2::new(x, y),
+            direction: Vec2::new(dir_x, dir_y).normalized(),
+        })
+    }
+
+    fn snapped_point(path: Option<ConstraintPath>) -> Pos2 {
+        match path {
+            Some(ConstraintPath::Point(point)) => point.origin,
+            other => panic!("expected a point, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn intersect_paths_snaps_to_points() {
+        let try_pos = Pos2::new(0.0, 0.0);
+
+        // crossing lines leave the one point they share
+        let paths = vec![line(0.0, 2.0, 1.0, 0.0), line(3.0, 0.0, 0.0, 1.0)];
+        let point = snapped_point(intersect_paths(paths, &try_pos));
+        assert!(point.distance(Pos2::new(3.0, 2.0)) < 0.001);
+
+        // a line through a circle crosses it twice, the one nearer the drag wins
+        let paths = vec![
+            ConstraintPath::Circle(circle(0.0, 0.0, 5.0)),
+            line(0.0, 0.0, 1.0, 0.0),
+        ];
+        let point = snapped_point(intersect_paths(paths.clone(), &Pos2::new(-4.0, 1.0)));
+        assert!(point.distance(Pos2::new(-5.0, 0.0)) < 0.001);
+
+        // a third path filters the crossings down, even to the one further away
+        let mut filtered_paths = paths.clone();
+        filtered_paths.push(line(5.0, -3.0, 0.0, 1.0));
+        let point = snapped_point(intersect_paths(filtered_paths, &Pos2::new(-4.0, 1.0)));
+        assert!(point.distance(Pos2::new(5.0, 0.0)) < 0.001);
+
+        // and when none of them survive nothing is left
+        let mut missed_paths = paths;
+        missed_paths.push(line(1.0, 0.0, 0.0, 1.0));
+        assert!(intersect_paths(missed_paths, &try_pos).is_none());
+    }
+
+    #[test]
+    fn angle_pivot_moves_on_the_inscribed_angle_circle() {
+        let (dm, mut cm) = managers();
+        let p = dm.borrow_mut().add_vertex(Pos2::new(0.0, 0.0));
+        let a = dm.borrow_mut().add_vertex(Pos2::new(10.0, 0.0));
+        let c = dm
+            .borrow_mut()
+            .add_vertex(Pos2::new(5.0, 60_f32.to_radians().tan() * 5.0));
+        let pa = dm.borrow_mut().add_edge(p, a).unwrap();
+        let pc = dm.borrow_mut().add_edge(p, c).unwrap();
+        cm.add_angle_constraint(pa, pc).unwrap();
+        cm.add_fixed_constraint(a).unwrap();
+        cm.add_fixed_constraint(c).unwrap();
+
+        let position = |vh| dm.borrow().get_vertex(vh).unwrap().position;
+        let angle_at = |pivot: Pos2| signed_angle(position(a) - pivot, position(c) - pivot);
+        let start_angle = angle_at(position(p));
+
+        // a 60 degree pivot slides along its arc and keeps the angle
+        let response = cm.solve_for_vertex(p, &position(p), &Pos2::new(2.0, -1.0), vec![]);
+        assert!(matches!(response.state, SolverState::Partial));
+        let new_pos = response.new_pos.unwrap();
+        assert!(new_pos.distance(position(p)) > 0.1);
+        assert!((angle_at(new_pos) - start_angle).abs() < 0.01);
+
+        // across the chord the same circle would flip the angle, so it stays put
+        let response = cm.solve_for_vertex(p, &position(p), &Pos2::new(5.0, 20.0), vec![]);
+        assert!(matches!(response.state, SolverState::Locked));
+    }
+
+    #[test]
+    fn perpendicular_end_follows_the_normal_line() {
+        let (dm, mut cm) = managers();
+        let ab = add_edge(&dm, Pos2::new(0.0, 0.0), Pos2::new(10.0, 0.0));
+        let cd = add_edge(&dm, Pos2::new(20.0, 0.0), Pos2::new(20.0, 10.0));
+        cm.add_perpendicular_constraint(ab, cd).unwrap();
+
+        let d = dm.borrow().get_edge(cd).unwrap().end_point_vh;
+        let response =
+            cm.solve_for_vertex(d, &Pos2::new(20.0, 10.0), &Pos2::new(25.0, 12.0), vec![]);
+        assert!(matches!(response.state, SolverState::Partial));
+        assert!(response.new_pos.unwrap().distance(Pos2::new(20.0, 12.0)) < 0.001);
+    }
+
+    #[test]
+    fn perpendicular_corner_follows_the_thales_circle() {
+        let (dm, mut cm) = managers();
+        let p = dm.borrow_mut().add_vertex(Pos2::new(0.0, 0.0));
+        let a = dm.borrow_mut().add_vertex(Pos2::new(10.0, 0.0));
+        let b = dm.borrow_mut().add_vertex(Pos2::new(0.0, 10.0));
+        let pa = dm.borrow_mut().add_edge(p, a).unwrap();
+        let pb = dm.borrow_mut().add_edge(p, b).unwrap();
+        cm.add_perpendicular_constraint(pa, pb).unwrap();
+
+        let response = cm.solve_for_vertex(p, &Pos2::new(0.0, 0.0), &Pos2::new(1.0, -2.0), vec![]);
+        let new_pos = response.new_pos.unwrap();
+        assert!((new_pos.distance(Pos2::new(5.0, 5.0)) - 50_f32.sqrt()).abs() < 0.001);
+        assert!(
+            (Pos2::new(10.0, 0.0) - new_pos)
+                .dot(Pos2::new(0.0, 10.0) - new_pos)
+                .abs()
+                < 0.01
+        );
+    }
+
+    #[test]
+    fn axis_constrained_ends_follow_axis_lines() {
+        let (dm, mut cm) = managers();
+        let ab = add_edge(&dm, Pos2::new(0.0, 0.0), Pos2::new(10.0, 0.0));
+        let cd = add_edge(&dm, Pos2::new(20.0, 0.0), Pos2::new(20.0, 10.0));
+        cm.add_horizontal_constraint(ab).unwrap();
+        cm.add_vertical_constraint(cd).unwrap();
+
+        let b = dm.borrow().get_edge(ab).unwrap().end_point_vh;
+        let response = cm.solve_for_vertex(b, &Pos2::new(10.0, 0.0), &Pos2::new(12.0, 5.0), vec![]);
+        assert!(response.new_pos.unwrap().distance(Pos2::new(12.0, 0.0)) < 0.001);
+
+        let d = dm.borrow().get_edge(cd).unwrap().end_point_vh;
+        let response =
+            cm.solve_for_vertex(d, &Pos2::new(20.0, 10.0), &Pos2::new(23.0, 12.0), vec![]);
+        assert!(response.new_pos.unwrap().distance(Pos2::new(20.0, 12.0)) < 0.001);
+    }
+
+    #[test]
+    fn horizontal_edge_stays_level_while_dragged() {
+        let (dm, mut cm) = managers();
+        let a = dm.borrow_mut().add_vertex(Pos2::new(0.0, 0.0));
+        let b = dm.borrow_mut().add_vertex(Pos2::new(10.0, 0.0));
+        let c = dm.borrow_mut().add_vertex(Pos2::new(10.0, 10.0));
+        let ab = dm.borrow_mut().add_edge(a, b).unwrap();
+        let bc = dm.borrow_mut().add_edge(b, c).unwrap();
+        cm.add_horizontal_constraint(ab).unwrap();
+        cm.add_vertical_constraint(bc).unwrap();
+
+        // b has to stay under c, so the level edge slides up along x = 10
+        let delta = Vec2::new(1.0, 3.0);
+        let response = cm.solve_for_edge(
+            ab,
+            &Pos2::new(5.0, 0.0),
+            &(Pos2::new(5.0, 0.0) + delta),
+            &Pos2::new(0.0, 0.0),
+            &(Pos2::new(0.0, 0.0) + delta),
+            &Pos2::new(10.0, 0.0),
+            &(Pos2::new(10.0, 0.0) + delta),
+        );
+        let [new_a, new_b] = response.new_pos.unwrap();
+        assert!((new_a.y - 3.0).abs() < 0.001);
+        assert!(new_b.distance(Pos2::new(10.0, 3.0)) < 0.001);
+    }
+
+    #[test]
+    fn coincident_brings_the_vertices_together() {
+        let (dm, mut cm) = managers();
+        let ab = add_edge(&dm, Pos2::new(0.0, 0.0), Pos2::new(10.0, 0.0));
+        let cd = add_edge(&dm, Pos2::new(3.0, 4.0), Pos2::new(3.0, 14.0));
+        let a = dm.borrow().get_edge(ab).unwrap().start_point_vh;
+        let c = dm.borrow().get_edge(cd).unwrap().start_point_vh;
+
+        cm.add_coincident_constraint(a, c).unwrap();
+
+        let position = |vh| dm.borrow().get_vertex(vh).unwrap().position;
+        assert!(position(a).distance(position(c)) < 0.01);
+    }
+
+    #[test]
+    fn point_on_edge_slides_within_the_segment() {
+        let (dm, mut cm) = managers();
+        let base = add_edge(&dm, Pos2::new(0.0, 0.0), Pos2::new(10.0, 0.0));
+        let post = add_edge(&dm, Pos2::new(4.0, 0.0), Pos2::new(4.0, 10.0));
+        let v = dm.borrow().get_edge(post).unwrap().start_point_vh;
+        cm.add_point_on_edge_constraint(v, base).unwrap();
+
+        let response = cm.solve_for_vertex(v, &Pos2::new(4.0, 0.0), &Pos2::new(6.0, 2.0), vec![]);
+        assert!(response.new_pos.unwrap().distance(Pos2::new(6.0, 0.0)) < 0.001);
+
+        // past the end of the edge it stops at the end
+        let response = cm.solve_for_vertex(v, &Pos2::new(4.0, 0.0), &Pos2::new(14.0, 3.0), vec![]);
+        assert!(response.new_pos.unwrap().distance(Pos2::new(10.0, 0.0)) < 0.001);
+    }
+
+    #[test]
+    fn equal_length_end_follows_the_other_length() {
+        let (dm, mut cm) = managers();
+        let ab = add_edge(&dm, Pos2::new(0.0, 0.0), Pos2::new(10.0, 0.0));
+        let cd = add_edge(&dm, Pos2::new(0.0, 5.0), Pos2::new(6.0, 5.0));
+        cm.add_equal_length_constraint(ab, cd).unwrap();
+
+        let length_ab = edge_vector(&dm, ab).length();
+        assert!((length_ab - edge_vector(&dm, cd).length()).abs() < 0.01);
+
+        // an end of either edge keeps to a circle with the other edge's length
+        let a = dm.borrow().get_edge(ab).unwrap().start_point_vh;
+        let b = dm.borrow().get_edge(ab).unwrap().end_point_vh;
+        let position = |vh| dm.borrow().get_vertex(vh).unwrap().position;
+        let response = cm.solve_for_vertex(b, &position(b), &Pos2::new(3.0, 20.0), vec![]);
+        let new_pos = response.new_pos.unwrap();
+        assert!((new_pos.distance(position(a)) - length_ab).abs() < 0.01);
+    }
+
+    #[test]
+    fn symmetric_mirrors_the_pair_across_the_axis() {
+        let (dm, mut cm) = managers();
+        let axis = add_edge(&dm, Pos2::new(0.0, -10.0), Pos2::new(2.0, 10.0));
+        let a = dm.borrow_mut().add_vertex(Pos2::new(-5.0, 2.0));
+        let b = dm.borrow_mut().add_vertex(Pos2::new(4.0, 3.0));
+
+        cm.add_symmetric_constraint(a, b, axis).unwrap();
+
+        let position = |vh| dm.borrow().get_vertex(vh).unwrap().position;
+        let axis_edge = dm.borrow().get_edge(axis).unwrap().clone();
+        let mirrored = reflect_across_line(
+            position(a),
+            position(axis_edge.start_point_vh),
+            position(axis_edge.end_point_vh),
+        );
+        assert!(mirrored.distance(position(b)) < 0.01);
+    }
+
+    #[test]
+    fn reflect_across_a_slanted_line() {
+        let reflected = reflect_across_line(
+            Pos2::new(2.0, 0.0),
+            Pos2::new(0.0, 0.0),
+            Pos2::new(1.0, 1.0),
+        );
+        assert!(reflected.distance(Pos2::new(0.0, 2.0)) < 0.001);
+    }
+
+    #[test]
+    fn distances_keep_their_circle_and_offset_line() {
+        let (dm, mut cm) = managers();
+        let ab = add_edge(&dm, Pos2::new(0.0, 0.0), Pos2::new(10.0, 0.0));
+        let a = dm.borrow().get_edge(ab).unwrap().start_point_vh;
+        let b = dm.borrow().get_edge(ab).unwrap().end_point_vh;
+        let v = dm.borrow_mut().add_vertex(Pos2::new(3.0, 4.0));
+        let w = dm.borrow_mut().add_vertex(Pos2::new(30.0, 6.0));
+
+        // a length is the way to dimension an edge
+        assert!(matches!(
+            cm.add_distance_constraint(a, b),
+            Err(ConstraintError::VerticesShareEdge)
+        ));
+
+        // 5 from a, anywhere around it
+        cm.add_distance_constraint(a, v).unwrap();
+        let response = cm.solve_for_vertex(v, &Pos2::new(3.0, 4.0), &Pos2::new(-8.0, 0.0), vec![]);
+        assert!(response.new_pos.unwrap().distance(Pos2::new(-5.0, 0.0)) < 0.001);
+
+        // 6 above the edge's line, on the side it started
+        cm.add_edge_distance_constraint(w, ab).unwrap();
+        let response = cm.solve_for_vertex(w, &Pos2::new(30.0, 6.0), &Pos2::new(40.0, 9.0), vec![]);
+        assert!(response.new_pos.unwrap().distance(Pos2::new(40.0, 6.0)) < 0.001);
+    }
+
+    #[test]
+    fn group_moves_as_far_as_the_rest_can_follow() {
+        let (dm, mut cm) = managers();
+        let f = dm.borrow_mut().add_vertex(Pos2::new(0.0, 0.0));
+        let m = dm.borrow_mut().add_vertex(Pos2::new(10.0, 0.0));
+        let a = dm.borrow_mut().add_vertex(Pos2::new(10.0, 10.0));
+        let b = dm.borrow_mut().add_vertex(Pos2::new(15.0, 10.0));
+        let fm = dm.borrow_mut().add_edge(f, m).unwrap();
+        let ma = dm.borrow_mut().add_edge(m, a).unwrap();
+        let ab = dm.borrow_mut().add_edge(a, b).unwrap();
+        cm.add_fixed_constraint(f).unwrap();
+        cm.add_length_constraint(fm).unwrap();
+        cm.add_length_constraint(ma).unwrap();
+        cm.add_length_constraint(ab).unwrap();
+
+        let position = |vh| dm.borrow().get_vertex(vh).unwrap().position;
+        let group = BTreeMap::from([(a, position(a)), (b, position(b))]);
+
+        // a small move stays in reach of the two lengths back to f
+        let response = cm.solve_for_group(&group, Vec2::new(2.0, 0.0));
+        assert!(matches!(response.state, SolverState::Free));
+        assert_eq!(response.new_positions[&a], Pos2::new(12.0, 10.0));
+
+        // the chain only reaches 20 from f, the group stops short of that along the drag
+        let response = cm.solve_for_group(&group, Vec2::new(20.0, 0.0));
+        assert!(matches!(response.state, SolverState::Partial));
+        let new_a = response.new_positions[&a];
+        let new_b = response.new_positions[&b];
+        assert_eq!(new_a.y, 10.0);
+        assert!(new_a.x > 10.0);
+        assert!(new_a.distance(Pos2::new(0.0, 0.0)) > 19.5);
+        assert!(new_a.distance(Pos2::new(0.0, 0.0)) < 20.01);
+        assert!((new_b - new_a - Vec2::new(5.0, 0.0)).length() < 0.001);
+
+        // a fixed vertex holds the whole group
+        let group = BTreeMap::from([(f, position(f)), (m, position(m))]);
+        let response = cm.solve_for_group(&group, Vec2::new(2.0, 0.0));
+        assert!(matches!(response.state, SolverState::Locked));
+    }
+}
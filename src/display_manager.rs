@@ -1,30 +1,180 @@
+use crate::camera::Camera;
 use crate::constraint_manager::{
-    Constraint, ConstraintManager, ConstraintPath,  SolverState,
+    rotate_vec2, Constraint, ConstraintError, ConstraintManager, ConstraintPath, DeletionReport,
+    Line, SolverMode, SolverState,
 };
 use crate::drawing_manager::DrawingManager;
+use crate::global_solver::{DofAnalysis, DofStatus, GlobalSolverResponse};
+use crate::handles::{ConstraintHandle, EdgeHandle, VertexHandle};
+use crate::history::{Command, History};
+use crate::svg_export::{self, SvgExportSettings};
 
 use core::f32;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use egui::{emath, Color32, Painter, Pos2, Rect, Response, Sense, Shape, Stroke, Ui, Vec2};
+use egui::{
+    emath, epaint::RectShape, Align2, Color32, Context, FontId, Key, Painter, PointerButton, Pos2,
+    Rect, Response, Sense, Shape, Stroke, Ui, Vec2,
+};
 
 use std::cell::RefCell;
 use std::rc::{Rc, Weak};
 
-type EdgeHandle = i32;
-type VertexHandle = i32;
-type ConstraintHandle = i32;
-
 #[derive(Default)]
 pub struct DisplayManager {
     drawing_manager: Option<Rc<RefCell<DrawingManager>>>,
     constraint_manager: Option<Rc<RefCell<ConstraintManager>>>,
 
-    edges: HashMap<VertexHandle, EdgeDisplay>,
-    vertices: HashMap<EdgeHandle, VertexDisplay>,
+    edges: HashMap<EdgeHandle, EdgeDisplay>,
+    vertices: HashMap<VertexHandle, VertexDisplay>,
     constraints: HashMap<ConstraintHandle, ConstraintDisplay>,
 
     pub constraint_paths: Vec<ConstraintPath>,
+
+    tool: Tool,
+    // last vertex of the polyline being drawn with the line tool
+    polyline_end: Option<VertexHandle>,
+    // where the next click of a drawing tool would land, in sketch coordinates
+    tool_hover_pos: Option<Pos2>,
+
+    history: History,
+    // where each vertex moved by the drag in progress was before it began, taken as each
+    // one first moves and compared against when the pointer comes up, so that a whole
+    // drag goes into the history as one move
+    drag_start_positions: BTreeMap<VertexHandle, Pos2>,
+
+    // colours the sketch, worked out again only once the sketch has changed since it's
+    // costly for large sketches. None until then
+    dof_analysis: Option<DofAnalysis>,
+
+    camera: Camera,
+    // a middle drag that began on the canvas, it pans until the button comes up
+    is_panning: bool,
+    // fitting needs the canvas size, so it waits for the next update
+    is_fit_requested: bool,
+
+    group_drag: Option<GroupDrag>,
+    selection_drag: Option<SelectionDrag>,
+}
+
+// a multi-selection being dragged as one
+struct GroupDrag {
+    // every vertex of the group, where it was when the drag began
+    start_positions: BTreeMap<VertexHandle, Pos2>,
+    // sketch position the drag began at
+    grab_pos: Pos2,
+}
+
+// a rubber-band rectangle between the first and last points, or a lasso through all of
+// them when alt was held as the drag began. the points are in screen coordinates
+struct SelectionDrag {
+    points: Vec<Pos2>,
+    is_lasso: bool,
+}
+
+impl SelectionDrag {
+    fn contains(&self, pos: Pos2) -> bool {
+        if !self.is_lasso {
+            return self.get_rect().contains(pos);
+        }
+
+        // even-odd rule, counting the lasso's crossings of a ray going right from pos
+        let mut is_inside = false;
+        for (i, pt_1) in self.points.iter().enumerate() {
+            let pt_2 = self.points[(i + 1) % self.points.len()];
+            if (pt_1.y > pos.y) != (pt_2.y > pos.y) {
+                let crossing_x = pt_1.x + (pos.y - pt_1.y) / (pt_2.y - pt_1.y) * (pt_2.x - pt_1.x);
+                if pos.x < crossing_x {
+                    is_inside = !is_inside;
+                }
+            }
+        }
+        is_inside
+    }
+
+    fn get_rect(&self) -> Rect {
+        Rect::from_two_pos(self.points[0], *self.points.last().unwrap())
+    }
+
+    fn get_shape(&self) -> Shape {
+        let stroke = Stroke::new(1.0, Color32::LIGHT_BLUE);
+        let fill = Color32::LIGHT_BLUE.gamma_multiply(0.1);
+
+        // a lasso can be any shape, and only convex polygons can be filled
+        if self.is_lasso {
+            Shape::closed_line(self.points.clone(), stroke)
+        } else {
+            Shape::Rect(RectShape::new(self.get_rect(), 0.0, fill, stroke))
+        }
+    }
+}
+
+// Select drags and selects existing geometry, Point places vertices and Line places
+// vertices joined into a polyline until it is finished with escape or a right-click
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Tool {
+    #[default]
+    Select,
+    Point,
+    Line,
+}
+
+// clicks this close to a vertex on screen land on it instead of making a new one
+const SNAP_RADIUS: f32 = 10.0;
+
+// the constraints that can be made from the current selection, each one takes
+// the selected vertices and edges in handle order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConstraintKind {
+    Length,
+    Horizontal,
+    Vertical,
+    Angle,
+    Parallel,
+    Perpendicular,
+    EqualLength,
+    Fixed,
+    Distance,
+    Coincident,
+    EdgeDistance,
+    PointOnEdge,
+    Symmetric,
+}
+
+impl ConstraintKind {
+    pub const ALL: [ConstraintKind; 13] = [
+        ConstraintKind::Length,
+        ConstraintKind::Horizontal,
+        ConstraintKind::Vertical,
+        ConstraintKind::Angle,
+        ConstraintKind::Parallel,
+        ConstraintKind::Perpendicular,
+        ConstraintKind::EqualLength,
+        ConstraintKind::Fixed,
+        ConstraintKind::Distance,
+        ConstraintKind::Coincident,
+        ConstraintKind::EdgeDistance,
+        ConstraintKind::PointOnEdge,
+        ConstraintKind::Symmetric,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ConstraintKind::Length => "Length",
+            ConstraintKind::Horizontal => "Horizontal",
+            ConstraintKind::Vertical => "Vertical",
+            ConstraintKind::Angle => "Angle",
+            ConstraintKind::Parallel => "Parallel",
+            ConstraintKind::Perpendicular => "Perpendicular",
+            ConstraintKind::EqualLength => "Equal length",
+            ConstraintKind::Fixed => "Fixed",
+            ConstraintKind::Distance => "Distance",
+            ConstraintKind::Coincident => "Coincident",
+            ConstraintKind::EdgeDistance => "Edge distance",
+            ConstraintKind::PointOnEdge => "Point on edge",
+            ConstraintKind::Symmetric => "Symmetric",
+        }
+    }
 }
 
 impl DisplayManager {
@@ -38,34 +188,720 @@ impl DisplayManager {
         self.constraint_manager = Some(constraint_manager);
     }
 
-    pub fn update_interaction(&mut self, ui: &Ui, response: &Response) {
+    pub fn get_tool(&self) -> Tool {
+        self.tool
+    }
+    pub fn set_tool(&mut self, tool: Tool) {
+        if tool == self.tool {
+            return;
+        }
+
+        self.tool = tool;
+        self.polyline_end = None;
+        self.tool_hover_pos = None;
+
+        // the drawing tools skip the per item interaction, so nothing would clear these
+        self.vertices
+            .values_mut()
+            .for_each(|v| v.is_hovered = false);
+        self.edges.values_mut().for_each(|e| e.is_hovered = false);
+        self.group_drag = None;
+        self.selection_drag = None;
+    }
+
+    // returns the responses of the vertices and edges, they sit on top of the canvas
+    // so anything that reacts to the pointer over the sketch has to look at them too
+    pub fn update_interaction(&mut self, ui: &Ui, response: &Response) -> Vec<Response> {
         self.constraint_paths.clear();
+        self.update_camera(ui, response);
+
+        let item_responses = if self.tool == Tool::Select {
+            self.update_select_tool(ui, response)
+        } else {
+            self.update_drawing_tool(ui, response);
+            vec![]
+        };
+
+        if self.dof_analysis.is_none() {
+            self.dof_analysis = self
+                .constraint_manager
+                .as_ref()
+                .map(|cm| cm.borrow().analyze_dof());
+        }
+
+        item_responses
+    }
+
+    fn update_select_tool(&mut self, ui: &Ui, response: &Response) -> Vec<Response> {
+        // not halfway through a drag, the same as undo, nor while a text field has the keys
+        let is_delete_pressed =
+            ui.input(|i| i.key_pressed(Key::Delete)) && !ui.ctx().wants_keyboard_input();
+        if is_delete_pressed && self.drag_start_positions.is_empty() {
+            if let Err(err) = self.remove_selection() {
+                println!("{err}");
+            }
+        }
+
+        // with more than one thing selected, dragging any of them moves them all together
+        let (selected_vertices, selected_edges) = self.get_selection();
+        let is_group_selected = selected_vertices.len() + selected_edges.len() > 1;
+
+        let mut item_responses = vec![];
+        let mut clicked_vertex = None;
+        let mut clicked_edge = None;
+        let mut is_group_drag_started = false;
+        let mut is_background_clicked = response.clicked();
+
+        for (eh, edge) in self.edges.iter_mut() {
+            let is_group_member = is_group_selected && edge.is_selected;
+            let edge_response = edge.interact(
+                &mut self.constraint_paths,
+                &mut self.drag_start_positions,
+                ui,
+                response,
+                &self.camera,
+                !is_group_member,
+            );
+
+            // the edge's interact area is its bounding box, only a click on the line counts
+            if edge_response.clicked() {
+                match edge.is_hovered {
+                    true => clicked_edge = Some(*eh),
+                    false => is_background_clicked = true,
+                }
+            }
+            if is_group_member && edge.is_hovered {
+                is_group_drag_started |= edge_response.drag_started_by(PointerButton::Primary);
+            }
+            item_responses.push(edge_response);
+        }
+        for (vh, vertex) in self.vertices.iter_mut() {
+            let is_group_member = is_group_selected && vertex.is_selected;
+            let vertex_response = vertex.interact(
+                &mut self.constraint_paths,
+                &mut self.drag_start_positions,
+                ui,
+                response,
+                &self.camera,
+                !is_group_member,
+            );
+
+            if vertex_response.clicked() {
+                match vertex.is_hovered {
+                    true => clicked_vertex = Some(*vh),
+                    false => is_background_clicked = true,
+                }
+            }
+            if is_group_member && vertex.is_hovered {
+                is_group_drag_started |= vertex_response.drag_started_by(PointerButton::Primary);
+            }
+            item_responses.push(vertex_response);
+        }
+
+        self.update_click_selection(ui, clicked_vertex, clicked_edge, is_background_clicked);
+
+        let to_screen = self.camera.screen_transform(response.rect);
+        let (pointer_pos, press_origin, is_alt_down, is_primary_down) = ui.input(|i| {
+            (
+                i.pointer.interact_pos(),
+                i.pointer.press_origin(),
+                i.modifiers.alt,
+                i.pointer.primary_down(),
+            )
+        });
+
+        if is_group_drag_started {
+            if let Some(press_origin) = press_origin {
+                self.group_drag = Some(GroupDrag {
+                    start_positions: self.get_group_positions(&selected_vertices, &selected_edges),
+                    grab_pos: to_screen.inverse().transform_pos(press_origin),
+                });
+            }
+        } else if self.is_drag_started(response, &item_responses) {
+            // a drag nothing picked up, from empty canvas or from beside an edge
+            if let Some(press_origin) = press_origin {
+                self.selection_drag = Some(SelectionDrag {
+                    points: vec![press_origin],
+                    is_lasso: is_alt_down,
+                });
+            }
+        }
+
+        if !is_primary_down {
+            self.group_drag = None;
+            if let Some(selection_drag) = self.selection_drag.take() {
+                self.update_area_selection(ui, &to_screen, &selection_drag);
+            }
+        }
+
+        if let (Some(group_drag), Some(pointer_pos)) = (&self.group_drag, pointer_pos) {
+            let delta = to_screen.inverse().transform_pos(pointer_pos) - group_drag.grab_pos;
+            let group_response = self
+                .constraint_manager
+                .as_ref()
+                .unwrap()
+                .borrow()
+                .solve_for_group(&group_drag.start_positions, delta);
+
+            let mut dm_borrowed = self.drawing_manager.as_ref().unwrap().borrow_mut();
+            for (vh, pos) in group_response.new_positions {
+                move_vertex(&mut dm_borrowed, &mut self.drag_start_positions, vh, pos);
+            }
+        }
+
+        if let (Some(selection_drag), Some(pointer_pos)) = (&mut self.selection_drag, pointer_pos) {
+            if selection_drag.points.last() != Some(&pointer_pos) {
+                selection_drag.points.push(pointer_pos);
+            }
+        }
+
+        // the drags have all ended by the time the pointer is up
+        if !ui.input(|i| i.pointer.primary_down()) && !self.drag_start_positions.is_empty() {
+            let drag_start_positions = std::mem::take(&mut self.drag_start_positions);
+            self.record_moves(&drag_start_positions);
+            self.history.commit();
+            self.dof_analysis = None;
+        }
+
+        item_responses
+    }
+
+    // a plain click selects just what was clicked, or clears the selection on empty canvas.
+    // shift-click adds to the selection or takes back out what is already in it
+    fn update_click_selection(
+        &mut self,
+        ui: &Ui,
+        clicked_vertex: Option<VertexHandle>,
+        clicked_edge: Option<EdgeHandle>,
+        is_background_clicked: bool,
+    ) {
+        let is_additive = ui.input(|i| i.modifiers.shift);
+
+        if clicked_vertex.is_none() && clicked_edge.is_none() {
+            if is_background_clicked && !is_additive {
+                self.clear_selection();
+            }
+            return;
+        }
+
+        let was_selected = clicked_vertex.is_some_and(|vh| self.vertices[&vh].is_selected)
+            || clicked_edge.is_some_and(|eh| self.edges[&eh].is_selected);
+        if !is_additive {
+            self.clear_selection();
+        }
+        let is_selected = !(is_additive && was_selected);
+
+        if let Some(vh) = clicked_vertex {
+            self.vertices.get_mut(&vh).unwrap().is_selected = is_selected;
+        }
+        if let Some(eh) = clicked_edge {
+            self.edges.get_mut(&eh).unwrap().is_selected = is_selected;
+        }
+    }
+
+    // whether a primary drag began this frame without a vertex or edge taking it
+    fn is_drag_started(&self, response: &Response, item_responses: &[Response]) -> bool {
+        let is_drag_started = std::iter::once(response)
+            .chain(item_responses)
+            .any(|r| r.drag_started_by(PointerButton::Primary));
+        let is_item_dragged = self.vertices.values().any(|v| v.is_being_dragged)
+            || self.edges.values().any(|e| e.is_being_dragged);
+
+        is_drag_started && !is_item_dragged && self.group_drag.is_none()
+    }
+
+    // selects whatever the rectangle or lasso encloses, an edge needs both its ends inside.
+    // shift adds to the selection instead of replacing it
+    fn update_area_selection(
+        &mut self,
+        ui: &Ui,
+        to_screen: &emath::RectTransform,
+        selection_drag: &SelectionDrag,
+    ) {
+        if !ui.input(|i| i.modifiers.shift) {
+            self.clear_selection();
+        }
+
+        let enclosed_vertices: Vec<VertexHandle> = self
+            .vertices
+            .iter()
+            .filter(|(_, v)| selection_drag.contains(to_screen.transform_pos(v.get_vertex_point())))
+            .map(|(vh, _)| *vh)
+            .collect();
+
+        for vh in &enclosed_vertices {
+            self.vertices.get_mut(vh).unwrap().is_selected = true;
+        }
+
+        let dm_borrowed = self.drawing_manager.as_ref().unwrap().borrow();
+        for (eh, edge) in self.edges.iter_mut() {
+            let dm_edge = dm_borrowed.get_edge(*eh).unwrap();
+            if enclosed_vertices.contains(&dm_edge.start_point_vh)
+                && enclosed_vertices.contains(&dm_edge.end_point_vh)
+            {
+                edge.is_selected = true;
+            }
+        }
+    }
+
+    // the selected vertices and the ends of the selected edges, where they are now
+    fn get_group_positions(
+        &self,
+        selected_vertices: &[VertexHandle],
+        selected_edges: &[EdgeHandle],
+    ) -> BTreeMap<VertexHandle, Pos2> {
+        let dm_borrowed = self.drawing_manager.as_ref().unwrap().borrow();
+
+        let mut group = selected_vertices.to_vec();
+        for eh in selected_edges {
+            let edge = dm_borrowed.get_edge(*eh).unwrap();
+            group.push(edge.start_point_vh);
+            group.push(edge.end_point_vh);
+        }
+
+        group
+            .into_iter()
+            .map(|vh| (vh, dm_borrowed.get_vertex(vh).unwrap().position))
+            .collect()
+    }
+
+    // wheel zooms about the cursor, middle drag pans. this reads the pointer directly
+    // rather than through the canvas response, the vertices and edges cover it
+    fn update_camera(&mut self, ui: &Ui, response: &Response) {
+        if self.is_fit_requested {
+            self.is_fit_requested = false;
+            let bounds = self.get_sketch_bounds();
+            self.camera.fit(response.rect, bounds, 40.0);
+        }
+
+        let is_over_canvas = ui.rect_contains_pointer(response.rect);
+        let (pointer_pos, pointer_delta, zoom_factor, middle_pressed, middle_down) =
+            ui.input(|i| {
+                (
+                    i.pointer.hover_pos(),
+                    i.pointer.delta(),
+                    i.zoom_delta() * (i.smooth_scroll_delta.y / 200.0).exp(),
+                    i.pointer.button_pressed(PointerButton::Middle),
+                    i.pointer.middle_down(),
+                )
+            });
+
+        if is_over_canvas && middle_pressed {
+            self.is_panning = true;
+        }
+        if !middle_down {
+            self.is_panning = false;
+        }
+        if self.is_panning {
+            self.camera.pan(pointer_delta);
+        }
+
+        if let (true, Some(pointer_pos)) = (is_over_canvas, pointer_pos) {
+            if zoom_factor != 1.0 {
+                self.camera
+                    .zoom_about(response.rect, pointer_pos, zoom_factor);
+            }
+        }
+    }
+
+    pub fn zoom_to_fit(&mut self) {
+        self.is_fit_requested = true;
+    }
+
+    fn get_sketch_bounds(&self) -> Rect {
+        let dm_borrowed = self.drawing_manager.as_ref().unwrap().borrow();
+        let mut bounds = Rect::NOTHING;
+        for vh in dm_borrowed.get_all_vertex_handles() {
+            bounds.extend_with(dm_borrowed.get_vertex(vh).unwrap().position);
+        }
+        bounds
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    // neither is allowed halfway through a drag, the drag hasn't gone into the history yet
+    pub fn undo(&mut self) {
+        if !self.drag_start_positions.is_empty() {
+            return;
+        }
+
+        if self.history.undo(
+            self.drawing_manager.as_ref().unwrap(),
+            self.constraint_manager.as_ref().unwrap(),
+        ) {
+            self.sync_displays();
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if !self.drag_start_positions.is_empty() {
+            return;
+        }
+
+        if self.history.redo(
+            self.drawing_manager.as_ref().unwrap(),
+            self.constraint_manager.as_ref().unwrap(),
+        ) {
+            self.sync_displays();
+        }
+    }
+
+    fn get_vertex_positions(&self) -> BTreeMap<VertexHandle, Pos2> {
+        let dm_borrowed = self.drawing_manager.as_ref().unwrap().borrow();
+        dm_borrowed
+            .get_all_vertex_handles()
+            .into_iter()
+            .map(|vh| (vh, dm_borrowed.get_vertex(vh).unwrap().position))
+            .collect()
+    }
+
+    // records every vertex that has moved away from where it was, if any did
+    fn record_moves(&mut self, previous_positions: &BTreeMap<VertexHandle, Pos2>) {
+        let moves = self.get_moves(previous_positions);
+
+        if !moves.is_empty() {
+            self.history.record(Command::MoveVertices(moves));
+        }
+    }
+
+    fn get_moves(
+        &self,
+        previous_positions: &BTreeMap<VertexHandle, Pos2>,
+    ) -> Vec<(VertexHandle, Pos2, Pos2)> {
+        let dm_borrowed = self.drawing_manager.as_ref().unwrap().borrow();
+        previous_positions
+            .iter()
+            .filter_map(|(vh, before)| {
+                let after = dm_borrowed.get_vertex(*vh).ok()?.position;
+                (after != *before).then_some((*vh, *before, after))
+            })
+            .collect()
+    }
+
+    // changes the value of a length, distance or angle constraint and drives the sketch
+    // to it as one undo step. distances are unsigned and angles in radians
+    pub fn set_constraint_value(
+        &mut self,
+        ch: ConstraintHandle,
+        value: f32,
+    ) -> Result<(), ConstraintError> {
+        let cm_shared = Rc::clone(self.constraint_manager.as_ref().unwrap());
+        let old = cm_shared.borrow().get_constraint_value(ch)?;
+        // the solve is global, anything in the sketch can move
+        let previous_positions = self.get_vertex_positions();
+
+        {
+            let mut cm_borrowed = cm_shared.borrow_mut();
+            match cm_borrowed.get_constraint(ch)? {
+                Constraint::LENGTH(_) => cm_borrowed.set_length_value(ch, value),
+                Constraint::ANGLE(_) => cm_borrowed.set_angle_value(ch, value),
+                _ => cm_borrowed.set_distance_value(ch, value),
+            }?;
+        }
 
-        self.edges.iter_mut().for_each(|e| {
-            e.1.interact(&mut self.constraint_paths, ui, response);
+        let new = cm_shared.borrow().get_constraint_value(ch)?;
+        let moves = self.get_moves(&previous_positions);
+        self.history.record(Command::SetValue {
+            handle: ch,
+            old,
+            new,
+            moves,
         });
-        self.vertices.iter_mut().for_each(|v| {
-            v.1.interact(&mut self.constraint_paths, ui, response);
+        self.history.commit();
+        self.dof_analysis = None;
+
+        Ok(())
+    }
+
+    // adds and drops displays until there is exactly one for everything in the sketch,
+    // the selection and drag state of the ones that stay is kept
+    fn sync_displays(&mut self) {
+        // undo and redo move geometry around even when no display comes or goes
+        self.dof_analysis = None;
+        let vertex_handles = self
+            .drawing_manager
+            .as_ref()
+            .unwrap()
+            .borrow()
+            .get_all_vertex_handles();
+        let edge_handles = self
+            .drawing_manager
+            .as_ref()
+            .unwrap()
+            .borrow()
+            .get_all_edge_handles();
+        let constraint_handles = self
+            .constraint_manager
+            .as_ref()
+            .unwrap()
+            .borrow()
+            .get_all_constraint_handles();
+
+        self.vertices
+            .retain(|vh, _| vertex_handles.binary_search(vh).is_ok());
+        self.edges
+            .retain(|eh, _| edge_handles.binary_search(eh).is_ok());
+        self.constraints
+            .retain(|ch, _| constraint_handles.binary_search(ch).is_ok());
+
+        for vh in vertex_handles {
+            if !self.vertices.contains_key(&vh) {
+                self.add_vertex(vh);
+            }
+        }
+        for eh in edge_handles {
+            if !self.edges.contains_key(&eh) {
+                self.add_edge(eh);
+            }
+        }
+        for ch in constraint_handles {
+            if !self.constraints.contains_key(&ch) {
+                self.add_constraint(ch);
+            }
+        }
+
+        if let Some(vh) = self.polyline_end {
+            if !self.vertices.contains_key(&vh) {
+                self.polyline_end = None;
+            }
+        }
+    }
+
+    pub fn get_selection(&self) -> (Vec<VertexHandle>, Vec<EdgeHandle>) {
+        let mut selected_vertices: Vec<VertexHandle> = self
+            .vertices
+            .iter()
+            .filter(|(_, v)| v.is_selected)
+            .map(|(vh, _)| *vh)
+            .collect();
+        let mut selected_edges: Vec<EdgeHandle> = self
+            .edges
+            .iter()
+            .filter(|(_, e)| e.is_selected)
+            .map(|(eh, _)| *eh)
+            .collect();
+
+        selected_vertices.sort();
+        selected_edges.sort();
+        (selected_vertices, selected_edges)
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.vertices
+            .values_mut()
+            .for_each(|v| v.is_selected = false);
+        self.edges.values_mut().for_each(|e| e.is_selected = false);
+    }
+
+    // makes the constraint out of exactly the selection it needs, anything more or less
+    // is refused before the constraint manager is asked. the selection is used up on success
+    pub fn add_constraint_from_selection(
+        &mut self,
+        kind: ConstraintKind,
+    ) -> Result<ConstraintHandle, ConstraintError> {
+        let (vertices, edges) = self.get_selection();
+        // adding a constraint snaps the geometry to it, that has to be undone as well
+        let previous_positions = self.get_vertex_positions();
+
+        let cm_shared = Rc::clone(self.constraint_manager.as_ref().unwrap());
+        let mut cm_borrowed = cm_shared.borrow_mut();
+
+        let ch = match (kind, vertices.as_slice(), edges.as_slice()) {
+            (ConstraintKind::Length, [], [eh]) => cm_borrowed.add_length_constraint(*eh),
+            (ConstraintKind::Horizontal, [], [eh]) => cm_borrowed.add_horizontal_constraint(*eh),
+            (ConstraintKind::Vertical, [], [eh]) => cm_borrowed.add_vertical_constraint(*eh),
+            (ConstraintKind::Angle, [], [eh_1, eh_2]) => {
+                cm_borrowed.add_angle_constraint(*eh_1, *eh_2)
+            }
+            (ConstraintKind::Parallel, [], [eh_1, eh_2]) => {
+                cm_borrowed.add_parallel_constraint(*eh_1, *eh_2)
+            }
+            (ConstraintKind::Perpendicular, [], [eh_1, eh_2]) => {
+                cm_borrowed.add_perpendicular_constraint(*eh_1, *eh_2)
+            }
+            (ConstraintKind::EqualLength, [], [eh_1, eh_2]) => {
+                cm_borrowed.add_equal_length_constraint(*eh_1, *eh_2)
+            }
+            (ConstraintKind::Fixed, [vh], []) => cm_borrowed.add_fixed_constraint(*vh),
+            (ConstraintKind::Distance, [vh_1, vh_2], []) => {
+                cm_borrowed.add_distance_constraint(*vh_1, *vh_2)
+            }
+            (ConstraintKind::Coincident, [vh_1, vh_2], []) => {
+                cm_borrowed.add_coincident_constraint(*vh_1, *vh_2)
+            }
+            (ConstraintKind::EdgeDistance, [vh], [eh]) => {
+                cm_borrowed.add_edge_distance_constraint(*vh, *eh)
+            }
+            (ConstraintKind::PointOnEdge, [vh], [eh]) => {
+                cm_borrowed.add_point_on_edge_constraint(*vh, *eh)
+            }
+            (ConstraintKind::Symmetric, [vh_1, vh_2], [eh]) => {
+                cm_borrowed.add_symmetric_constraint(*vh_1, *vh_2, *eh)
+            }
+            (
+                ConstraintKind::Length | ConstraintKind::Horizontal | ConstraintKind::Vertical,
+                ..,
+            ) => Err(ConstraintError::InvalidSelection("one edge")),
+            (
+                ConstraintKind::Angle
+                | ConstraintKind::Parallel
+                | ConstraintKind::Perpendicular
+                | ConstraintKind::EqualLength,
+                ..,
+            ) => Err(ConstraintError::InvalidSelection("two edges")),
+            (ConstraintKind::Fixed, ..) => Err(ConstraintError::InvalidSelection("one vertex")),
+            (ConstraintKind::Distance | ConstraintKind::Coincident, ..) => {
+                Err(ConstraintError::InvalidSelection("two vertices"))
+            }
+            (ConstraintKind::EdgeDistance | ConstraintKind::PointOnEdge, ..) => {
+                Err(ConstraintError::InvalidSelection("one vertex and one edge"))
+            }
+            (ConstraintKind::Symmetric, ..) => Err(ConstraintError::InvalidSelection(
+                "two vertices and an axis edge",
+            )),
+        }?;
+
+        self.history.record(Command::AddConstraint {
+            handle: ch,
+            constraint: cm_borrowed.get_constraint(ch).unwrap().clone(),
+        });
+        drop(cm_borrowed);
+        self.record_moves(&previous_positions);
+        self.history.commit();
+
+        self.add_constraint(ch);
+        self.clear_selection();
+        Ok(ch)
+    }
+
+    fn update_drawing_tool(&mut self, ui: &Ui, response: &Response) {
+        let to_screen = self.camera.screen_transform(response.rect);
+        let from_screen = to_screen.inverse();
+
+        if ui.input(|i| i.key_pressed(Key::Escape)) || response.secondary_clicked() {
+            self.polyline_end = None;
+        }
+
+        self.tool_hover_pos = response.hover_pos().map(|pos| {
+            let pos = from_screen.transform_pos(pos);
+            match self.vertex_at(pos) {
+                Some(vh) => self.vertices[&vh].get_vertex_point(),
+                None => pos,
+            }
         });
+
+        if !response.clicked() {
+            return;
+        }
+        if let Some(pos) = response.interact_pointer_pos() {
+            self.place_vertex(from_screen.transform_pos(pos));
+        }
+    }
+
+    // a click of the point or line tool, landing on an existing vertex when it is close
+    fn place_vertex(&mut self, click_pos: Pos2) {
+        let vh = match self.vertex_at(click_pos) {
+            Some(vh) => vh,
+            None => {
+                let vh = self
+                    .drawing_manager
+                    .as_ref()
+                    .unwrap()
+                    .borrow_mut()
+                    .add_vertex(click_pos);
+                self.add_vertex(vh);
+                self.history.record(Command::AddVertex {
+                    handle: vh,
+                    position: click_pos,
+                });
+                vh
+            }
+        };
+
+        if self.tool == Tool::Line {
+            self.continue_polyline(vh);
+        }
+        // the new vertex and the edge to it undo together
+        self.history.commit();
+    }
+
+    fn continue_polyline(&mut self, vh: VertexHandle) {
+        if let Some(end_vh) = self.polyline_end {
+            let dm_shared = Rc::clone(self.drawing_manager.as_ref().unwrap());
+            let mut dm_borrowed = dm_shared.borrow_mut();
+
+            let is_joined = dm_borrowed
+                .get_vertex(end_vh)
+                .unwrap()
+                .edge_handles
+                .iter()
+                .any(|eh| {
+                    let edge = dm_borrowed.get_edge(*eh).unwrap();
+                    edge.start_point_vh == vh || edge.end_point_vh == vh
+                });
+
+            if end_vh != vh && !is_joined {
+                let eh = dm_borrowed.add_edge(end_vh, vh).unwrap();
+                drop(dm_borrowed);
+                self.add_edge(eh);
+                self.history.record(Command::AddEdge {
+                    handle: eh,
+                    start_point_vh: end_vh,
+                    end_point_vh: vh,
+                });
+            }
+        }
+        self.polyline_end = Some(vh);
+    }
+
+    // the closest vertex within snapping distance of a sketch position
+    fn vertex_at(&self, pos: Pos2) -> Option<VertexHandle> {
+        self.vertices
+            .iter()
+            .map(|(vh, v)| (*vh, v.get_vertex_point().distance(pos)))
+            .filter(|(_, dist)| *dist <= self.camera.sketch_length(SNAP_RADIUS))
+            .min_by(|(_, dist_1), (_, dist_2)| dist_1.total_cmp(dist_2))
+            .map(|(vh, _)| vh)
     }
 
     pub fn draw(&self, response: &Response, painter: &Painter) {
+        let to_screen = self.camera.screen_transform(response.rect);
+
         let const_shapes = self.generate_constraint_shapes(response);
 
         painter.extend(const_shapes);
 
+        self.draw_tool_preview(response, painter);
+
+        // colour the sketch by how constrained each part of it is. the analysis is only
+        // out of date here if the sketch changed after this frame's interaction
+        let fresh_analysis;
+        let dof_analysis = match (&self.dof_analysis, &self.constraint_manager) {
+            (Some(dof_analysis), _) => dof_analysis,
+            (None, Some(cm)) => {
+                fresh_analysis = cm.borrow().analyze_dof();
+                &fresh_analysis
+            }
+            (None, None) => &DofAnalysis::default(),
+        };
+
         let segments: Vec<Shape> = self
             .edges
-            .iter()
-            .map(|(_, edge)| edge.get_shape(&response))
+            .values()
+            .map(|edge| edge.get_shape(&to_screen, dof_analysis))
             .collect();
 
         painter.extend(segments);
 
         let vertices: Vec<Shape> = self
             .vertices
-            .iter()
-            .map(|(_, vertex)| vertex.get_shape(&response))
+            .values()
+            .map(|vertex| vertex.get_shape(&to_screen, dof_analysis))
             .collect();
 
         painter.extend(vertices);
@@ -73,20 +909,46 @@ impl DisplayManager {
         let mut constr_shapes: Vec<Shape> = vec![];
 
         for constraint in &self.constraints {
-            constr_shapes.extend(constraint.1.get_shape(&response));
+            constr_shapes.extend(constraint.1.get_shape(&response.ctx, &to_screen));
         }
 
         painter.extend(constr_shapes);
+
+        if let Some(selection_drag) = &self.selection_drag {
+            painter.add(selection_drag.get_shape());
+        }
+    }
+
+    // the edge the line tool would add next, and a marker where the click would land
+    fn draw_tool_preview(&self, response: &Response, painter: &Painter) {
+        let hover_pos = match (self.tool, self.tool_hover_pos) {
+            (Tool::Select, _) | (_, None) => return,
+            (_, Some(pos)) => pos,
+        };
+
+        let to_screen = self.camera.screen_transform(response.rect);
+        let stroke = Stroke::new(1.5, Color32::GRAY);
+
+        if let (Tool::Line, Some(end_vh)) = (self.tool, self.polyline_end) {
+            if let Some(end_vertex) = self.vertices.get(&end_vh) {
+                painter.line_segment(
+                    [
+                        to_screen.transform_pos(end_vertex.get_vertex_point()),
+                        to_screen.transform_pos(hover_pos),
+                    ],
+                    stroke,
+                );
+            }
+        }
+
+        painter.circle_stroke(to_screen.transform_pos(hover_pos), 6.0, stroke);
     }
 
     pub fn generate_constraint_shapes(&self, response: &Response) -> Vec<Shape> {
         let constraint_color = Color32::LIGHT_RED;
         let mut shapes: Vec<Shape> = vec![];
 
-        let to_screen = emath::RectTransform::from_to(
-            Rect::from_min_size(Pos2::ZERO, response.rect.size()),
-            response.rect,
-        );
+        let to_screen = self.camera.screen_transform(response.rect);
         for path in &self.constraint_paths {
             match path {
                 ConstraintPath::Circle(c) => {
@@ -94,30 +956,53 @@ impl DisplayManager {
 
                     shapes.push(Shape::circle_stroke(
                         point_in_screen,
-                        c.radius,
+                        c.radius * self.camera.scale,
                         Stroke::new(2.0, constraint_color),
                     ));
                 }
                 ConstraintPath::Line(l) => {
                     let point_in_screen = to_screen.transform_pos(l.origin);
+                    if let Some(segment) = clip_line(
+                        point_in_screen,
+                        l.direction,
+                        f32::NEG_INFINITY,
+                        response.rect,
+                    ) {
+                        shapes.push(Shape::line_segment(
+                            segment,
+                            Stroke::new(2.0, constraint_color),
+                        ));
+                    }
+                }
+                ConstraintPath::Ray(r) => {
+                    let point_in_screen = to_screen.transform_pos(r.origin);
+                    if let Some(segment) =
+                        clip_line(point_in_screen, r.direction, 0.0, response.rect)
+                    {
+                        shapes.push(Shape::line_segment(
+                            segment,
+                            Stroke::new(2.0, constraint_color),
+                        ));
+                    }
+                }
+                ConstraintPath::Segment(s) => {
                     shapes.push(Shape::line_segment(
                         [
-                            point_in_screen + l.direction * -5000.,
-                            point_in_screen + l.direction * 5000.,
+                            to_screen.transform_pos(s.start),
+                            to_screen.transform_pos(s.end),
                         ],
                         Stroke::new(2.0, constraint_color),
                     ));
                 }
-                ConstraintPath::Ray(r) => {
-                    let point_in_screen = to_screen.transform_pos(r.origin);
+                ConstraintPath::Point(p) => {
+                    let point_in_screen = to_screen.transform_pos(p.origin);
 
-                    shapes.push(Shape::line_segment(
-                        [point_in_screen, point_in_screen + r.direction * 5000.],
+                    shapes.push(Shape::circle_stroke(
+                        point_in_screen,
+                        14.0,
                         Stroke::new(2.0, constraint_color),
                     ));
                 }
-                // ConstraintPath::Point(p) => p.closest_point(point),
-                _ => (),
             }
         }
 
@@ -135,6 +1020,7 @@ impl DisplayManager {
             vertex_handle,
             VertexDisplay::new(dm_weak, constr_weak, vertex_handle),
         );
+        self.dof_analysis = None;
         println!("vert added");
     }
 
@@ -145,19 +1031,209 @@ impl DisplayManager {
             edge_handle,
             EdgeDisplay::new(weak, constr_weak, edge_handle),
         );
+        self.dof_analysis = None;
         println!("edge added");
     }
 
-    pub fn add_constraint(&mut self, constraint_handle: ConstraintHandle) {
-        let weak_dm = Rc::downgrade(self.drawing_manager.as_mut().unwrap());
-        let weak_cm = Rc::downgrade(self.constraint_manager.as_mut().unwrap());
-        self.constraints.insert(
-            constraint_handle,
-            ConstraintDisplay::new(weak_dm, weak_cm, constraint_handle),
-        );
+    pub fn add_constraint(&mut self, constraint_handle: ConstraintHandle) {
+        let weak_dm = Rc::downgrade(self.drawing_manager.as_mut().unwrap());
+        let weak_cm = Rc::downgrade(self.constraint_manager.as_mut().unwrap());
+        self.constraints.insert(
+            constraint_handle,
+            ConstraintDisplay::new(weak_dm, weak_cm, constraint_handle),
+        );
+        self.dof_analysis = None;
+    }
+
+    // the remove calls go through the constraint manager so nothing is left referring to
+    // deleted geometry, then drop the displays for everything that went
+    pub fn remove_vertex(
+        &mut self,
+        vertex_handle: VertexHandle,
+    ) -> Result<DeletionReport, ConstraintError> {
+        let report = self
+            .constraint_manager
+            .as_ref()
+            .unwrap()
+            .borrow_mut()
+            .remove_vertex(vertex_handle)?;
+        self.record_removal(&report);
+        self.history.commit();
+        Ok(report)
+    }
+
+    pub fn remove_edge(
+        &mut self,
+        edge_handle: EdgeHandle,
+    ) -> Result<DeletionReport, ConstraintError> {
+        let report = self
+            .constraint_manager
+            .as_ref()
+            .unwrap()
+            .borrow_mut()
+            .remove_edge(edge_handle)?;
+        self.record_removal(&report);
+        self.history.commit();
+        Ok(report)
+    }
+
+    pub fn remove_constraint(
+        &mut self,
+        constraint_handle: ConstraintHandle,
+    ) -> Result<DeletionReport, ConstraintError> {
+        let report = self
+            .constraint_manager
+            .as_ref()
+            .unwrap()
+            .borrow_mut()
+            .remove_constraint(constraint_handle)?;
+        self.record_removal(&report);
+        self.history.commit();
+        Ok(report)
+    }
+
+    // removes the selected edges and vertices as one undo step. anything already taken
+    // along by an earlier removal is skipped
+    pub fn remove_selection(&mut self) -> Result<(), ConstraintError> {
+        let (selected_vertices, selected_edges) = self.get_selection();
+        let cm_shared = Rc::clone(self.constraint_manager.as_ref().unwrap());
+
+        for eh in selected_edges {
+            if self.edges.contains_key(&eh) {
+                let report = cm_shared.borrow_mut().remove_edge(eh)?;
+                self.record_removal(&report);
+            }
+        }
+        for vh in selected_vertices {
+            if self.vertices.contains_key(&vh) {
+                let report = cm_shared.borrow_mut().remove_vertex(vh)?;
+                self.record_removal(&report);
+            }
+        }
+
+        self.history.commit();
+        Ok(())
+    }
+
+    fn record_removal(&mut self, report: &DeletionReport) {
+        self.remove_displays(report);
+        self.history.record(Command::Remove(report.clone()));
+    }
+
+    // annotations are drawn the same way as on screen, just in sketch coordinates
+    pub fn export_svg(&self, ctx: &Context, settings: &SvgExportSettings) -> String {
+        let mut annotations = vec![];
+        if settings.show_constraints {
+            let sketch_space =
+                emath::RectTransform::identity(Rect::from_min_size(Pos2::ZERO, Vec2::splat(1.0)));
+            // handle order so the same sketch always writes the same file
+            let mut constraint_handles: Vec<&ConstraintHandle> = self.constraints.keys().collect();
+            constraint_handles.sort();
+            for ch in constraint_handles {
+                annotations.extend(self.constraints[ch].get_shape(ctx, &sketch_space));
+            }
+        }
+
+        svg_export::sketch_to_svg(
+            &self.drawing_manager.as_ref().unwrap().borrow(),
+            &annotations,
+            settings,
+        )
+    }
+
+    pub fn to_record(&self) -> DisplayManagerRecord {
+        let mut record = DisplayManagerRecord {
+            camera: self.camera,
+            ..Default::default()
+        };
+        for (vh, vertex) in &self.vertices {
+            if vertex.is_selected {
+                record.selected_vertices.push(*vh);
+            }
+        }
+        for (eh, edge) in &self.edges {
+            if edge.is_selected {
+                record.selected_edges.push(*eh);
+            }
+        }
+        record.selected_vertices.sort();
+        record.selected_edges.sort();
+        record
+    }
+
+    // rebuilds a display for everything in the drawing and constraint managers, which
+    // have to be loaded already, then puts the saved selection back
+    pub fn load_record(&mut self, record: DisplayManagerRecord) {
+        self.camera = record.camera;
+        self.vertices.clear();
+        self.edges.clear();
+        self.constraints.clear();
+
+        let vertex_handles = self
+            .drawing_manager
+            .as_ref()
+            .unwrap()
+            .borrow()
+            .get_all_vertex_handles();
+        let edge_handles = self
+            .drawing_manager
+            .as_ref()
+            .unwrap()
+            .borrow()
+            .get_all_edge_handles();
+        let constraint_handles = self
+            .constraint_manager
+            .as_ref()
+            .unwrap()
+            .borrow()
+            .get_all_constraint_handles();
+
+        vertex_handles
+            .into_iter()
+            .for_each(|vh| self.add_vertex(vh));
+        edge_handles.into_iter().for_each(|eh| self.add_edge(eh));
+        constraint_handles
+            .into_iter()
+            .for_each(|ch| self.add_constraint(ch));
+
+        for vh in record.selected_vertices {
+            if let Some(vertex) = self.vertices.get_mut(&vh) {
+                vertex.is_selected = true;
+            }
+        }
+        for eh in record.selected_edges {
+            if let Some(edge) = self.edges.get_mut(&eh) {
+                edge.is_selected = true;
+            }
+        }
+    }
+
+    fn remove_displays(&mut self, report: &DeletionReport) {
+        self.dof_analysis = None;
+        for (vh, _) in &report.vertices {
+            self.vertices.remove(vh);
+            if self.polyline_end == Some(*vh) {
+                self.polyline_end = None;
+            }
+        }
+        for (eh, _) in &report.edges {
+            self.edges.remove(eh);
+        }
+        for (ch, _) in &report.constraints {
+            self.constraints.remove(ch);
+        }
     }
 }
 
+// the saved form of the view, the displays themselves are rebuilt from the sketch
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct DisplayManagerRecord {
+    pub selected_vertices: Vec<VertexHandle>,
+    pub selected_edges: Vec<EdgeHandle>,
+    pub camera: Camera,
+}
+
 pub struct VertexDisplay {
     drawing_manager: Weak<RefCell<DrawingManager>>,
     constraint_manager: Weak<RefCell<ConstraintManager>>,
@@ -192,15 +1268,16 @@ impl VertexDisplay {
     pub fn interact(
         &mut self,
         constraint_paths: &mut Vec<ConstraintPath>,
+        drag_start_positions: &mut BTreeMap<VertexHandle, Pos2>,
         ui: &Ui,
         response: &Response,
-    ) {
+        camera: &Camera,
+        // false while the display manager drags this along with the rest of a group
+        can_drag: bool,
+    ) -> Response {
         let buffer_size = Vec2::splat(30.0);
 
-        let to_screen = emath::RectTransform::from_to(
-            Rect::from_min_size(Pos2::ZERO, response.rect.size()),
-            response.rect,
-        );
+        let to_screen = camera.screen_transform(response.rect);
 
         let point_in_screen = to_screen.transform_pos(self.get_vertex_point());
         let point_rect = Rect::from_center_size(point_in_screen, buffer_size);
@@ -215,17 +1292,16 @@ impl VertexDisplay {
             let cursor_pt = point_response.hover_pos().unwrap();
             let cursor_pt = to_screen.inverse().transform_pos(cursor_pt);
 
-            let is_on_vertex = self.is_point_on_vertex(cursor_pt, 10.0);
+            let is_on_vertex = self.is_point_on_vertex(cursor_pt, camera.sketch_length(10.0));
 
             if is_on_vertex {
                 self.is_hovered = true;
 
-                if point_response.clicked() {
-                    self.is_selected = !self.is_selected;
-                }
-
                 // drag begins -- initiate drag parameters
-                if !self.is_being_dragged && point_response.dragged() {
+                if can_drag
+                    && !self.is_being_dragged
+                    && point_response.dragged_by(PointerButton::Primary)
+                {
                     // set previous position before starting to drag
                     let dm_shared = self.drawing_manager.upgrade().unwrap();
                     let mut dm_borrow = dm_shared.as_ref().borrow_mut();
@@ -244,7 +1320,7 @@ impl VertexDisplay {
         // drag ends
         // this is outside of the hovered() call so that it will
         // properly be called without being in the interact region
-        if self.is_being_dragged && !point_response.dragged() {
+        if self.is_being_dragged && !point_response.dragged_by(PointerButton::Primary) {
             self.is_being_dragged = false;
             println!("drag end");
         }
@@ -260,6 +1336,16 @@ impl VertexDisplay {
 
             let try_pt = self.current_drag_position;
 
+            if constr_borrow.get_solver_mode() == SolverMode::Global {
+                let global_response = constr_borrow.solve_global(&[(self.vertex_handle, try_pt)]);
+                apply_global_response(
+                    &self.drawing_manager,
+                    drag_start_positions,
+                    &global_response,
+                );
+                return point_response;
+            }
+
             let solver_response = constr_borrow.solve_for_vertex(
                 self.vertex_handle,
                 &self.pre_drag_position,
@@ -274,30 +1360,53 @@ impl VertexDisplay {
             // get mutable vertex again, so we can modify it
             let dm_shared = self.drawing_manager.upgrade().unwrap();
             let mut dm_borrow = dm_shared.as_ref().borrow_mut();
-            let vertex = dm_borrow.get_vertex_mut(self.vertex_handle).unwrap();
+            let previous_position = dm_borrow.get_vertex(self.vertex_handle).unwrap().position;
 
             match solver_response.state {
                 SolverState::Free => {
                     //println!("Free");
-                    vertex.position = try_pt
+                    move_vertex(
+                        &mut dm_borrow,
+                        drag_start_positions,
+                        self.vertex_handle,
+                        try_pt,
+                    );
                 }
                 SolverState::Locked => {
                     //println!("Locked");
-                    ()
                 }
-                SolverState::Partial => vertex.position = solver_response.new_pos.unwrap(),
+                SolverState::Partial => move_vertex(
+                    &mut dm_borrow,
+                    drag_start_positions,
+                    self.vertex_handle,
+                    solver_response.new_pos.unwrap(),
+                ),
             }
+
+            drop(dm_borrow);
+            apply_followers(
+                &self.drawing_manager,
+                &constr_borrow,
+                drag_start_positions,
+                &BTreeMap::from([(self.vertex_handle, previous_position)]),
+            );
         }
+
+        point_response
     }
 
-    pub fn get_shape(&self, response: &Response) -> Shape {
+    pub fn get_shape(&self, to_screen: &emath::RectTransform, dof_analysis: &DofAnalysis) -> Shape {
         let base_color = if self.is_selected || self.is_being_dragged {
             Color32::WHITE.gamma_multiply(0.9)
         } else {
-            Color32::GRAY
+            match dof_analysis.vertex_status(self.vertex_handle) {
+                DofStatus::Under => Color32::GRAY,
+                DofStatus::Fully => Color32::from_rgb(90, 170, 90),
+                DofStatus::Over => Color32::from_rgb(200, 70, 70),
+            }
         };
 
-        let hover_color = base_color.clone().gamma_multiply(1.2);
+        let hover_color = base_color.gamma_multiply(1.2);
 
         let current_color = if self.is_hovered {
             base_color
@@ -305,18 +1414,46 @@ impl VertexDisplay {
             hover_color
         };
 
-        let to_screen = emath::RectTransform::from_to(
-            Rect::from_min_size(Pos2::ZERO, response.rect.size()),
-            response.rect,
-        );
-
         let circ_position = self.get_vertex_point();
 
         let point_in_screen = to_screen.transform_pos(circ_position);
 
         let control_point_radius = 10.0;
 
-        Shape::circle_filled(point_in_screen, control_point_radius, current_color)
+        let point_shape =
+            Shape::circle_filled(point_in_screen, control_point_radius, current_color);
+
+        let constraint_manager_rc = self.constraint_manager.upgrade().unwrap();
+        let is_fixed = constraint_manager_rc
+            .borrow()
+            .is_vertex_fixed(self.vertex_handle);
+
+        if !is_fixed {
+            return point_shape;
+        }
+
+        // anchor symbol -- a ground triangle hanging under the vertex
+        let stroke = Stroke::new(2.5, Color32::LIGHT_GREEN);
+        let base_y = point_in_screen.y + control_point_radius + 10.0;
+
+        Shape::Vec(vec![
+            point_shape,
+            Shape::closed_line(
+                vec![
+                    point_in_screen + Vec2::new(0.0, control_point_radius),
+                    Pos2::new(point_in_screen.x - 9.0, base_y),
+                    Pos2::new(point_in_screen.x + 9.0, base_y),
+                ],
+                stroke,
+            ),
+            Shape::line_segment(
+                [
+                    Pos2::new(point_in_screen.x - 13.0, base_y + 4.0),
+                    Pos2::new(point_in_screen.x + 13.0, base_y + 4.0),
+                ],
+                stroke,
+            ),
+        ])
     }
     fn get_vertex_point(&self) -> Pos2 {
         let drawing_manager_rc = self.drawing_manager.upgrade().unwrap();
@@ -372,23 +1509,24 @@ impl EdgeDisplay {
     pub fn interact(
         &mut self,
         constraint_paths: &mut Vec<ConstraintPath>,
+        drag_start_positions: &mut BTreeMap<VertexHandle, Pos2>,
         ui: &Ui,
         response: &Response,
-    ) {
-        let to_screen = emath::RectTransform::from_to(
-            Rect::from_min_size(Pos2::ZERO, response.rect.size()),
-            response.rect,
-        );
+        camera: &Camera,
+        // false while the display manager drags this along with the rest of a group
+        can_drag: bool,
+    ) -> Response {
+        let to_screen = camera.screen_transform(response.rect);
 
         let end_points: Vec<Pos2> = self
             .get_end_points()
             .iter()
-            .map(|p| to_screen.transform_pos(p.clone()))
+            .map(|p| to_screen.transform_pos(*p))
             .collect();
 
         //let point_in_screen = to_screen.transform_pos(self.get_vertex_point());
         let point_rect = Rect::from_two_pos(end_points[0], end_points[1]).expand(15.);
-        let point_id = response.id.with(self.edge_handle + 10000);
+        let point_id = response.id.with(("edge", self.edge_handle));
         let point_response = ui.interact(point_rect, point_id, Sense::click_and_drag());
 
         self.is_hovered = false;
@@ -400,16 +1538,15 @@ impl EdgeDisplay {
             let cursor_pt = to_screen.inverse().transform_pos(cursor_pt);
             //self.is_hovered = true;
             //let is_on_edge = true;
-            let is_on_edge = self.is_point_on_edge(cursor_pt, 10.0);
+            let is_on_edge = self.is_point_on_edge(cursor_pt, camera.sketch_length(10.0));
             if is_on_edge {
                 self.is_hovered = true;
 
-                if point_response.clicked() {
-                    self.is_selected = !self.is_selected;
-                }
-
                 // drag begins -- initiate drag parameters
-                if !self.is_being_dragged && point_response.dragged() {
+                if can_drag
+                    && !self.is_being_dragged
+                    && point_response.dragged_by(PointerButton::Primary)
+                {
                     // set previous position before starting to drag
                     //let dm_shared = self.drawing_manager.upgrade().unwrap();
                     //let mut dm_borrow = dm_shared.as_ref().borrow_mut();
@@ -430,7 +1567,7 @@ impl EdgeDisplay {
         // drag ends
         // this is outside of the hovered() call so that it will
         // properly be called without being in the interact region
-        if self.is_being_dragged && !point_response.dragged() {
+        if self.is_being_dragged && !point_response.dragged_by(PointerButton::Primary) {
             self.is_being_dragged = false;
             println!("drag end");
         }
@@ -446,6 +1583,26 @@ impl EdgeDisplay {
             let constr_shared = self.constraint_manager.upgrade().unwrap();
             let constr_borrow = constr_shared.as_ref().borrow_mut();
 
+            if constr_borrow.get_solver_mode() == SolverMode::Global {
+                let (vh_1, vh_2) = {
+                    let dm_shared = self.drawing_manager.upgrade().unwrap();
+                    let dm_borrow = dm_shared.as_ref().borrow();
+                    let edge = dm_borrow.get_edge(self.edge_handle).unwrap();
+                    (edge.start_point_vh, edge.end_point_vh)
+                };
+
+                let global_response = constr_borrow.solve_global(&[
+                    (vh_1, self.pre_drag_start_point + delta),
+                    (vh_2, self.pre_drag_end_point + delta),
+                ]);
+                apply_global_response(
+                    &self.drawing_manager,
+                    drag_start_positions,
+                    &global_response,
+                );
+                return point_response;
+            }
+
             let solver_response = constr_borrow.solve_for_edge(
                 self.edge_handle,
                 &self.pre_drag_position,
@@ -463,59 +1620,71 @@ impl EdgeDisplay {
             // get mutable vertex again, so we can modify it
             let dm_shared = self.drawing_manager.upgrade().unwrap();
 
-            #[allow(unused_assignments)]
-            let mut eh_1 = 0;
-            #[allow(unused_assignments)]
-            let mut eh_2 = 0;
+            let eh_1;
+            let eh_2;
+            let mut previous_positions = BTreeMap::new();
             {
                 let dm_borrow = dm_shared.as_ref().borrow_mut();
                 let edge = dm_borrow.get_edge(self.edge_handle).unwrap();
                 eh_1 = edge.start_point_vh;
                 eh_2 = edge.end_point_vh;
+
+                for vh in [eh_1, eh_2] {
+                    previous_positions.insert(vh, dm_borrow.get_vertex(vh).unwrap().position);
+                }
             }
 
             match solver_response.state {
                 SolverState::Free => {
                     //println!("Free");
-                    {
-                        let mut dm_borrow = dm_shared.as_ref().borrow_mut();
-                        let vert_1 = dm_borrow.get_vertex_mut(eh_1).unwrap();
-                        vert_1.position = self.pre_drag_start_point + delta;
-                    }
-                    {
-                        let mut dm_borrow = dm_shared.as_ref().borrow_mut();
-                        let vert_2 = dm_borrow.get_vertex_mut(eh_2).unwrap();
-                        vert_2.position = self.pre_drag_end_point + delta;
-                    }
+                    let mut dm_borrow = dm_shared.as_ref().borrow_mut();
+                    let start_pos = self.pre_drag_start_point + delta;
+                    move_vertex(&mut dm_borrow, drag_start_positions, eh_1, start_pos);
+                    let end_pos = self.pre_drag_end_point + delta;
+                    move_vertex(&mut dm_borrow, drag_start_positions, eh_2, end_pos);
                 }
                 SolverState::Locked => {
                     //println!("Locked");
-                    ()
                 }
                 //SolverState::Partial => vertex.position = solver_response.new_pos.unwrap(),
                 SolverState::Partial => {
-                    {
-                        let mut dm_borrow = dm_shared.as_ref().borrow_mut();
-                        let vert_1 = dm_borrow.get_vertex_mut(eh_1).unwrap();
-                        vert_1.position = solver_response.new_pos.unwrap()[0];
-                    }
-                    {
-                        let mut dm_borrow = dm_shared.as_ref().borrow_mut();
-                        let vert_2 = dm_borrow.get_vertex_mut(eh_2).unwrap();
-                        vert_2.position = solver_response.new_pos.unwrap()[1];
-                    }
+                    let mut dm_borrow = dm_shared.as_ref().borrow_mut();
+                    let [start_pos, end_pos] = solver_response.new_pos.unwrap();
+                    move_vertex(&mut dm_borrow, drag_start_positions, eh_1, start_pos);
+                    move_vertex(&mut dm_borrow, drag_start_positions, eh_2, end_pos);
                 }
             }
+
+            apply_followers(
+                &self.drawing_manager,
+                &constr_borrow,
+                drag_start_positions,
+                &previous_positions,
+            );
         }
+
+        point_response
     }
 
-    pub fn get_shape(&self, response: &Response) -> Shape {
+    pub fn get_shape(&self, to_screen: &emath::RectTransform, dof_analysis: &DofAnalysis) -> Shape {
+        let status_color = {
+            let drawing_manager_rc = self.drawing_manager.upgrade().unwrap();
+            let drawing_manager = drawing_manager_rc.borrow();
+            let edge = drawing_manager.get_edge(self.edge_handle).unwrap();
+
+            match dof_analysis.edge_status(edge.start_point_vh, edge.end_point_vh) {
+                DofStatus::Under => Color32::LIGHT_BLUE,
+                DofStatus::Fully => Color32::LIGHT_GREEN,
+                DofStatus::Over => Color32::LIGHT_RED,
+            }
+        };
+
         let base_color = if self.is_selected {
-            Color32::LIGHT_BLUE.gamma_multiply(0.9)
+            status_color.gamma_multiply(0.9)
         } else {
-            Color32::LIGHT_BLUE.gamma_multiply(0.5)
+            status_color.gamma_multiply(0.5)
         };
-        let hover_color = base_color.clone().gamma_multiply(1.2);
+        let hover_color = base_color.gamma_multiply(1.2);
 
         let current_color = if self.is_hovered {
             base_color
@@ -523,11 +1692,6 @@ impl EdgeDisplay {
             hover_color
         };
 
-        let to_screen = emath::RectTransform::from_to(
-            Rect::from_min_size(Pos2::ZERO, response.rect.size()),
-            response.rect,
-        );
-
         let stroke = Stroke::new(5., current_color);
         let [p_1, p_2] = self.get_end_points();
 
@@ -591,18 +1755,14 @@ impl ConstraintDisplay {
         }
     }
 
-    pub fn get_shape(&self, response: &Response) -> Vec<Shape> {
+    // the fonts come from ctx, to_screen places sketch positions on whatever is being drawn to
+    pub fn get_shape(&self, ctx: &Context, to_screen: &emath::RectTransform) -> Vec<Shape> {
         let constraint_manager_rc = self.constraint_manager.upgrade().unwrap();
         let constraint_manager = constraint_manager_rc.borrow_mut();
 
         let drawing_manager_rc = self.drawing_manager.upgrade().unwrap();
         let drawing_manager = drawing_manager_rc.borrow_mut();
 
-        let to_screen = emath::RectTransform::from_to(
-            Rect::from_min_size(Pos2::ZERO, response.rect.size()),
-            response.rect,
-        );
-
         let constraint = constraint_manager
             .get_constraint(self.constraint_handle)
             .unwrap();
@@ -615,11 +1775,21 @@ impl ConstraintDisplay {
                     .position;
                 let pos = to_screen.transform_pos(pos);
 
-                return vec![Shape::circle_stroke(
-                    pos,
-                    15.0,
-                    Stroke::new(3.0, Color32::LIGHT_GREEN),
-                )];
+                let label = ctx.fonts(|fonts| {
+                    Shape::text(
+                        fonts,
+                        pos + Vec2::new(18.0, -18.0),
+                        Align2::LEFT_BOTTOM,
+                        format!("{:.1}°", a.angle.to_degrees()),
+                        FontId::proportional(14.0),
+                        Color32::LIGHT_GREEN,
+                    )
+                });
+
+                vec![
+                    Shape::circle_stroke(pos, 15.0, Stroke::new(3.0, Color32::LIGHT_GREEN)),
+                    label,
+                ]
             }
             Constraint::LENGTH(l) => {
                 let edge = drawing_manager.get_edge(l.edge_handle).unwrap();
@@ -637,21 +1807,50 @@ impl ConstraintDisplay {
                     .position;
                 let end_pt = to_screen.transform_pos(end_pt);
 
-                let main_dir = end_pt - start_pt;
-
-                let perp_dir = main_dir.normalized();
-                let perp_dir = rotate_vec2(perp_dir, f32::consts::FRAC_PI_2);
+                dimension_shapes(ctx, start_pt, end_pt, l.length)
+            }
+            Constraint::DISTANCE(d) => {
+                let get_pos_func = |vh: VertexHandle| -> Pos2 {
+                    let pos = drawing_manager.get_vertex(vh).unwrap().position;
+                    to_screen.transform_pos(pos)
+                };
 
-                let stroke = Stroke::new(3.0, Color32::LIGHT_GREEN);
+                dimension_shapes(
+                    ctx,
+                    get_pos_func(d.vertex_1_handle),
+                    get_pos_func(d.vertex_2_handle),
+                    d.distance,
+                )
+            }
+            Constraint::EDGE_DISTANCE(d) => {
+                let edge = drawing_manager.get_edge(d.edge_handle).unwrap();
 
-                let peg_1 = Shape::line_segment([start_pt, start_pt + perp_dir * 32.0], stroke);
-                let peg_2 = Shape::line_segment([end_pt, end_pt + perp_dir * 32.0], stroke);
-                let line = Shape::line_segment(
-                    [start_pt + perp_dir * 16.0, end_pt + perp_dir * 16.0],
-                    stroke,
-                );
+                let start_pt = drawing_manager
+                    .get_vertex(edge.start_point_vh)
+                    .unwrap()
+                    .position;
+                let end_pt = drawing_manager
+                    .get_vertex(edge.end_point_vh)
+                    .unwrap()
+                    .position;
+                let pos = drawing_manager
+                    .get_vertex(d.vertex_handle)
+                    .unwrap()
+                    .position;
 
-                vec![peg_1, peg_2, line]
+                // dimension from the foot of the perpendicular on the edge's line
+                let foot = Line {
+                    origin: start_pt,
+                    direction: (end_pt - start_pt).normalized(),
+                }
+                .closest_point(&pos);
+
+                dimension_shapes(
+                    ctx,
+                    to_screen.transform_pos(foot),
+                    to_screen.transform_pos(pos),
+                    d.distance.abs(),
+                )
             }
             Constraint::PARALLEL(p) => {
                 let edge_1 = drawing_manager.get_edge(p.edge_1_handle).unwrap();
@@ -682,16 +1881,567 @@ impl ConstraintDisplay {
                     ),
                 ]
             }
+            Constraint::PERPENDICULAR(p) => {
+                let edge_1 = drawing_manager.get_edge(p.edge_1_handle).unwrap();
+                let edge_2 = drawing_manager.get_edge(p.edge_2_handle).unwrap();
+
+                let get_pos_func = |vh: VertexHandle| -> Pos2 {
+                    let pos = drawing_manager.get_vertex(vh).unwrap().position;
+                    to_screen.transform_pos(pos)
+                };
+
+                let stroke = Stroke::new(3.0, Color32::LIGHT_GREEN);
+
+                // right angle corner sitting on the middle of each edge
+                let right_angle_glyph = |start_pt: Pos2, end_pt: Pos2| -> Shape {
+                    let mid_pt = start_pt.lerp(end_pt, 0.5);
+                    let main_dir = (end_pt - start_pt).normalized();
+                    let perp_dir = rotate_vec2(main_dir, f32::consts::FRAC_PI_2);
+
+                    Shape::line(
+                        vec![
+                            mid_pt + main_dir * 10.0,
+                            mid_pt + main_dir * 10.0 + perp_dir * 10.0,
+                            mid_pt + perp_dir * 10.0,
+                        ],
+                        stroke,
+                    )
+                };
+
+                vec![
+                    right_angle_glyph(
+                        get_pos_func(edge_1.start_point_vh),
+                        get_pos_func(edge_1.end_point_vh),
+                    ),
+                    right_angle_glyph(
+                        get_pos_func(edge_2.start_point_vh),
+                        get_pos_func(edge_2.end_point_vh),
+                    ),
+                ]
+            }
+            // drawn as an anchor by the vertex itself
+            Constraint::FIXED(_) => vec![],
+            Constraint::HORIZONTAL(a) | Constraint::VERTICAL(a) => {
+                let edge = drawing_manager.get_edge(a.edge_handle).unwrap();
+
+                let start_pt = drawing_manager
+                    .get_vertex(edge.start_point_vh)
+                    .unwrap()
+                    .position;
+                let end_pt = drawing_manager
+                    .get_vertex(edge.end_point_vh)
+                    .unwrap()
+                    .position;
+                let mid_pt = to_screen.transform_pos(start_pt.lerp(end_pt, 0.5));
+
+                let marker = if let Constraint::HORIZONTAL(_) = constraint {
+                    "H"
+                } else {
+                    "V"
+                };
+
+                // offset the marker off the edge so it doesn't sit on the line
+                let marker_pos = mid_pt + Vec2::new(12.0, -12.0);
+
+                let label = ctx.fonts(|fonts| {
+                    Shape::text(
+                        fonts,
+                        marker_pos,
+                        Align2::CENTER_CENTER,
+                        marker,
+                        FontId::monospace(14.0),
+                        Color32::LIGHT_GREEN,
+                    )
+                });
+
+                vec![
+                    Shape::rect_stroke(
+                        Rect::from_center_size(marker_pos, Vec2::splat(18.0)),
+                        3.0,
+                        Stroke::new(2.0, Color32::LIGHT_GREEN),
+                    ),
+                    label,
+                ]
+            }
+            Constraint::EQUAL_LENGTH(e) => {
+                let edge_1 = drawing_manager.get_edge(e.edge_1_handle).unwrap();
+                let edge_2 = drawing_manager.get_edge(e.edge_2_handle).unwrap();
+
+                let get_pos_func = |vh: VertexHandle| -> Pos2 {
+                    let pos = drawing_manager.get_vertex(vh).unwrap().position;
+                    to_screen.transform_pos(pos)
+                };
+
+                let stroke = Stroke::new(3.0, Color32::LIGHT_GREEN);
+
+                // a pair of ticks across the middle of each edge
+                let tick_marks = |start_pt: Pos2, end_pt: Pos2| -> Vec<Shape> {
+                    let mid_pt = start_pt.lerp(end_pt, 0.5);
+                    let main_dir = (end_pt - start_pt).normalized();
+                    let perp_dir = rotate_vec2(main_dir, f32::consts::FRAC_PI_2);
+
+                    [-4.0, 4.0]
+                        .iter()
+                        .map(|offset| {
+                            let tick_pt = mid_pt + main_dir * *offset;
+                            Shape::line_segment(
+                                [tick_pt - perp_dir * 8.0, tick_pt + perp_dir * 8.0],
+                                stroke,
+                            )
+                        })
+                        .collect()
+                };
+
+                let mut shapes = tick_marks(
+                    get_pos_func(edge_1.start_point_vh),
+                    get_pos_func(edge_1.end_point_vh),
+                );
+                shapes.extend(tick_marks(
+                    get_pos_func(edge_2.start_point_vh),
+                    get_pos_func(edge_2.end_point_vh),
+                ));
+                shapes
+            }
+            Constraint::SYMMETRIC(sym) => {
+                let get_pos_func = |vh: VertexHandle| -> Pos2 {
+                    let pos = drawing_manager.get_vertex(vh).unwrap().position;
+                    to_screen.transform_pos(pos)
+                };
+
+                let pos_1 = get_pos_func(sym.vertex_1_handle);
+                let pos_2 = get_pos_func(sym.vertex_2_handle);
+
+                let stroke = Stroke::new(2.0, Color32::LIGHT_GREEN);
+
+                // dashed connector with a bracket at each vertex opening towards the axis
+                let mut shapes = Shape::dashed_line(&[pos_1, pos_2], stroke, 6.0, 6.0);
+
+                let bracket = |pos: Pos2, towards: Pos2| -> Shape {
+                    let main_dir = (towards - pos).normalized();
+                    let perp_dir = rotate_vec2(main_dir, f32::consts::FRAC_PI_2);
+
+                    Shape::line(
+                        vec![
+                            pos + main_dir * 14.0 + perp_dir * 6.0,
+                            pos + main_dir * 10.0 + perp_dir * 6.0,
+                            pos + main_dir * 10.0 - perp_dir * 6.0,
+                            pos + main_dir * 14.0 - perp_dir * 6.0,
+                        ],
+                        stroke,
+                    )
+                };
+
+                if pos_1.distance(pos_2) > 0.001 {
+                    shapes.push(bracket(pos_1, pos_2));
+                    shapes.push(bracket(pos_2, pos_1));
+                }
+
+                shapes
+            }
+            Constraint::COINCIDENT(c) => {
+                let pos = drawing_manager
+                    .get_vertex(c.vertex_1_handle)
+                    .unwrap()
+                    .position;
+                let pos = to_screen.transform_pos(pos);
+
+                vec![Shape::circle_stroke(
+                    pos,
+                    10.0,
+                    Stroke::new(3.0, Color32::LIGHT_GREEN),
+                )]
+            }
+            Constraint::POINT_ON_EDGE(p) => {
+                let edge = drawing_manager.get_edge(p.edge_handle).unwrap();
+
+                let start_pt = drawing_manager
+                    .get_vertex(edge.start_point_vh)
+                    .unwrap()
+                    .position;
+                let end_pt = drawing_manager
+                    .get_vertex(edge.end_point_vh)
+                    .unwrap()
+                    .position;
+                let pos = drawing_manager
+                    .get_vertex(p.vertex_handle)
+                    .unwrap()
+                    .position;
+                let pos = to_screen.transform_pos(pos);
+
+                // tick across the edge where the vertex sits on it
+                let perp_dir =
+                    rotate_vec2((end_pt - start_pt).normalized(), f32::consts::FRAC_PI_2);
+
+                vec![Shape::line_segment(
+                    [pos - perp_dir * 12.0, pos + perp_dir * 12.0],
+                    Stroke::new(3.0, Color32::LIGHT_GREEN),
+                )]
+            }
+        }
+    }
+}
+
+// the part of the line origin + direction * t, t >= t_min, that crosses rect
+fn clip_line(origin: Pos2, direction: Vec2, t_min: f32, rect: Rect) -> Option<[Pos2; 2]> {
+    let mut t_range = (t_min, f32::INFINITY);
+
+    for (start, dir, min, max) in [
+        (origin.x, direction.x, rect.min.x, rect.max.x),
+        (origin.y, direction.y, rect.min.y, rect.max.y),
+    ] {
+        if dir.abs() < f32::EPSILON {
+            // parallel to this pair of sides, either always between them or never
+            if start < min || start > max {
+                return None;
+            }
+            continue;
         }
+
+        let t_1 = (min - start) / dir;
+        let t_2 = (max - start) / dir;
+        t_range.0 = t_range.0.max(t_1.min(t_2));
+        t_range.1 = t_range.1.min(t_1.max(t_2));
+    }
+
+    if t_range.0 > t_range.1 {
+        return None;
+    }
+    Some([
+        origin + direction * t_range.0,
+        origin + direction * t_range.1,
+    ])
+}
+
+// moves the sketch to the solved positions, or leaves it where it was
+// when the drag position can't satisfy every constraint
+fn apply_global_response(
+    drawing_manager: &Weak<RefCell<DrawingManager>>,
+    drag_start_positions: &mut BTreeMap<VertexHandle, Pos2>,
+    global_response: &GlobalSolverResponse,
+) {
+    if !global_response.converged {
+        return;
+    }
+
+    let dm_shared = drawing_manager.upgrade().unwrap();
+    let mut dm_borrow = dm_shared.as_ref().borrow_mut();
+
+    for (vh, pos) in &global_response.new_positions {
+        move_vertex(&mut dm_borrow, drag_start_positions, *vh, *pos);
+    }
+}
+
+// moves the coincident and mirrored partners of whatever the analytic solver just moved
+fn apply_followers(
+    drawing_manager: &Weak<RefCell<DrawingManager>>,
+    constraint_manager: &ConstraintManager,
+    drag_start_positions: &mut BTreeMap<VertexHandle, Pos2>,
+    previous_positions: &BTreeMap<VertexHandle, Pos2>,
+) {
+    let mut followers = constraint_manager.solve_coincident_followers(previous_positions);
+    followers.extend(constraint_manager.solve_symmetric_followers(previous_positions));
+
+    let dm_shared = drawing_manager.upgrade().unwrap();
+    let mut dm_borrow = dm_shared.as_ref().borrow_mut();
+
+    for (vh, pos) in followers {
+        move_vertex(&mut dm_borrow, drag_start_positions, vh, pos);
     }
 }
 
-fn rotate_vec2(vec: Vec2, angle: f32) -> Vec2 {
-    let cos_angle = angle.cos();
-    let sin_angle = angle.sin();
+// moves a vertex for a drag, keeping where it was before the drag first moved it
+fn move_vertex(
+    dm: &mut DrawingManager,
+    drag_start_positions: &mut BTreeMap<VertexHandle, Pos2>,
+    vh: VertexHandle,
+    pos: Pos2,
+) {
+    let vertex = dm.get_vertex_mut(vh).unwrap();
+    drag_start_positions.entry(vh).or_insert(vertex.position);
+    vertex.position = pos;
+}
+
+// peg-and-line dimension between two screen points, labelled with the value
+fn dimension_shapes(ctx: &Context, start_pt: Pos2, end_pt: Pos2, value: f32) -> Vec<Shape> {
+    let main_dir = end_pt - start_pt;
+
+    let perp_dir = main_dir.normalized();
+    let perp_dir = rotate_vec2(perp_dir, f32::consts::FRAC_PI_2);
+
+    let stroke = Stroke::new(3.0, Color32::LIGHT_GREEN);
+
+    let peg_1 = Shape::line_segment([start_pt, start_pt + perp_dir * 32.0], stroke);
+    let peg_2 = Shape::line_segment([end_pt, end_pt + perp_dir * 32.0], stroke);
+    let line = Shape::line_segment(
+        [start_pt + perp_dir * 16.0, end_pt + perp_dir * 16.0],
+        stroke,
+    );
+
+    let label = ctx.fonts(|fonts| {
+        Shape::text(
+            fonts,
+            start_pt.lerp(end_pt, 0.5) + perp_dir * 40.0,
+            Align2::CENTER_CENTER,
+            format!("{:.1}", value),
+            FontId::proportional(14.0),
+            Color32::LIGHT_GREEN,
+        )
+    });
+
+    vec![peg_1, peg_2, line, label]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn managers() -> (
+        Rc<RefCell<DrawingManager>>,
+        Rc<RefCell<ConstraintManager>>,
+        DisplayManager,
+    ) {
+        let dm = Rc::new(RefCell::new(DrawingManager::new()));
+        let cm = Rc::new(RefCell::new(ConstraintManager::new()));
+        cm.borrow_mut().set_drawing_manager(Rc::clone(&dm));
+
+        let mut display_manager = DisplayManager::new();
+        display_manager.set_drawing_manager(Rc::clone(&dm));
+        display_manager.set_constraint_manager(Rc::clone(&cm));
+        (dm, cm, display_manager)
+    }
+
+    fn distance(dm: &RefCell<DrawingManager>, vh_1: VertexHandle, vh_2: VertexHandle) -> f32 {
+        let dm = dm.borrow();
+        let pos = |vh| dm.get_vertex(vh).unwrap().position;
+        pos(vh_1).distance(pos(vh_2))
+    }
+
+    #[test]
+    fn value_change_is_one_undo_step() {
+        let (dm, cm, mut display_manager) = managers();
+        let a = dm.borrow_mut().add_vertex(Pos2::new(0.0, 0.0));
+        let b = dm.borrow_mut().add_vertex(Pos2::new(10.0, 0.0));
+        let ab = dm.borrow_mut().add_edge(a, b).unwrap();
+        let ch = cm.borrow_mut().add_length_constraint(ab).unwrap();
+
+        display_manager.set_constraint_value(ch, 15.0).unwrap();
+        assert!((distance(&dm, a, b) - 15.0).abs() < 0.01);
+
+        display_manager.undo();
+        assert_eq!(cm.borrow().get_constraint_value(ch).unwrap(), 10.0);
+        assert_eq!(distance(&dm, a, b), 10.0);
+
+        display_manager.redo();
+        assert_eq!(cm.borrow().get_constraint_value(ch).unwrap(), 15.0);
+        assert!((distance(&dm, a, b) - 15.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn drag_keeps_where_a_vertex_first_was() {
+        let mut dm = DrawingManager::new();
+        let a = dm.add_vertex(Pos2::new(0.0, 0.0));
+        dm.add_vertex(Pos2::new(10.0, 0.0));
+
+        let mut drag_start_positions = BTreeMap::new();
+        move_vertex(&mut dm, &mut drag_start_positions, a, Pos2::new(1.0, 1.0));
+        move_vertex(&mut dm, &mut drag_start_positions, a, Pos2::new(2.0, 2.0));
+
+        // only the vertex that moved, from before its first move
+        assert_eq!(
+            drag_start_positions,
+            BTreeMap::from([(a, Pos2::new(0.0, 0.0))])
+        );
+    }
+
+    #[test]
+    fn removing_a_vertex_cascades_and_undoes() {
+        let (dm, cm, mut display_manager) = managers();
+        let a = dm.borrow_mut().add_vertex(Pos2::new(0.0, 0.0));
+        let b = dm.borrow_mut().add_vertex(Pos2::new(10.0, 0.0));
+        let c = dm.borrow_mut().add_vertex(Pos2::new(10.0, 10.0));
+        let ab = dm.borrow_mut().add_edge(a, b).unwrap();
+        let bc = dm.borrow_mut().add_edge(b, c).unwrap();
+        let ch = cm.borrow_mut().add_length_constraint(ab).unwrap();
+        for vh in [a, b, c] {
+            display_manager.add_vertex(vh);
+        }
+        for eh in [ab, bc] {
+            display_manager.add_edge(eh);
+        }
+        display_manager.add_constraint(ch);
+
+        // b takes both of its edges with it, and the length on one of them
+        let report = display_manager.remove_vertex(b).unwrap();
+        assert_eq!(report.vertices.len(), 1);
+        assert_eq!(report.edges.len(), 2);
+        assert_eq!(report.constraints.len(), 1);
+
+        assert!(dm.borrow().get_vertex(a).unwrap().edge_handles.is_empty());
+        assert!(dm.borrow().get_vertex(c).unwrap().edge_handles.is_empty());
+        assert!(cm.borrow().get_constraint(ch).is_err());
+        assert!(!display_manager.vertices.contains_key(&b));
+        assert!(display_manager.edges.is_empty());
+        assert!(display_manager.constraints.is_empty());
+
+        // all of it comes back under the same handles
+        display_manager.undo();
+        assert_eq!(dm.borrow().get_vertex(a).unwrap().edge_handles, vec![ab]);
+        assert_eq!(dm.borrow().get_vertex(b).unwrap().edge_handles.len(), 2);
+        assert!(cm.borrow().get_constraint(ch).is_ok());
+        assert_eq!(display_manager.vertices.len(), 3);
+        assert_eq!(display_manager.edges.len(), 2);
+        assert!(display_manager.constraints.contains_key(&ch));
+    }
+
+    #[test]
+    fn removing_the_selection_is_one_undo_step() {
+        let (dm, _, mut display_manager) = managers();
+        let a = dm.borrow_mut().add_vertex(Pos2::new(0.0, 0.0));
+        let b = dm.borrow_mut().add_vertex(Pos2::new(10.0, 0.0));
+        let c = dm.borrow_mut().add_vertex(Pos2::new(20.0, 0.0));
+        let ab = dm.borrow_mut().add_edge(a, b).unwrap();
+        for vh in [a, b, c] {
+            display_manager.add_vertex(vh);
+        }
+        display_manager.add_edge(ab);
+
+        display_manager.edges.get_mut(&ab).unwrap().is_selected = true;
+        display_manager.vertices.get_mut(&a).unwrap().is_selected = true;
+        display_manager.vertices.get_mut(&c).unwrap().is_selected = true;
+        display_manager.remove_selection().unwrap();
+
+        assert_eq!(dm.borrow().get_all_vertex_handles(), vec![b]);
+        assert!(dm.borrow().get_all_edge_handles().is_empty());
+
+        display_manager.undo();
+        assert_eq!(dm.borrow().get_all_vertex_handles(), vec![a, b, c]);
+        assert_eq!(dm.borrow().get_all_edge_handles(), vec![ab]);
+        assert!(!display_manager.can_undo());
+    }
+
+    #[test]
+    fn line_tool_closes_a_polyline_on_its_first_vertex() {
+        let (dm, _, mut display_manager) = managers();
+        display_manager.set_tool(Tool::Line);
+
+        for pos in [(0.0, 0.0), (40.0, 0.0), (40.0, 40.0), (0.5, -0.5)] {
+            display_manager.place_vertex(Pos2::new(pos.0, pos.1));
+        }
+
+        // the last click snapped onto the first vertex instead of making a fourth
+        assert_eq!(dm.borrow().get_all_vertex_handles().len(), 3);
+        assert_eq!(dm.borrow().get_all_edge_handles().len(), 3);
+
+        // each click is its own undo step
+        display_manager.undo();
+        assert_eq!(dm.borrow().get_all_vertex_handles().len(), 3);
+        assert_eq!(dm.borrow().get_all_edge_handles().len(), 2);
+        display_manager.undo();
+        assert_eq!(dm.borrow().get_all_vertex_handles().len(), 2);
+        assert_eq!(dm.borrow().get_all_edge_handles().len(), 1);
+    }
+
+    #[test]
+    fn point_tool_places_loose_vertices() {
+        let (dm, _, mut display_manager) = managers();
+        display_manager.set_tool(Tool::Point);
+
+        display_manager.place_vertex(Pos2::new(0.0, 0.0));
+        display_manager.place_vertex(Pos2::new(20.0, 0.0));
+        display_manager.place_vertex(Pos2::new(20.0, 3.0));
+
+        assert_eq!(dm.borrow().get_all_vertex_handles().len(), 2);
+        assert!(dm.borrow().get_all_edge_handles().is_empty());
+    }
+
+    #[test]
+    fn constraints_come_from_the_selection() {
+        let (dm, cm, mut display_manager) = managers();
+        let a = dm.borrow_mut().add_vertex(Pos2::new(0.0, 0.0));
+        let b = dm.borrow_mut().add_vertex(Pos2::new(10.0, 3.0));
+        let c = dm.borrow_mut().add_vertex(Pos2::new(20.0, 20.0));
+        let d = dm.borrow_mut().add_vertex(Pos2::new(30.0, 25.0));
+        let ab = dm.borrow_mut().add_edge(a, b).unwrap();
+        let cd = dm.borrow_mut().add_edge(c, d).unwrap();
+        for vh in [a, b, c, d] {
+            display_manager.add_vertex(vh);
+        }
+        for eh in [ab, cd] {
+            display_manager.add_edge(eh);
+        }
+
+        // a selection that doesn't fit says what it needs
+        display_manager.edges.get_mut(&ab).unwrap().is_selected = true;
+        assert!(matches!(
+            display_manager.add_constraint_from_selection(ConstraintKind::Parallel),
+            Err(ConstraintError::InvalidSelection("two edges"))
+        ));
+        display_manager.edges.get_mut(&cd).unwrap().is_selected = true;
+        assert!(matches!(
+            display_manager.add_constraint_from_selection(ConstraintKind::Angle),
+            Err(ConstraintError::NoSharedVertex)
+        ));
+
+        display_manager.clear_selection();
+        display_manager.edges.get_mut(&ab).unwrap().is_selected = true;
+        let ch = display_manager
+            .add_constraint_from_selection(ConstraintKind::Horizontal)
+            .unwrap();
+        assert!(display_manager.constraints.contains_key(&ch));
+        assert_eq!(display_manager.get_selection(), (vec![], vec![]));
+        let position = |vh| dm.borrow().get_vertex(vh).unwrap().position;
+        assert!((position(a).y - position(b).y).abs() < 0.01);
+
+        // undoing takes the constraint away and puts the edge back as it was
+        display_manager.undo();
+        assert!(cm.borrow().get_constraint(ch).is_err());
+        assert_eq!(position(b), Pos2::new(10.0, 3.0));
+    }
+
+    #[test]
+    fn clip_line_to_the_canvas() {
+        let rect = Rect::from_min_max(Pos2::new(0.0, 0.0), Pos2::new(100.0, 50.0));
+
+        // a diagonal through the middle, both ways
+        let [start, end] = clip_line(
+            Pos2::new(50.0, 25.0),
+            Vec2::new(1.0, 1.0),
+            f32::NEG_INFINITY,
+            rect,
+        )
+        .unwrap();
+        assert!(start.distance(Pos2::new(25.0, 0.0)) < 0.001);
+        assert!(end.distance(Pos2::new(75.0, 50.0)) < 0.001);
+
+        // a ray only goes forward from its start
+        let [start, end] = clip_line(Pos2::new(50.0, 25.0), Vec2::X, 0.0, rect).unwrap();
+        assert_eq!(start, Pos2::new(50.0, 25.0));
+        assert_eq!(end, Pos2::new(100.0, 25.0));
+
+        // lines that pass beside the canvas, or rays pointing away from it, miss
+        assert!(clip_line(Pos2::new(0.0, 80.0), Vec2::X, f32::NEG_INFINITY, rect).is_none());
+        assert!(clip_line(Pos2::new(150.0, 25.0), Vec2::X, 0.0, rect).is_none());
+    }
 
-    Vec2 {
-        x: vec.x * cos_angle - vec.y * sin_angle,
-        y: vec.x * sin_angle + vec.y * cos_angle,
+    #[test]
+    fn selection_drag_rectangle_and_lasso() {
+        let rectangle = SelectionDrag {
+            points: vec![Pos2::new(100.0, 100.0), Pos2::new(0.0, 0.0)],
+            is_lasso: false,
+        };
+        assert!(rectangle.contains(Pos2::new(90.0, 10.0)));
+        assert!(!rectangle.contains(Pos2::new(110.0, 10.0)));
+
+        // a lasso only takes what is inside its outline, not its bounding box
+        let lasso = SelectionDrag {
+            points: vec![
+                Pos2::new(0.0, 0.0),
+                Pos2::new(100.0, 0.0),
+                Pos2::new(100.0, 100.0),
+            ],
+            is_lasso: true,
+        };
+        assert!(lasso.contains(Pos2::new(90.0, 10.0)));
+        assert!(!lasso.contains(Pos2::new(10.0, 90.0)));
     }
 }
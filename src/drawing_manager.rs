@@ -4,22 +4,21 @@ use thiserror::Error;
 use egui::{Pos2, Vec2};
 
 use crate::display_manager::DisplayManager;
+use crate::handles::{ConstraintHandle, EdgeHandle, HandleAllocator, VertexHandle};
 use std::cell::RefCell;
 use std::rc::Rc;
 
-// BTreeMap used because the highest key value is being queried
-// to get the next key and this structure maintains order
+// BTreeMap used so that iterating the sketch always happens in handle order
 #[derive(Default)]
 pub struct DrawingManager {
     display_manager: Option<Rc<RefCell<DisplayManager>>>,
 
     edge_map: BTreeMap<EdgeHandle, Edge>,
     vertex_map: BTreeMap<VertexHandle, Vertex>,
-}
 
-type EdgeHandle = i32;
-type VertexHandle = i32;
-type ConstraintHandle = i32;
+    edge_allocator: HandleAllocator<EdgeHandle>,
+    vertex_allocator: HandleAllocator<VertexHandle>,
+}
 
 impl DrawingManager {
     pub fn new() -> Self {
@@ -30,12 +29,15 @@ impl DrawingManager {
     }
 
     pub fn has_edge(&self, eh: &EdgeHandle) -> bool{
-        self.edge_map.contains_key(&eh)
+        self.edge_map.contains_key(eh)
     }
 
     pub fn get_all_edges(&self) -> Vec<&Edge> {
         self.edge_map.values().collect()
     }
+    pub fn get_all_edge_handles(&self) -> Vec<EdgeHandle> {
+        self.edge_map.keys().copied().collect()
+    }
 
     pub fn get_edge(&self, eh: EdgeHandle) -> Result<&Edge, DrawingManagerError> {
         self.edge_map
@@ -61,7 +63,7 @@ impl DrawingManager {
         }
 
         // get next Id to use
-        let next_id = get_next_id(&self.edge_map);
+        let next_id = self.edge_allocator.allocate();
 
         self.edge_map.insert(next_id, Edge::new(vh_1, vh_2));
 
@@ -77,7 +79,10 @@ impl DrawingManager {
     }
 
     pub fn has_vertex(&self, vh: &VertexHandle) -> bool{
-        self.vertex_map.contains_key(&vh)
+        self.vertex_map.contains_key(vh)
+    }
+    pub fn get_all_vertex_handles(&self) -> Vec<VertexHandle> {
+        self.vertex_map.keys().copied().collect()
     }
     pub fn get_all_vertices_mut(&mut self) -> Vec<&mut Vertex> {
         self.vertex_map.values_mut().collect()
@@ -97,24 +102,184 @@ impl DrawingManager {
 
     pub fn add_vertex(&mut self, position: Pos2) -> VertexHandle {
         // get next Id to use
-        let next_id = get_next_id(&self.vertex_map);
+        let next_id = self.vertex_allocator.allocate();
         let vert = Vertex::new(position);
         self.vertex_map.insert(next_id, vert);
         next_id
     }
+
+    // removes the edge and its handle from both end vertices. constraints on the edge
+    // are left alone, ConstraintManager::remove_edge takes care of those as well
+    pub fn remove_edge(&mut self, eh: EdgeHandle) -> Result<Edge, DrawingManagerError> {
+        let edge = self
+            .edge_map
+            .remove(&eh)
+            .ok_or(DrawingManagerError::EdgeNotFound(eh))?;
+        self.edge_allocator.release(eh);
+
+        for vh in [edge.start_point_vh, edge.end_point_vh] {
+            if let Some(vertex) = self.vertex_map.get_mut(&vh) {
+                vertex.edge_handles.retain(|h| *h != eh);
+            }
+        }
+
+        Ok(edge)
+    }
+
+    // removes the vertex along with every edge attached to it
+    pub fn remove_vertex(
+        &mut self,
+        vh: VertexHandle,
+    ) -> Result<(Vertex, Vec<(EdgeHandle, Edge)>), DrawingManagerError> {
+        let edge_handles = self.get_vertex(vh)?.edge_handles.clone();
+
+        let mut removed_edges = vec![];
+        for eh in edge_handles {
+            removed_edges.push((eh, self.remove_edge(eh)?));
+        }
+
+        let vertex = self.vertex_map.remove(&vh).unwrap();
+        self.vertex_allocator.release(vh);
+
+        Ok((vertex, removed_edges))
+    }
+
+    // puts a removed vertex back under its old handle, for undoing the removal
+    pub fn restore_vertex(
+        &mut self,
+        vh: VertexHandle,
+        position: Pos2,
+    ) -> Result<(), DrawingManagerError> {
+        if !self.vertex_allocator.restore(vh) {
+            return Err(DrawingManagerError::VertexNotAdded);
+        }
+        self.vertex_map.insert(vh, Vertex::new(position));
+        Ok(())
+    }
+
+    // puts a removed edge back under its old handle, its end vertices have to be back already
+    pub fn restore_edge(
+        &mut self,
+        eh: EdgeHandle,
+        vh_1: VertexHandle,
+        vh_2: VertexHandle,
+    ) -> Result<(), DrawingManagerError> {
+        for vh in [vh_1, vh_2] {
+            if !self.vertex_map.contains_key(&vh) {
+                return Err(DrawingManagerError::VertexNotFound(vh));
+            }
+        }
+        if !self.edge_allocator.restore(eh) {
+            return Err(DrawingManagerError::EdgeNotAdded);
+        }
+
+        self.edge_map.insert(eh, Edge::new(vh_1, vh_2));
+        self.get_vertex_mut(vh_1).unwrap().edge_handles.push(eh);
+        self.get_vertex_mut(vh_2).unwrap().edge_handles.push(eh);
+
+        Ok(())
+    }
+
+    pub fn to_record(&self) -> DrawingManagerRecord {
+        DrawingManagerRecord {
+            vertices: self
+                .vertex_map
+                .iter()
+                .map(|(vh, vertex)| VertexRecord {
+                    handle: *vh,
+                    position: vertex.position,
+                })
+                .collect(),
+            edges: self
+                .edge_map
+                .iter()
+                .map(|(eh, edge)| EdgeRecord {
+                    handle: *eh,
+                    start_point_vh: edge.start_point_vh,
+                    end_point_vh: edge.end_point_vh,
+                })
+                .collect(),
+            vertex_allocator: self.vertex_allocator.clone(),
+            edge_allocator: self.edge_allocator.clone(),
+        }
+    }
+
+    // replaces the sketch with the one in the record, keeping every handle as it was saved.
+    // the edges come back without constraints, ConstraintManager::load_record adds those
+    pub fn load_record(&mut self, record: DrawingManagerRecord) -> Result<(), DrawingManagerError> {
+        // the allocators have to be sound before they can vouch for the handles below,
+        // is_live then also keeps every saved handle off the free lists
+        if !record.vertex_allocator.is_valid() {
+            return Err(DrawingManagerError::VertexNotAdded);
+        }
+        if !record.edge_allocator.is_valid() {
+            return Err(DrawingManagerError::EdgeNotAdded);
+        }
+
+        let mut vertex_map = BTreeMap::new();
+        for vr in record.vertices {
+            if !record.vertex_allocator.is_live(vr.handle) || vertex_map.contains_key(&vr.handle) {
+                return Err(DrawingManagerError::VertexNotAdded);
+            }
+            vertex_map.insert(vr.handle, Vertex::new(vr.position));
+        }
+
+        let mut edge_map = BTreeMap::new();
+        for er in record.edges {
+            if !record.edge_allocator.is_live(er.handle) || edge_map.contains_key(&er.handle) {
+                return Err(DrawingManagerError::EdgeNotAdded);
+            }
+            for vh in [er.start_point_vh, er.end_point_vh] {
+                let vertex: &mut Vertex = vertex_map
+                    .get_mut(&vh)
+                    .ok_or(DrawingManagerError::VertexNotFound(vh))?;
+                vertex.edge_handles.push(er.handle);
+            }
+            edge_map.insert(er.handle, Edge::new(er.start_point_vh, er.end_point_vh));
+        }
+
+        self.vertex_map = vertex_map;
+        self.edge_map = edge_map;
+        self.vertex_allocator = record.vertex_allocator;
+        self.edge_allocator = record.edge_allocator;
+
+        Ok(())
+    }
+}
+
+// the saved form of the sketch geometry, the allocators go along with it so that
+// handles stay unique after loading
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct DrawingManagerRecord {
+    pub vertices: Vec<VertexRecord>,
+    pub edges: Vec<EdgeRecord>,
+    pub vertex_allocator: HandleAllocator<VertexHandle>,
+    pub edge_allocator: HandleAllocator<EdgeHandle>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct VertexRecord {
+    pub handle: VertexHandle,
+    pub position: Pos2,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct EdgeRecord {
+    pub handle: EdgeHandle,
+    pub start_point_vh: VertexHandle,
+    pub end_point_vh: VertexHandle,
 }
 
+#[derive(Clone)]
 pub struct Edge {
     pub start_point_vh: VertexHandle,
     pub end_point_vh: VertexHandle,
-    pub constraints: Vec<ConstraintHandle>
 }
 impl Edge {
     pub fn new(start_point_vh: VertexHandle, end_point_vh: VertexHandle) -> Self {
         Self {
             start_point_vh,
             end_point_vh,
-            constraints : vec![]
         }
     }
     pub fn direction_from_edge( drawing_manager : &DrawingManager, edge : &Edge) -> Vec2{
@@ -135,6 +300,7 @@ impl Edge {
         Edge::direction_from_edge(drawing_manager, edge)
     }
 }
+#[derive(Clone)]
 pub struct Vertex {
     pub position: Pos2,
     pub edge_handles: Vec<EdgeHandle>,
@@ -149,12 +315,6 @@ impl Vertex {
     }
 }
 
-//Utilities
-
-fn get_next_id<V>(map: &BTreeMap<i32, V>) -> i32 {
-    map.last_key_value().map_or(0, |(k, _)| k + 1)
-}
-
 #[derive(Debug, Error)]
 pub enum DrawingManagerError {
     #[error("Edge could not be added")]
@@ -175,4 +335,26 @@ pub enum DrawingManagerError {
     DegenerateEdge,
     #[error("Full overlap detected")]
     FullOverlap,
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handles::Handle;
+
+    #[test]
+    fn stale_vertex_handle_is_not_found() {
+        let mut dm = DrawingManager::new();
+        let stale = dm.add_vertex(Pos2::new(1.0, 2.0));
+        dm.remove_vertex(stale).unwrap();
+
+        // the new vertex takes over the index, the old handle must not reach it
+        let current = dm.add_vertex(Pos2::new(3.0, 4.0));
+        assert_eq!(current.index(), stale.index());
+
+        assert!(matches!(
+            dm.get_vertex(stale),
+            Err(DrawingManagerError::VertexNotFound(vh)) if vh == stale
+        ));
+        assert_eq!(dm.get_vertex(current).unwrap().position, Pos2::new(3.0, 4.0));
+    }
+}
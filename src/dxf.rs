@@ -0,0 +1,497 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use egui::Pos2;
+use thiserror::Error;
+
+use crate::constraint_manager::{Constraint, ConstraintManager};
+use crate::drawing_manager::DrawingManager;
+use crate::handles::{EdgeHandle, Handle, VertexHandle};
+
+// ASCII DXF R12. LINEs are the edges, vertices that no edge uses go out as POINTs.
+// DXF is y up where the sketch is y down, so y is flipped both ways.
+//
+// everything we write carries XDATA under our APPID with the ids the vertices and
+// edges have in the file. constraints are TEXT entities on their own layer, readable
+// in any CAD package, with the constraint itself as json in the XDATA, pointing at
+// those file ids. without the XDATA, as with a drawing from somewhere else, LINE ends
+// that land within the merge tolerance of each other become one vertex
+
+const APP_ID: &str = "CONSTRAINT_PROJECT";
+const GEOMETRY_LAYER: &str = "0";
+const CONSTRAINT_LAYER: &str = "CONSTRAINTS";
+
+// XDATA strings can't be any longer than this
+const XDATA_STRING_LENGTH: usize = 255;
+
+pub const DEFAULT_MERGE_TOLERANCE: f32 = 0.01;
+
+pub fn write_dxf(
+    drawing_manager: &DrawingManager,
+    constraint_manager: &ConstraintManager,
+) -> String {
+    let mut dxf = String::new();
+
+    group(&mut dxf, 0, "SECTION");
+    group(&mut dxf, 2, "HEADER");
+    group(&mut dxf, 9, "$ACADVER");
+    group(&mut dxf, 1, "AC1009");
+    group(&mut dxf, 0, "ENDSEC");
+
+    group(&mut dxf, 0, "SECTION");
+    group(&mut dxf, 2, "TABLES");
+    group(&mut dxf, 0, "TABLE");
+    group(&mut dxf, 2, "LTYPE");
+    group(&mut dxf, 70, 1);
+    group(&mut dxf, 0, "LTYPE");
+    group(&mut dxf, 2, "CONTINUOUS");
+    group(&mut dxf, 70, 0);
+    group(&mut dxf, 3, "Solid line");
+    group(&mut dxf, 72, 65);
+    group(&mut dxf, 73, 0);
+    group(&mut dxf, 40, 0.0);
+    group(&mut dxf, 0, "ENDTAB");
+    group(&mut dxf, 0, "TABLE");
+    group(&mut dxf, 2, "LAYER");
+    group(&mut dxf, 70, 2);
+    for (layer, colour) in [(GEOMETRY_LAYER, 7), (CONSTRAINT_LAYER, 3)] {
+        group(&mut dxf, 0, "LAYER");
+        group(&mut dxf, 2, layer);
+        group(&mut dxf, 70, 0);
+        group(&mut dxf, 62, colour);
+        group(&mut dxf, 6, "CONTINUOUS");
+    }
+    group(&mut dxf, 0, "ENDTAB");
+    group(&mut dxf, 0, "TABLE");
+    group(&mut dxf, 2, "APPID");
+    group(&mut dxf, 70, 1);
+    group(&mut dxf, 0, "APPID");
+    group(&mut dxf, 2, APP_ID);
+    group(&mut dxf, 70, 0);
+    group(&mut dxf, 0, "ENDTAB");
+    group(&mut dxf, 0, "ENDSEC");
+
+    group(&mut dxf, 0, "SECTION");
+    group(&mut dxf, 2, "ENTITIES");
+
+    // file ids are just the position in handle order
+    let vertex_ids: BTreeMap<VertexHandle, u32> = drawing_manager
+        .get_all_vertex_handles()
+        .into_iter()
+        .zip(0..)
+        .collect();
+    let edge_ids: BTreeMap<EdgeHandle, u32> = drawing_manager
+        .get_all_edge_handles()
+        .into_iter()
+        .zip(0..)
+        .collect();
+
+    let pos = |vh: VertexHandle| drawing_manager.get_vertex(vh).unwrap().position;
+
+    for (eh, edge_id) in &edge_ids {
+        let edge = drawing_manager.get_edge(*eh).unwrap();
+
+        group(&mut dxf, 0, "LINE");
+        group(&mut dxf, 8, GEOMETRY_LAYER);
+        point(&mut dxf, 0, pos(edge.start_point_vh));
+        point(&mut dxf, 1, pos(edge.end_point_vh));
+        group(&mut dxf, 1001, APP_ID);
+        group(&mut dxf, 1071, edge_id);
+        group(&mut dxf, 1071, vertex_ids[&edge.start_point_vh]);
+        group(&mut dxf, 1071, vertex_ids[&edge.end_point_vh]);
+    }
+
+    for (vh, vertex_id) in &vertex_ids {
+        if !drawing_manager
+            .get_vertex(*vh)
+            .unwrap()
+            .edge_handles
+            .is_empty()
+        {
+            continue;
+        }
+
+        group(&mut dxf, 0, "POINT");
+        group(&mut dxf, 8, GEOMETRY_LAYER);
+        point(&mut dxf, 0, pos(*vh));
+        group(&mut dxf, 1001, APP_ID);
+        group(&mut dxf, 1071, vertex_id);
+    }
+
+    for ch in constraint_manager.get_all_constraint_handles() {
+        let constraint = constraint_manager.get_constraint(ch).unwrap();
+        let in_file = constraint
+            .map_handles(
+                |vh| Some(VertexHandle::new(vertex_ids[&vh], 0)),
+                |eh| Some(EdgeHandle::new(edge_ids[&eh], 0)),
+            )
+            .unwrap();
+        let json = serde_json::to_string(&in_file).unwrap();
+
+        // the label sits in the middle of everything the constraint touches
+        let mut positions: Vec<Pos2> = constraint.vertex_handles().into_iter().map(pos).collect();
+        for eh in constraint.edge_handles() {
+            let edge = drawing_manager.get_edge(eh).unwrap();
+            positions.push(pos(edge.start_point_vh).lerp(pos(edge.end_point_vh), 0.5));
+        }
+        let label_pos = positions
+            .iter()
+            .fold(Pos2::ZERO, |sum, p| sum + p.to_vec2())
+            / positions.len().max(1) as f32;
+
+        group(&mut dxf, 0, "TEXT");
+        group(&mut dxf, 8, CONSTRAINT_LAYER);
+        point(&mut dxf, 0, label_pos);
+        group(&mut dxf, 40, 10.0);
+        group(&mut dxf, 1, label(constraint));
+        group(&mut dxf, 1001, APP_ID);
+        for chunk in json.as_bytes().chunks(XDATA_STRING_LENGTH) {
+            // the json is all ascii so any split lands on a char boundary
+            group(&mut dxf, 1000, std::str::from_utf8(chunk).unwrap());
+        }
+    }
+
+    group(&mut dxf, 0, "ENDSEC");
+    group(&mut dxf, 0, "EOF");
+
+    dxf
+}
+
+// adds the drawing to the managers, which are expected to be empty. constraints that
+// refer to geometry which didn't make it into the file are skipped
+pub fn read_dxf(
+    text: &str,
+    merge_tolerance: f32,
+    drawing_manager: &RefCell<DrawingManager>,
+    constraint_manager: &RefCell<ConstraintManager>,
+) -> Result<(), DxfError> {
+    let entities = parse_entities(text)?;
+
+    let mut vertex_handles: BTreeMap<u32, VertexHandle> = BTreeMap::new();
+    let mut edge_handles: BTreeMap<u32, EdgeHandle> = BTreeMap::new();
+    let mut constraints = vec![];
+
+    {
+        let mut dm_borrowed = drawing_manager.borrow_mut();
+
+        // vertices that came without ids, only these are merged with each other
+        let mut loose_vertices: Vec<(Pos2, VertexHandle)> = vec![];
+
+        let mut vertex_for = |dm: &mut DrawingManager, id: Option<u32>, position: Pos2| match id {
+            Some(id) => *vertex_handles
+                .entry(id)
+                .or_insert_with(|| dm.add_vertex(position)),
+            None => {
+                if let Some((_, vh)) = loose_vertices
+                    .iter()
+                    .find(|(p, _)| p.distance(position) <= merge_tolerance)
+                {
+                    return *vh;
+                }
+                let vh = dm.add_vertex(position);
+                loose_vertices.push((position, vh));
+                vh
+            }
+        };
+
+        for entity in &entities {
+            match entity.kind.as_str() {
+                "LINE" => {
+                    let start = entity.point(0)?;
+                    let end = entity.point(1)?;
+                    let ids = entity.xdata_ids()?;
+
+                    let vh_1 = vertex_for(&mut dm_borrowed, ids.get(1).copied(), start);
+                    let vh_2 = vertex_for(&mut dm_borrowed, ids.get(2).copied(), end);
+                    if vh_1 == vh_2 {
+                        continue;
+                    }
+
+                    let eh = dm_borrowed.add_edge(vh_1, vh_2).unwrap();
+                    if let Some(edge_id) = ids.first() {
+                        edge_handles.insert(*edge_id, eh);
+                    }
+                }
+                "POINT" => {
+                    let position = entity.point(0)?;
+                    let ids = entity.xdata_ids()?;
+                    vertex_for(&mut dm_borrowed, ids.first().copied(), position);
+                }
+                "TEXT" if entity.layer() == Some(CONSTRAINT_LAYER) => {
+                    let json = entity.xdata_string();
+                    match serde_json::from_str::<Constraint>(&json) {
+                        Ok(constraint) => constraints.push(constraint),
+                        Err(err) => log::warn!("Skipping unreadable DXF constraint: {err}"),
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    let mut cm_borrowed = constraint_manager.borrow_mut();
+    for constraint in constraints {
+        let constraint = constraint.map_handles(
+            |vh| vertex_handles.get(&vh.index()).copied(),
+            |eh| edge_handles.get(&eh.index()).copied(),
+        );
+
+        match constraint {
+            Some(constraint) => {
+                cm_borrowed.restore_constraint(constraint)?;
+            }
+            None => log::warn!("Skipping DXF constraint on missing geometry"),
+        }
+    }
+
+    Ok(())
+}
+
+fn group(dxf: &mut String, code: i32, value: impl std::fmt::Display) {
+    let _ = write!(dxf, "{code:>3}\n{value}\n");
+}
+
+// offset 0 writes the 10/20/30 group, offset 1 the 11/21/31 one
+fn point(dxf: &mut String, offset: i32, pos: Pos2) {
+    group(dxf, 10 + offset, pos.x);
+    group(dxf, 20 + offset, -pos.y);
+    group(dxf, 30 + offset, 0.0);
+}
+
+fn label(constraint: &Constraint) -> String {
+    match constraint {
+        Constraint::LENGTH(c) => format!("LENGTH {:.3}", c.length),
+        Constraint::ANGLE(c) => format!("ANGLE {:.3}", c.angle.to_degrees()),
+        Constraint::PARALLEL(_) => "PARALLEL".to_string(),
+        Constraint::PERPENDICULAR(_) => "PERPENDICULAR".to_string(),
+        Constraint::HORIZONTAL(_) => "HORIZONTAL".to_string(),
+        Constraint::VERTICAL(_) => "VERTICAL".to_string(),
+        Constraint::FIXED(_) => "FIXED".to_string(),
+        Constraint::COINCIDENT(_) => "COINCIDENT".to_string(),
+        Constraint::POINT_ON_EDGE(_) => "POINT ON EDGE".to_string(),
+        Constraint::EQUAL_LENGTH(_) => "EQUAL LENGTH".to_string(),
+        Constraint::SYMMETRIC(_) => "SYMMETRIC".to_string(),
+        Constraint::DISTANCE(c) => format!("DISTANCE {:.3}", c.distance),
+        Constraint::EDGE_DISTANCE(c) => format!("EDGE DISTANCE {:.3}", c.distance),
+    }
+}
+
+struct Entity {
+    kind: String,
+    // code, value and the line the code was on
+    groups: Vec<(i32, String, usize)>,
+}
+
+impl Entity {
+    fn layer(&self) -> Option<&str> {
+        self.groups
+            .iter()
+            .find(|(code, _, _)| *code == 8)
+            .map(|(_, value, _)| value.as_str())
+    }
+
+    fn number(&self, code: i32) -> Result<Option<f32>, DxfError> {
+        match self.groups.iter().find(|(c, _, _)| *c == code) {
+            Some((_, value, line)) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| DxfError::BadNumber(*line)),
+            None => Ok(None),
+        }
+    }
+
+    fn point(&self, offset: i32) -> Result<Pos2, DxfError> {
+        let x = self.number(10 + offset)?.unwrap_or(0.0);
+        let y = self.number(20 + offset)?.unwrap_or(0.0);
+        Ok(Pos2::new(x, -y))
+    }
+
+    // the groups between our APPID and the next one
+    fn xdata(&self) -> impl Iterator<Item = &(i32, String, usize)> {
+        self.groups
+            .iter()
+            .skip_while(|(code, value, _)| !(*code == 1001 && value == APP_ID))
+            .skip(1)
+            .take_while(|(code, _, _)| *code != 1001)
+    }
+
+    fn xdata_ids(&self) -> Result<Vec<u32>, DxfError> {
+        self.xdata()
+            .filter(|(code, _, _)| *code == 1071)
+            .map(|(_, value, line)| value.parse().map_err(|_| DxfError::BadNumber(*line)))
+            .collect()
+    }
+
+    fn xdata_string(&self) -> String {
+        self.xdata()
+            .filter(|(code, _, _)| *code == 1000)
+            .map(|(_, value, _)| value.as_str())
+            .collect()
+    }
+}
+
+// the entities in the ENTITIES section, everything else in the file is skipped over
+fn parse_entities(text: &str) -> Result<Vec<Entity>, DxfError> {
+    let mut lines = text.lines().enumerate();
+    let mut groups = vec![];
+    while let Some((line, code)) = lines.next() {
+        let code: i32 = code
+            .trim()
+            .parse()
+            .map_err(|_| DxfError::BadGroupCode(line + 1))?;
+        let (_, value) = lines.next().ok_or(DxfError::UnexpectedEnd)?;
+        groups.push((code, value.trim().to_string(), line + 1));
+    }
+
+    let mut entities: Vec<Entity> = vec![];
+    let mut in_entities = false;
+    let mut groups = groups.into_iter().peekable();
+    while let Some((code, value, line)) = groups.next() {
+        if code != 0 {
+            if let (true, Some(entity)) = (in_entities, entities.last_mut()) {
+                entity.groups.push((code, value, line));
+            }
+            continue;
+        }
+
+        match value.as_str() {
+            "SECTION" => {
+                in_entities = groups
+                    .next_if(|(code, _, _)| *code == 2)
+                    .is_some_and(|(_, name, _)| name == "ENTITIES");
+            }
+            "ENDSEC" => in_entities = false,
+            _ if in_entities => entities.push(Entity {
+                kind: value,
+                groups: vec![],
+            }),
+            _ => (),
+        }
+    }
+
+    Ok(entities)
+}
+
+#[derive(Debug, Error)]
+pub enum DxfError {
+    #[error("DXF group code on line {0} is not a number")]
+    BadGroupCode(usize),
+    #[error("DXF value on line {0} is not a number")]
+    BadNumber(usize),
+    #[error("DXF file ends part way through a group")]
+    UnexpectedEnd,
+    #[error("DXF constraint could not be restored: {0}")]
+    Constraint(#[from] crate::constraint_manager::ConstraintError),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+
+    fn managers() -> (Rc<RefCell<DrawingManager>>, RefCell<ConstraintManager>) {
+        let dm = Rc::new(RefCell::new(DrawingManager::new()));
+        let mut cm = ConstraintManager::new();
+        cm.set_drawing_manager(Rc::clone(&dm));
+        (dm, RefCell::new(cm))
+    }
+
+    #[test]
+    fn write_then_read_keeps_the_sketch() {
+        let (dm, cm) = managers();
+        let a = dm.borrow_mut().add_vertex(Pos2::new(0.0, 0.0));
+        let b = dm.borrow_mut().add_vertex(Pos2::new(10.0, 0.0));
+        let c = dm.borrow_mut().add_vertex(Pos2::new(10.0, -5.5));
+        // a vertex on its own goes out as a POINT
+        let d = dm.borrow_mut().add_vertex(Pos2::new(3.25, 8.0));
+        let ab = dm.borrow_mut().add_edge(a, b).unwrap();
+        let bc = dm.borrow_mut().add_edge(b, c).unwrap();
+        {
+            let mut cm = cm.borrow_mut();
+            cm.add_fixed_constraint(a).unwrap();
+            cm.add_length_constraint(ab).unwrap();
+            cm.add_horizontal_constraint(ab).unwrap();
+            cm.add_angle_constraint(ab, bc).unwrap();
+            cm.add_distance_constraint(a, d).unwrap();
+        }
+
+        let dxf = write_dxf(&dm.borrow(), &cm.borrow());
+        let (read_dm, read_cm) = managers();
+        read_dxf(&dxf, DEFAULT_MERGE_TOLERANCE, &read_dm, &read_cm).unwrap();
+
+        let (dm, read_dm) = (dm.borrow(), read_dm.borrow());
+        assert_eq!(
+            read_dm.get_all_vertex_handles(),
+            dm.get_all_vertex_handles()
+        );
+        for vh in dm.get_all_vertex_handles() {
+            let position = dm.get_vertex(vh).unwrap().position;
+            let read_position = read_dm.get_vertex(vh).unwrap().position;
+            assert!(position.distance(read_position) < 1e-4);
+        }
+
+        assert_eq!(read_dm.get_all_edge_handles(), dm.get_all_edge_handles());
+        for eh in dm.get_all_edge_handles() {
+            let edge = dm.get_edge(eh).unwrap();
+            let read_edge = read_dm.get_edge(eh).unwrap();
+            assert_eq!(
+                (read_edge.start_point_vh, read_edge.end_point_vh),
+                (edge.start_point_vh, edge.end_point_vh)
+            );
+        }
+
+        let (cm, read_cm) = (cm.borrow(), read_cm.borrow());
+        let as_json = |cm: &ConstraintManager| -> Vec<serde_json::Value> {
+            cm.get_all_constraint_handles()
+                .into_iter()
+                .map(|ch| serde_json::to_value(cm.get_constraint(ch).unwrap()).unwrap())
+                .collect()
+        };
+        assert_eq!(as_json(&read_cm), as_json(&cm));
+    }
+
+    // a plain drawing from somewhere else, LINEs and nothing more
+    fn foreign_dxf(lines: &[[f32; 4]]) -> String {
+        let mut dxf = String::new();
+        group(&mut dxf, 0, "SECTION");
+        group(&mut dxf, 2, "ENTITIES");
+        for [x_1, y_1, x_2, y_2] in lines {
+            group(&mut dxf, 0, "LINE");
+            group(&mut dxf, 8, GEOMETRY_LAYER);
+            point(&mut dxf, 0, Pos2::new(*x_1, *y_1));
+            point(&mut dxf, 1, Pos2::new(*x_2, *y_2));
+        }
+        group(&mut dxf, 0, "ENDSEC");
+        group(&mut dxf, 0, "EOF");
+        dxf
+    }
+
+    #[test]
+    fn foreign_line_ends_merge_within_tolerance() {
+        let near = DEFAULT_MERGE_TOLERANCE * 0.5;
+        let dxf = foreign_dxf(&[[0.0, 0.0, 10.0, 0.0], [10.0 + near, near, 10.0, 10.0]]);
+
+        let (dm, cm) = managers();
+        read_dxf(&dxf, DEFAULT_MERGE_TOLERANCE, &dm, &cm).unwrap();
+
+        // the two lines meet in one shared vertex
+        let dm = dm.borrow();
+        assert_eq!(dm.get_all_vertex_handles().len(), 3);
+        let edges = dm.get_all_edges();
+        assert_eq!(edges.len(), 2);
+        assert_eq!(edges[0].end_point_vh, edges[1].start_point_vh);
+    }
+
+    #[test]
+    fn foreign_line_ends_apart_stay_separate() {
+        let far = DEFAULT_MERGE_TOLERANCE * 2.0;
+        let dxf = foreign_dxf(&[[0.0, 0.0, 10.0, 0.0], [10.0 + far, 0.0, 10.0, 10.0]]);
+
+        let (dm, cm) = managers();
+        read_dxf(&dxf, DEFAULT_MERGE_TOLERANCE, &dm, &cm).unwrap();
+
+        assert_eq!(dm.borrow().get_all_vertex_handles().len(), 4);
+    }
+}
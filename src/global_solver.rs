@@ -0,0 +1,1297 @@
+use std::collections::BTreeMap;
+
+use egui::Pos2;
+
+use crate::constraint_manager::Constraint;
+use crate::drawing_manager::DrawingManager;
+use crate::handles::{ConstraintHandle, VertexHandle};
+
+// Global solver
+// Every constraint is turned into one or more residual equations over the
+// vertex positions, then all of them are minimised together with
+// Levenberg-Marquardt. Pinned vertices (the ones being dragged) are held in place
+// and everything else connected through constraints is free to move.
+//
+// Residuals are all expressed in drawing units (angular errors are scaled by the
+// arm length) so a single tolerance applies to every constraint type.
+
+pub struct GlobalSolverSettings {
+    pub max_iterations: usize,
+    pub tolerance: f64,
+}
+
+impl Default for GlobalSolverSettings {
+    fn default() -> Self {
+        Self {
+            max_iterations: 50,
+            tolerance: 0.001,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct GlobalSolverResponse {
+    pub converged: bool,
+    pub iterations: usize,
+    // largest absolute residual at the end of the solve
+    pub residual: f32,
+    // solved positions for every vertex the solver touched, pinned ones included
+    pub new_positions: BTreeMap<VertexHandle, Pos2>,
+}
+
+// how constrained a vertex or edge is, for colouring the sketch
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DofStatus {
+    Under,
+    Fully,
+    Over,
+}
+
+#[derive(Default)]
+pub struct DofAnalysis {
+    // directions each vertex can still move in, 0 to 2
+    pub vertex_dof: BTreeMap<VertexHandle, usize>,
+    // total for the sketch, including rigid body motion if nothing is fixed
+    pub sketch_dof: usize,
+    // constraints that only repeat what earlier constraints already enforce
+    pub redundant_constraints: Vec<ConstraintHandle>,
+    // constraints that repeat earlier ones and disagree with them
+    pub conflicting_constraints: Vec<ConstraintHandle>,
+    // vertices touched by a redundant or conflicting constraint
+    pub over_constrained_vertices: Vec<VertexHandle>,
+}
+
+impl DofAnalysis {
+    pub fn vertex_status(&self, vh: VertexHandle) -> DofStatus {
+        if self.over_constrained_vertices.contains(&vh) {
+            DofStatus::Over
+        } else if self.vertex_dof.get(&vh).is_some_and(|dof| *dof == 0) {
+            DofStatus::Fully
+        } else {
+            DofStatus::Under
+        }
+    }
+
+    pub fn edge_status(&self, vh_1: VertexHandle, vh_2: VertexHandle) -> DofStatus {
+        match (self.vertex_status(vh_1), self.vertex_status(vh_2)) {
+            (DofStatus::Over, _) | (_, DofStatus::Over) => DofStatus::Over,
+            (DofStatus::Fully, DofStatus::Fully) => DofStatus::Fully,
+            _ => DofStatus::Under,
+        }
+    }
+}
+
+// A single scalar equation that is zero when its constraint is satisfied.
+// Scales are measured from the geometry when the solve starts.
+enum Residual {
+    Distance {
+        vh_1: VertexHandle,
+        vh_2: VertexHandle,
+        target: f64,
+    },
+    Angle {
+        pivot_vh: VertexHandle,
+        outer_vh_1: VertexHandle,
+        outer_vh_2: VertexHandle,
+        target: f64,
+        scale: f64,
+    },
+    // aligned or opposed, whichever is nearer the starting geometry
+    Parallel {
+        e1_vh_1: VertexHandle,
+        e1_vh_2: VertexHandle,
+        e2_vh_1: VertexHandle,
+        e2_vh_2: VertexHandle,
+        target: f64,
+        scale: f64,
+    },
+    // the right angle nearest the starting geometry, so the residual has a slope
+    // even when the edges start out parallel
+    Perpendicular {
+        e1_vh_1: VertexHandle,
+        e1_vh_2: VertexHandle,
+        e2_vh_1: VertexHandle,
+        e2_vh_2: VertexHandle,
+        target: f64,
+        scale: f64,
+    },
+    // difference between the lengths of two edges
+    EqualLength {
+        e1_vh_1: VertexHandle,
+        e1_vh_2: VertexHandle,
+        e2_vh_1: VertexHandle,
+        e2_vh_2: VertexHandle,
+    },
+    // two vertices mirrored across a line: the offset between them along the line,
+    // and how far their midpoint sits off of it
+    MirrorOffset {
+        vh_1: VertexHandle,
+        vh_2: VertexHandle,
+        line_vh_1: VertexHandle,
+        line_vh_2: VertexHandle,
+    },
+    MidpointOnLine {
+        vh_1: VertexHandle,
+        vh_2: VertexHandle,
+        line_vh_1: VertexHandle,
+        line_vh_2: VertexHandle,
+    },
+    // difference of the endpoints along one axis, 0 for x and 1 for y
+    AxisOffset {
+        vh_1: VertexHandle,
+        vh_2: VertexHandle,
+        axis: usize,
+    },
+    // signed perpendicular distance from a vertex to the line through two others
+    PointOnLine {
+        vh: VertexHandle,
+        line_vh_1: VertexHandle,
+        line_vh_2: VertexHandle,
+        target: f64,
+    },
+    // how far the vertex's projection lands outside the segment, 0 when within it
+    PointWithinSegment {
+        vh: VertexHandle,
+        segment_vh_1: VertexHandle,
+        segment_vh_2: VertexHandle,
+    },
+}
+
+pub fn solve<'a>(
+    constraints: impl Iterator<Item = &'a Constraint>,
+    drawing_manager: &DrawingManager,
+    pinned: &[(VertexHandle, Pos2)],
+    settings: &GlobalSolverSettings,
+) -> GlobalSolverResponse {
+    let residuals = build_residuals(constraints, drawing_manager);
+
+    // every vertex referenced by a residual takes part, pinned vertices are not variables
+    let mut variable_vhs: Vec<VertexHandle> = vec![];
+    for residual in &residuals {
+        for vh in residual.vertex_handles() {
+            if !variable_vhs.contains(&vh) && !pinned.iter().any(|(p_vh, _)| *p_vh == vh) {
+                variable_vhs.push(vh);
+            }
+        }
+    }
+
+    let state = SolverState {
+        drawing_manager,
+        pinned,
+        variable_vhs,
+    };
+
+    let mut x = state.initial_variables();
+    let mut r = state.evaluate(&residuals, &x);
+    let mut cost = sum_sq(&r);
+
+    let mut lambda = 1e-3;
+    let mut iterations = 0;
+    let mut converged = max_abs(&r) < settings.tolerance;
+
+    while !converged && iterations < settings.max_iterations {
+        iterations += 1;
+
+        let jacobian = state.jacobian(&residuals, &x);
+        let n = x.len();
+
+        // normal equations: (JtJ + lambda * I) dx = -Jt r
+        let mut jtj = vec![vec![0.0; n]; n];
+        let mut jtr = vec![0.0; n];
+        for (row, r_i) in jacobian.iter().zip(&r) {
+            for a in 0..n {
+                if row[a] == 0.0 {
+                    continue;
+                }
+                jtr[a] += row[a] * r_i;
+                for b in 0..n {
+                    jtj[a][b] += row[a] * row[b];
+                }
+            }
+        }
+
+        // grow the damping until a step actually lowers the cost
+        let mut improved = false;
+        while lambda < 1e12 {
+            let mut damped = jtj.clone();
+            for (i, damped_row) in damped.iter_mut().enumerate() {
+                damped_row[i] += lambda;
+            }
+            let rhs: Vec<f64> = jtr.iter().map(|v| -v).collect();
+
+            if let Some(dx) = solve_linear_system(damped, rhs) {
+                let x_new: Vec<f64> = x.iter().zip(&dx).map(|(a, b)| a + b).collect();
+                let r_new = state.evaluate(&residuals, &x_new);
+                let cost_new = sum_sq(&r_new);
+
+                if cost_new < cost {
+                    x = x_new;
+                    r = r_new;
+                    cost = cost_new;
+                    lambda = (lambda * 0.1).max(1e-9);
+                    improved = true;
+                    break;
+                }
+            }
+            lambda *= 10.0;
+        }
+
+        converged = max_abs(&r) < settings.tolerance;
+
+        // stuck in a minimum that doesn't satisfy the constraints
+        if !improved {
+            break;
+        }
+    }
+
+    GlobalSolverResponse {
+        converged,
+        iterations,
+        residual: max_abs(&r) as f32,
+        new_positions: state.positions(&x),
+    }
+}
+
+fn build_residuals<'a>(
+    constraints: impl Iterator<Item = &'a Constraint>,
+    dm: &DrawingManager,
+) -> Vec<Residual> {
+    let pos = |vh: VertexHandle| -> Pos2 { dm.get_vertex(vh).unwrap().position };
+
+    let mut residuals = vec![];
+
+    for constraint in constraints {
+        match constraint {
+            Constraint::LENGTH(lc) => {
+                let edge = dm.get_edge(lc.edge_handle).unwrap();
+                residuals.push(Residual::Distance {
+                    vh_1: edge.start_point_vh,
+                    vh_2: edge.end_point_vh,
+                    target: lc.length as f64,
+                });
+            }
+            Constraint::ANGLE(ac) => {
+                let pivot = pos(ac.pivot_vert_handle);
+                let dir_1 = pos(ac.edge_1_outer_vert_handle) - pivot;
+                let dir_2 = pos(ac.edge_2_outer_vert_handle) - pivot;
+
+                residuals.push(Residual::Angle {
+                    pivot_vh: ac.pivot_vert_handle,
+                    outer_vh_1: ac.edge_1_outer_vert_handle,
+                    outer_vh_2: ac.edge_2_outer_vert_handle,
+                    target: ac.angle as f64,
+                    scale: ((dir_1.length() + dir_2.length()) * 0.5) as f64,
+                });
+            }
+            Constraint::PARALLEL(pc) => {
+                let edge_1 = dm.get_edge(pc.edge_1_handle).unwrap();
+                let edge_2 = dm.get_edge(pc.edge_2_handle).unwrap();
+
+                let length_1 = pos(edge_1.start_point_vh).distance(pos(edge_1.end_point_vh));
+                let length_2 = pos(edge_2.start_point_vh).distance(pos(edge_2.end_point_vh));
+
+                let dir_1 = pos(edge_1.end_point_vh) - pos(edge_1.start_point_vh);
+                let dir_2 = pos(edge_2.end_point_vh) - pos(edge_2.start_point_vh);
+                let start_angle = signed_angle(
+                    [dir_1.x as f64, dir_1.y as f64],
+                    [dir_2.x as f64, dir_2.y as f64],
+                );
+                let target = if start_angle.abs() <= std::f64::consts::FRAC_PI_2 {
+                    0.0
+                } else {
+                    std::f64::consts::PI
+                };
+
+                residuals.push(Residual::Parallel {
+                    e1_vh_1: edge_1.start_point_vh,
+                    e1_vh_2: edge_1.end_point_vh,
+                    e2_vh_1: edge_2.start_point_vh,
+                    e2_vh_2: edge_2.end_point_vh,
+                    target,
+                    scale: ((length_1 + length_2) * 0.5) as f64,
+                });
+            }
+            Constraint::PERPENDICULAR(pc) => {
+                let edge_1 = dm.get_edge(pc.edge_1_handle).unwrap();
+                let edge_2 = dm.get_edge(pc.edge_2_handle).unwrap();
+
+                let length_1 = pos(edge_1.start_point_vh).distance(pos(edge_1.end_point_vh));
+                let length_2 = pos(edge_2.start_point_vh).distance(pos(edge_2.end_point_vh));
+
+                let dir_1 = pos(edge_1.end_point_vh) - pos(edge_1.start_point_vh);
+                let dir_2 = pos(edge_2.end_point_vh) - pos(edge_2.start_point_vh);
+                let start_angle = signed_angle(
+                    [dir_1.x as f64, dir_1.y as f64],
+                    [dir_2.x as f64, dir_2.y as f64],
+                );
+
+                residuals.push(Residual::Perpendicular {
+                    e1_vh_1: edge_1.start_point_vh,
+                    e1_vh_2: edge_1.end_point_vh,
+                    e2_vh_1: edge_2.start_point_vh,
+                    e2_vh_2: edge_2.end_point_vh,
+                    target: std::f64::consts::FRAC_PI_2.copysign(start_angle),
+                    scale: ((length_1 + length_2) * 0.5) as f64,
+                });
+            }
+            // fixed vertices are passed in as pinned instead
+            Constraint::FIXED(_) => (),
+            Constraint::HORIZONTAL(ac) | Constraint::VERTICAL(ac) => {
+                let edge = dm.get_edge(ac.edge_handle).unwrap();
+
+                // a horizontal edge has no offset in y, a vertical one none in x
+                let axis = if let Constraint::HORIZONTAL(_) = constraint {
+                    1
+                } else {
+                    0
+                };
+
+                residuals.push(Residual::AxisOffset {
+                    vh_1: edge.start_point_vh,
+                    vh_2: edge.end_point_vh,
+                    axis,
+                });
+            }
+            Constraint::EQUAL_LENGTH(elc) => {
+                let edge_1 = dm.get_edge(elc.edge_1_handle).unwrap();
+                let edge_2 = dm.get_edge(elc.edge_2_handle).unwrap();
+
+                residuals.push(Residual::EqualLength {
+                    e1_vh_1: edge_1.start_point_vh,
+                    e1_vh_2: edge_1.end_point_vh,
+                    e2_vh_1: edge_2.start_point_vh,
+                    e2_vh_2: edge_2.end_point_vh,
+                });
+            }
+            Constraint::DISTANCE(dc) => {
+                residuals.push(Residual::Distance {
+                    vh_1: dc.vertex_1_handle,
+                    vh_2: dc.vertex_2_handle,
+                    target: dc.distance as f64,
+                });
+            }
+            Constraint::EDGE_DISTANCE(edc) => {
+                let edge = dm.get_edge(edc.edge_handle).unwrap();
+
+                residuals.push(Residual::PointOnLine {
+                    vh: edc.vertex_handle,
+                    line_vh_1: edge.start_point_vh,
+                    line_vh_2: edge.end_point_vh,
+                    target: edc.distance as f64,
+                });
+            }
+            Constraint::SYMMETRIC(sc) => {
+                let axis = dm.get_edge(sc.axis_edge_handle).unwrap();
+
+                residuals.push(Residual::MirrorOffset {
+                    vh_1: sc.vertex_1_handle,
+                    vh_2: sc.vertex_2_handle,
+                    line_vh_1: axis.start_point_vh,
+                    line_vh_2: axis.end_point_vh,
+                });
+                residuals.push(Residual::MidpointOnLine {
+                    vh_1: sc.vertex_1_handle,
+                    vh_2: sc.vertex_2_handle,
+                    line_vh_1: axis.start_point_vh,
+                    line_vh_2: axis.end_point_vh,
+                });
+            }
+            Constraint::COINCIDENT(cc) => {
+                for axis in 0..2 {
+                    residuals.push(Residual::AxisOffset {
+                        vh_1: cc.vertex_1_handle,
+                        vh_2: cc.vertex_2_handle,
+                        axis,
+                    });
+                }
+            }
+            Constraint::POINT_ON_EDGE(pc) => {
+                let edge = dm.get_edge(pc.edge_handle).unwrap();
+
+                residuals.push(Residual::PointOnLine {
+                    vh: pc.vertex_handle,
+                    line_vh_1: edge.start_point_vh,
+                    line_vh_2: edge.end_point_vh,
+                    target: 0.0,
+                });
+                residuals.push(Residual::PointWithinSegment {
+                    vh: pc.vertex_handle,
+                    segment_vh_1: edge.start_point_vh,
+                    segment_vh_2: edge.end_point_vh,
+                });
+            }
+        }
+    }
+
+    residuals
+}
+
+impl Residual {
+    fn vertex_handles(&self) -> Vec<VertexHandle> {
+        match self {
+            Residual::Distance { vh_1, vh_2, .. } | Residual::AxisOffset { vh_1, vh_2, .. } => {
+                vec![*vh_1, *vh_2]
+            }
+            Residual::Angle {
+                pivot_vh,
+                outer_vh_1,
+                outer_vh_2,
+                ..
+            } => vec![*pivot_vh, *outer_vh_1, *outer_vh_2],
+            Residual::Parallel {
+                e1_vh_1,
+                e1_vh_2,
+                e2_vh_1,
+                e2_vh_2,
+                ..
+            }
+            | Residual::Perpendicular {
+                e1_vh_1,
+                e1_vh_2,
+                e2_vh_1,
+                e2_vh_2,
+                ..
+            }
+            | Residual::EqualLength {
+                e1_vh_1,
+                e1_vh_2,
+                e2_vh_1,
+                e2_vh_2,
+            } => vec![*e1_vh_1, *e1_vh_2, *e2_vh_1, *e2_vh_2],
+            Residual::PointOnLine {
+                vh,
+                line_vh_1,
+                line_vh_2,
+                ..
+            } => vec![*vh, *line_vh_1, *line_vh_2],
+            Residual::MirrorOffset {
+                vh_1,
+                vh_2,
+                line_vh_1,
+                line_vh_2,
+            }
+            | Residual::MidpointOnLine {
+                vh_1,
+                vh_2,
+                line_vh_1,
+                line_vh_2,
+            } => vec![*vh_1, *vh_2, *line_vh_1, *line_vh_2],
+            Residual::PointWithinSegment {
+                vh,
+                segment_vh_1,
+                segment_vh_2,
+            } => vec![*vh, *segment_vh_1, *segment_vh_2],
+        }
+    }
+
+    fn evaluate(&self, pos: &impl Fn(VertexHandle) -> [f64; 2]) -> f64 {
+        match self {
+            Residual::Distance { vh_1, vh_2, target } => {
+                let d = sub(pos(*vh_2), pos(*vh_1));
+                length(d) - target
+            }
+            Residual::Angle {
+                pivot_vh,
+                outer_vh_1,
+                outer_vh_2,
+                target,
+                scale,
+            } => {
+                let pivot = pos(*pivot_vh);
+                let dir_1 = sub(pos(*outer_vh_1), pivot);
+                let dir_2 = sub(pos(*outer_vh_2), pivot);
+
+                wrap_angle(signed_angle(dir_1, dir_2) - target) * scale
+            }
+            Residual::Parallel {
+                e1_vh_1,
+                e1_vh_2,
+                e2_vh_1,
+                e2_vh_2,
+                target,
+                scale,
+            }
+            | Residual::Perpendicular {
+                e1_vh_1,
+                e1_vh_2,
+                e2_vh_1,
+                e2_vh_2,
+                target,
+                scale,
+            } => {
+                let dir_1 = sub(pos(*e1_vh_2), pos(*e1_vh_1));
+                let dir_2 = sub(pos(*e2_vh_2), pos(*e2_vh_1));
+
+                if length(dir_1) * length(dir_2) < 1e-9 {
+                    return 0.0;
+                }
+
+                wrap_angle(signed_angle(dir_1, dir_2) - target) * scale
+            }
+            Residual::EqualLength {
+                e1_vh_1,
+                e1_vh_2,
+                e2_vh_1,
+                e2_vh_2,
+            } => {
+                let length_1 = length(sub(pos(*e1_vh_2), pos(*e1_vh_1)));
+                let length_2 = length(sub(pos(*e2_vh_2), pos(*e2_vh_1)));
+
+                length_1 - length_2
+            }
+            Residual::AxisOffset { vh_1, vh_2, axis } => pos(*vh_2)[*axis] - pos(*vh_1)[*axis],
+            Residual::MirrorOffset {
+                vh_1,
+                vh_2,
+                line_vh_1,
+                line_vh_2,
+            } => {
+                let line_dir = sub(pos(*line_vh_2), pos(*line_vh_1));
+                let offset = sub(pos(*vh_2), pos(*vh_1));
+
+                let line_length = length(line_dir);
+                if line_length < 1e-9 {
+                    return 0.0;
+                }
+
+                dot(line_dir, offset) / line_length
+            }
+            Residual::MidpointOnLine {
+                vh_1,
+                vh_2,
+                line_vh_1,
+                line_vh_2,
+            } => {
+                let line_start = pos(*line_vh_1);
+                let line_dir = sub(pos(*line_vh_2), line_start);
+                let (p_1, p_2) = (pos(*vh_1), pos(*vh_2));
+                let midpoint = [(p_1[0] + p_2[0]) * 0.5, (p_1[1] + p_2[1]) * 0.5];
+
+                let line_length = length(line_dir);
+                if line_length < 1e-9 {
+                    return 0.0;
+                }
+
+                cross(line_dir, sub(midpoint, line_start)) / line_length
+            }
+            Residual::PointOnLine {
+                vh,
+                line_vh_1,
+                line_vh_2,
+                target,
+            } => {
+                let line_dir = sub(pos(*line_vh_2), pos(*line_vh_1));
+                let to_point = sub(pos(*vh), pos(*line_vh_1));
+
+                let line_length = length(line_dir);
+                if line_length < 1e-9 {
+                    return length(to_point) - target.abs();
+                }
+
+                cross(line_dir, to_point) / line_length - target
+            }
+            Residual::PointWithinSegment {
+                vh,
+                segment_vh_1,
+                segment_vh_2,
+            } => {
+                let segment_dir = sub(pos(*segment_vh_2), pos(*segment_vh_1));
+                let to_point = sub(pos(*vh), pos(*segment_vh_1));
+
+                let segment_length = length(segment_dir);
+                if segment_length < 1e-9 {
+                    return 0.0;
+                }
+
+                // distance along the edge measured from the start, outside of [0, length] is an error
+                let along = dot(segment_dir, to_point) / segment_length;
+                if along < 0.0 {
+                    along
+                } else if along > segment_length {
+                    along - segment_length
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+// Degrees of freedom analysis
+// Each constraint's rows of the jacobian are added in turn to an orthonormal basis of
+// the constraint row space. Rows that don't grow the basis mean the constraint is
+// redundant, or conflicting when it isn't satisfied either. A vertex keeps as many
+// degrees of freedom as its own x and y directions stay outside of that row space.
+pub fn analyze<'a>(
+    constraints: impl Iterator<Item = (&'a ConstraintHandle, &'a Constraint)>,
+    drawing_manager: &DrawingManager,
+    settings: &GlobalSolverSettings,
+) -> DofAnalysis {
+    let all_vhs = drawing_manager.get_all_vertex_handles();
+
+    let state = SolverState {
+        drawing_manager,
+        pinned: &[],
+        variable_vhs: all_vhs.clone(),
+    };
+    let x = state.initial_variables();
+    let n = x.len();
+
+    let column = |vh: VertexHandle| all_vhs.iter().position(|v| *v == vh).unwrap() * 2;
+
+    let mut basis: Vec<Vec<f64>> = vec![];
+    let mut analysis = DofAnalysis::default();
+
+    for (ch, constraint) in constraints {
+        let constraint_rows = ConstraintRows::new(constraint, &state, &x, &column);
+
+        if !add_to_basis(&mut basis, &constraint_rows.rows) {
+            continue;
+        }
+
+        if max_abs(&constraint_rows.values) > settings.tolerance {
+            analysis.conflicting_constraints.push(*ch);
+        } else {
+            analysis.redundant_constraints.push(*ch);
+        }
+
+        for vh in constraint_rows.vhs {
+            if !analysis.over_constrained_vertices.contains(&vh) {
+                analysis.over_constrained_vertices.push(vh);
+            }
+        }
+    }
+
+    for vh in &all_vhs {
+        // count how many of the vertex's own directions are left outside the row space
+        let mut vertex_basis = basis.clone();
+        let mut dof = 0;
+
+        for axis in 0..2 {
+            let mut direction = vec![0.0; n];
+            direction[column(*vh) + axis] = 1.0;
+
+            let remainder = reject_from_basis(&direction, &vertex_basis);
+            let remainder_norm = length_n(&remainder);
+
+            if remainder_norm > 1e-6 {
+                dof += 1;
+                vertex_basis.push(remainder.iter().map(|v| v / remainder_norm).collect());
+            }
+        }
+
+        analysis.vertex_dof.insert(*vh, dof);
+    }
+
+    analysis.sketch_dof = n - basis.len();
+
+    analysis
+}
+
+// Checks a constraint against an existing set before it gets added. When it is consistent
+// with them, returns the solve of the set with it included, so the sketch can be moved to
+// satisfy it. Otherwise returns the handles of the constraints it clashes with:
+// - the constraints it depends on, when its rows add nothing to the existing row space
+//   (the geometry it would constrain is already locked)
+// - the constraints sharing its vertices, when it can't be solved together with them
+// The list is empty when the candidate fails on its own, with nothing else to blame.
+pub fn find_conflicts(
+    constraints: &[(&ConstraintHandle, &Constraint)],
+    candidate: &Constraint,
+    drawing_manager: &DrawingManager,
+    pinned: &[(VertexHandle, Pos2)],
+    settings: &GlobalSolverSettings,
+) -> Result<GlobalSolverResponse, Vec<ConstraintHandle>> {
+    let all_vhs = drawing_manager.get_all_vertex_handles();
+
+    let state = SolverState {
+        drawing_manager,
+        pinned: &[],
+        variable_vhs: all_vhs.clone(),
+    };
+    let x = state.initial_variables();
+
+    let column = |vh: VertexHandle| all_vhs.iter().position(|v| *v == vh).unwrap() * 2;
+
+    let existing_rows: Vec<ConstraintRows> = constraints
+        .iter()
+        .map(|(_, constraint)| ConstraintRows::new(constraint, &state, &x, &column))
+        .collect();
+    let candidate_rows = ConstraintRows::new(candidate, &state, &x, &column);
+
+    // does the candidate add nothing on top of the existing constraints, minus one of them
+    let is_dependent = |skip: Option<usize>| {
+        let mut basis: Vec<Vec<f64>> = vec![];
+        for (i, constraint_rows) in existing_rows.iter().enumerate() {
+            if Some(i) != skip {
+                add_to_basis(&mut basis, &constraint_rows.rows);
+            }
+        }
+        add_to_basis(&mut basis, &candidate_rows.rows)
+    };
+
+    let sharing_vertices = || -> Vec<ConstraintHandle> {
+        constraints
+            .iter()
+            .zip(&existing_rows)
+            .filter(|(_, constraint_rows)| {
+                constraint_rows
+                    .vhs
+                    .iter()
+                    .any(|vh| candidate_rows.vhs.contains(vh))
+            })
+            .map(|((ch, _), _)| **ch)
+            .collect()
+    };
+
+    if is_dependent(None) {
+        // the clashing constraints are the ones the dependency can't do without
+        let clashing: Vec<ConstraintHandle> = constraints
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !is_dependent(Some(*i)))
+            .map(|(_, (ch, _))| **ch)
+            .collect();
+
+        if clashing.is_empty() {
+            return Err(sharing_vertices());
+        }
+        return Err(clashing);
+    }
+
+    let response = solve(
+        constraints
+            .iter()
+            .map(|(_, constraint)| *constraint)
+            .chain(std::iter::once(candidate)),
+        drawing_manager,
+        pinned,
+        settings,
+    );
+
+    if !response.converged {
+        return Err(sharing_vertices());
+    }
+
+    Ok(response)
+}
+
+// jacobian rows of a single constraint at the current geometry, over every vertex
+struct ConstraintRows {
+    rows: Vec<Vec<f64>>,
+    values: Vec<f64>,
+    vhs: Vec<VertexHandle>,
+}
+
+impl ConstraintRows {
+    fn new(
+        constraint: &Constraint,
+        state: &SolverState<'_>,
+        x: &[f64],
+        column: &impl Fn(VertexHandle) -> usize,
+    ) -> Self {
+        if let Constraint::FIXED(fc) = constraint {
+            // a fixed vertex takes away both of its own directions
+            let mut row_x = vec![0.0; x.len()];
+            let mut row_y = vec![0.0; x.len()];
+            row_x[column(fc.vertex_handle)] = 1.0;
+            row_y[column(fc.vertex_handle) + 1] = 1.0;
+
+            return Self {
+                rows: vec![row_x, row_y],
+                values: vec![0.0, 0.0],
+                vhs: vec![fc.vertex_handle],
+            };
+        }
+
+        let residuals = build_residuals(std::iter::once(constraint), state.drawing_manager);
+
+        Self {
+            rows: state.jacobian(&residuals, x),
+            values: state.evaluate(&residuals, x),
+            vhs: residuals.iter().flat_map(|r| r.vertex_handles()).collect(),
+        }
+    }
+}
+
+// grows the orthonormal basis with the rows, returns true if any of them was
+// already spanned by it
+fn add_to_basis(basis: &mut Vec<Vec<f64>>, rows: &[Vec<f64>]) -> bool {
+    let mut is_dependent = false;
+
+    for row in rows {
+        let row_norm = length_n(row);
+
+        // inequality residuals have no gradient while satisfied
+        if row_norm < 1e-9 {
+            continue;
+        }
+
+        let remainder = reject_from_basis(row, basis);
+        let remainder_norm = length_n(&remainder);
+
+        if remainder_norm < 1e-6 * row_norm {
+            is_dependent = true;
+        } else {
+            basis.push(remainder.iter().map(|v| v / remainder_norm).collect());
+        }
+    }
+
+    is_dependent
+}
+
+// removes every component of the vector along the orthonormal basis (modified Gram-Schmidt)
+fn reject_from_basis(vector: &[f64], basis: &[Vec<f64>]) -> Vec<f64> {
+    let mut remainder = vector.to_vec();
+
+    for basis_vector in basis {
+        let projection: f64 = remainder.iter().zip(basis_vector).map(|(a, b)| a * b).sum();
+        for (r, b) in remainder.iter_mut().zip(basis_vector) {
+            *r -= projection * b;
+        }
+    }
+
+    remainder
+}
+
+struct SolverState<'a> {
+    drawing_manager: &'a DrawingManager,
+    pinned: &'a [(VertexHandle, Pos2)],
+    variable_vhs: Vec<VertexHandle>,
+}
+
+impl SolverState<'_> {
+    fn initial_variables(&self) -> Vec<f64> {
+        let mut x = Vec::with_capacity(self.variable_vhs.len() * 2);
+        for vh in &self.variable_vhs {
+            let p = self.drawing_manager.get_vertex(*vh).unwrap().position;
+            x.push(p.x as f64);
+            x.push(p.y as f64);
+        }
+        x
+    }
+
+    fn position(&self, vh: VertexHandle, x: &[f64]) -> [f64; 2] {
+        if let Some((_, p)) = self.pinned.iter().find(|(p_vh, _)| *p_vh == vh) {
+            return [p.x as f64, p.y as f64];
+        }
+        if let Some(i) = self.variable_vhs.iter().position(|v| *v == vh) {
+            return [x[i * 2], x[i * 2 + 1]];
+        }
+        let p = self.drawing_manager.get_vertex(vh).unwrap().position;
+        [p.x as f64, p.y as f64]
+    }
+
+    fn evaluate(&self, residuals: &[Residual], x: &[f64]) -> Vec<f64> {
+        let pos = |vh: VertexHandle| self.position(vh, x);
+        residuals.iter().map(|r| r.evaluate(&pos)).collect()
+    }
+
+    // central differences, the residuals are cheap and this keeps new constraint
+    // types down to writing a single evaluate function
+    fn jacobian(&self, residuals: &[Residual], x: &[f64]) -> Vec<Vec<f64>> {
+        let n = x.len();
+        let mut jacobian = vec![vec![0.0; n]; residuals.len()];
+        let mut x_step = x.to_vec();
+
+        for col in 0..n {
+            let h = 1e-6 * x[col].abs().max(1.0);
+
+            x_step[col] = x[col] + h;
+            let r_plus = self.evaluate(residuals, &x_step);
+            x_step[col] = x[col] - h;
+            let r_minus = self.evaluate(residuals, &x_step);
+            x_step[col] = x[col];
+
+            for (row, (rp, rm)) in r_plus.iter().zip(&r_minus).enumerate() {
+                jacobian[row][col] = (rp - rm) / (2.0 * h);
+            }
+        }
+
+        jacobian
+    }
+
+    fn positions(&self, x: &[f64]) -> BTreeMap<VertexHandle, Pos2> {
+        let mut positions = BTreeMap::new();
+        for (i, vh) in self.variable_vhs.iter().enumerate() {
+            positions.insert(*vh, Pos2::new(x[i * 2] as f32, x[i * 2 + 1] as f32));
+        }
+        for (vh, p) in self.pinned {
+            positions.insert(*vh, *p);
+        }
+        positions
+    }
+}
+
+// Gaussian elimination with partial pivoting, returns None for singular systems
+fn solve_linear_system(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+
+    for col in 0..n {
+        let pivot_row = (col..n).max_by(|i, j| a[*i][col].abs().total_cmp(&a[*j][col].abs()))?;
+
+        if a[pivot_row][col].abs() < 1e-12 {
+            return None;
+        }
+
+        a.swap(col, pivot_row);
+        b.swap(col, pivot_row);
+
+        for row in (col + 1)..n {
+            let factor = a[row][col] / a[col][col];
+            if factor == 0.0 {
+                continue;
+            }
+            for k in col..n {
+                a[row][k] -= factor * a[col][k];
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let known: f64 = a[row][(row + 1)..]
+            .iter()
+            .zip(&x[(row + 1)..])
+            .map(|(a_k, x_k)| a_k * x_k)
+            .sum();
+        x[row] = (b[row] - known) / a[row][row];
+    }
+
+    Some(x)
+}
+
+//Utilities
+
+fn sub(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn length(a: [f64; 2]) -> f64 {
+    (a[0] * a[0] + a[1] * a[1]).sqrt()
+}
+
+fn cross(a: [f64; 2], b: [f64; 2]) -> f64 {
+    a[0] * b[1] - a[1] * b[0]
+}
+
+fn dot(a: [f64; 2], b: [f64; 2]) -> f64 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+// counterclockwise angle from a to b, [-pi to pi]
+fn signed_angle(a: [f64; 2], b: [f64; 2]) -> f64 {
+    cross(a, b).atan2(dot(a, b))
+}
+
+fn wrap_angle(angle: f64) -> f64 {
+    let two_pi = std::f64::consts::TAU;
+    let wrapped = (angle + std::f64::consts::PI).rem_euclid(two_pi);
+    wrapped - std::f64::consts::PI
+}
+
+fn length_n(v: &[f64]) -> f64 {
+    sum_sq(v).sqrt()
+}
+
+fn sum_sq(r: &[f64]) -> f64 {
+    r.iter().map(|v| v * v).sum()
+}
+
+fn max_abs(r: &[f64]) -> f64 {
+    r.iter().fold(0.0, |acc, v| acc.max(v.abs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::constraint_manager::{
+        AxisConstraint, ConstraintManager, FixedConstraint, LengthConstraint, ParallelConstraint,
+        PerpendicularConstraint,
+    };
+    use crate::handles::{EdgeHandle, HandleAllocator};
+
+    fn length(eh: EdgeHandle, length: f32) -> Constraint {
+        Constraint::LENGTH(LengthConstraint {
+            edge_handle: eh,
+            length,
+        })
+    }
+
+    fn distance(dm: &DrawingManager, vh_1: VertexHandle, vh_2: VertexHandle) -> f32 {
+        let pos = |vh| dm.get_vertex(vh).unwrap().position;
+        pos(vh_1).distance(pos(vh_2))
+    }
+
+    fn apply(dm: &mut DrawingManager, response: &GlobalSolverResponse) {
+        for (vh, pos) in &response.new_positions {
+            dm.get_vertex_mut(*vh).unwrap().position = *pos;
+        }
+    }
+
+    // a triangle a-b-c, in edge order a-b, b-c, c-a
+    fn triangle(dm: &mut DrawingManager) -> ([VertexHandle; 3], [EdgeHandle; 3]) {
+        let a = dm.add_vertex(Pos2::new(0.0, 0.0));
+        let b = dm.add_vertex(Pos2::new(10.0, 0.0));
+        let c = dm.add_vertex(Pos2::new(4.0, 7.0));
+        let ab = dm.add_edge(a, b).unwrap();
+        let bc = dm.add_edge(b, c).unwrap();
+        let ca = dm.add_edge(c, a).unwrap();
+        ([a, b, c], [ab, bc, ca])
+    }
+
+    #[test]
+    fn triangle_lengths_converge() {
+        let mut dm = DrawingManager::new();
+        let ([a, b, c], [ab, bc, ca]) = triangle(&mut dm);
+        let constraints = [length(ab, 12.0), length(bc, 9.0), length(ca, 7.0)];
+
+        let response = solve(
+            constraints.iter(),
+            &dm,
+            &[],
+            &GlobalSolverSettings::default(),
+        );
+        assert!(response.converged);
+        apply(&mut dm, &response);
+
+        assert!((distance(&dm, a, b) - 12.0).abs() < 0.01);
+        assert!((distance(&dm, b, c) - 9.0).abs() < 0.01);
+        assert!((distance(&dm, c, a) - 7.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn fixed_vertex_stays_put() {
+        let dm = Rc::new(RefCell::new(DrawingManager::new()));
+        let mut cm = ConstraintManager::new();
+        cm.set_drawing_manager(Rc::clone(&dm));
+        let ([a, b, _], [ab, _, _]) = triangle(&mut dm.borrow_mut());
+
+        cm.add_fixed_constraint(a).unwrap();
+        let ch = cm.add_length_constraint(ab).unwrap();
+
+        // stretching a-b has to move b, a is pinned by its fixed constraint
+        cm.set_length_value(ch, 15.0).unwrap();
+        assert_eq!(
+            dm.borrow().get_vertex(a).unwrap().position,
+            Pos2::new(0.0, 0.0)
+        );
+        assert!((distance(&dm.borrow(), a, b) - 15.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn inconsistent_lengths_do_not_converge() {
+        let mut dm = DrawingManager::new();
+        let a = dm.add_vertex(Pos2::new(0.0, 0.0));
+        let b = dm.add_vertex(Pos2::new(10.0, 0.0));
+        let edge_1 = dm.add_edge(a, b).unwrap();
+        let edge_2 = dm.add_edge(a, b).unwrap();
+        let constraints = [length(edge_1, 10.0), length(edge_2, 20.0)];
+
+        let response = solve(
+            constraints.iter(),
+            &dm,
+            &[],
+            &GlobalSolverSettings::default(),
+        );
+        assert!(!response.converged);
+    }
+
+    #[test]
+    fn jacobian_of_a_distance() {
+        let mut dm = DrawingManager::new();
+        let a = dm.add_vertex(Pos2::new(0.0, 0.0));
+        let b = dm.add_vertex(Pos2::new(3.0, 4.0));
+        let residuals = [Residual::Distance {
+            vh_1: a,
+            vh_2: b,
+            target: 5.0,
+        }];
+
+        let state = SolverState {
+            drawing_manager: &dm,
+            pinned: &[],
+            variable_vhs: vec![a, b],
+        };
+        let jacobian = state.jacobian(&residuals, &state.initial_variables());
+
+        // moving either end along the edge changes the length one for one
+        let expected = [-0.6, -0.8, 0.6, 0.8];
+        for (value, expected) in jacobian[0].iter().zip(expected) {
+            assert!((value - expected).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn dof_of_a_rigid_triangle() {
+        let mut dm = DrawingManager::new();
+        let ([a, b, c], [ab, bc, ca]) = triangle(&mut dm);
+        let mut allocator = HandleAllocator::<ConstraintHandle>::default();
+        let constraints: Vec<(ConstraintHandle, Constraint)> =
+            [length(ab, 10.0), length(bc, 8.06), length(ca, 8.06)]
+                .into_iter()
+                .map(|constraint| (allocator.allocate(), constraint))
+                .collect();
+
+        // only moving and turning it as a whole is left
+        let analysis = analyze(
+            constraints.iter().map(|(ch, c)| (ch, c)),
+            &dm,
+            &GlobalSolverSettings::default(),
+        );
+        assert_eq!(analysis.sketch_dof, 3);
+        assert!(analysis.redundant_constraints.is_empty());
+
+        let mut constraints = constraints;
+        constraints.push((
+            allocator.allocate(),
+            Constraint::FIXED(FixedConstraint { vertex_handle: a }),
+        ));
+        constraints.push((
+            allocator.allocate(),
+            Constraint::HORIZONTAL(AxisConstraint { edge_handle: ab }),
+        ));
+
+        let analysis = analyze(
+            constraints.iter().map(|(ch, c)| (ch, c)),
+            &dm,
+            &GlobalSolverSettings::default(),
+        );
+        assert_eq!(analysis.sketch_dof, 0);
+        for vh in [a, b, c] {
+            assert_eq!(analysis.vertex_status(vh), DofStatus::Fully);
+        }
+    }
+
+    #[test]
+    fn conflicts_name_the_clashing_constraints() {
+        let mut dm = DrawingManager::new();
+        let ([a, b, _], [ab, bc, _]) = triangle(&mut dm);
+        let mut allocator = HandleAllocator::<ConstraintHandle>::default();
+        let ab_length = allocator.allocate();
+        let bc_length = allocator.allocate();
+        let existing = [(ab_length, length(ab, 10.0)), (bc_length, length(bc, 8.06))];
+        let existing: Vec<(&ConstraintHandle, &Constraint)> =
+            existing.iter().map(|(ch, c)| (ch, c)).collect();
+
+        // a second length on a-b adds nothing but a different value, only the first
+        // length is to blame for it
+        let clashing = find_conflicts(
+            &existing,
+            &length(ab, 15.0),
+            &dm,
+            &[],
+            &GlobalSolverSettings::default(),
+        )
+        .err();
+        assert_eq!(clashing, Some(vec![ab_length]));
+
+        // a horizontal a-b is fine, and the solve that comes back satisfies it
+        let response = find_conflicts(
+            &existing,
+            &Constraint::HORIZONTAL(AxisConstraint { edge_handle: ab }),
+            &dm,
+            &[],
+            &GlobalSolverSettings::default(),
+        )
+        .unwrap();
+        assert!((response.new_positions[&a].y - response.new_positions[&b].y).abs() < 0.01);
+    }
+
+    #[test]
+    fn perpendicular_from_parallel_edges() {
+        let mut dm = DrawingManager::new();
+        let a = dm.add_vertex(Pos2::new(0.0, 0.0));
+        let b = dm.add_vertex(Pos2::new(10.0, 0.0));
+        let c = dm.add_vertex(Pos2::new(0.0, 5.0));
+        let d = dm.add_vertex(Pos2::new(10.0, 5.0));
+        let ab = dm.add_edge(a, b).unwrap();
+        let cd = dm.add_edge(c, d).unwrap();
+        let constraints = [Constraint::PERPENDICULAR(PerpendicularConstraint {
+            edge_1_handle: ab,
+            edge_2_handle: cd,
+        })];
+
+        // the cosine of the angle is flat here, the solver must still find a way out
+        let response = solve(
+            constraints.iter(),
+            &dm,
+            &[],
+            &GlobalSolverSettings::default(),
+        );
+        assert!(response.converged);
+        apply(&mut dm, &response);
+
+        let pos = |vh| dm.get_vertex(vh).unwrap().position;
+        let dir_1 = (pos(b) - pos(a)).normalized();
+        let dir_2 = (pos(d) - pos(c)).normalized();
+        assert!(dir_1.dot(dir_2).abs() < 0.001);
+    }
+
+    #[test]
+    fn parallel_from_perpendicular_edges() {
+        let mut dm = DrawingManager::new();
+        let a = dm.add_vertex(Pos2::new(0.0, 0.0));
+        let b = dm.add_vertex(Pos2::new(10.0, 0.0));
+        let c = dm.add_vertex(Pos2::new(20.0, 0.0));
+        let d = dm.add_vertex(Pos2::new(20.0, 10.0));
+        let ab = dm.add_edge(a, b).unwrap();
+        let cd = dm.add_edge(c, d).unwrap();
+        let constraints = [Constraint::PARALLEL(ParallelConstraint {
+            edge_1_handle: ab,
+            edge_2_handle: cd,
+        })];
+
+        // the sine of the angle is flat at a right angle
+        let response = solve(
+            constraints.iter(),
+            &dm,
+            &[],
+            &GlobalSolverSettings::default(),
+        );
+        assert!(response.converged);
+        apply(&mut dm, &response);
+
+        let pos = |vh| dm.get_vertex(vh).unwrap().position;
+        let dir_1 = (pos(b) - pos(a)).normalized();
+        let dir_2 = (pos(d) - pos(c)).normalized();
+        assert!(dir_1.dot(dir_2).abs() > 0.999);
+    }
+
+    #[test]
+    fn dof_status_of_repeated_constraints() {
+        let mut dm = DrawingManager::new();
+        let ([a, b, c], [ab, bc, _]) = triangle(&mut dm);
+        let loose = dm.add_vertex(Pos2::new(30.0, 30.0));
+        let mut allocator = HandleAllocator::<ConstraintHandle>::default();
+        let constraints: Vec<(ConstraintHandle, Constraint)> = [
+            length(ab, 10.0),
+            length(bc, 8.06),
+            length(ab, 10.0),
+            length(ab, 12.0),
+        ]
+        .into_iter()
+        .map(|constraint| (allocator.allocate(), constraint))
+        .collect();
+
+        let analysis = analyze(
+            constraints.iter().map(|(ch, c)| (ch, c)),
+            &dm,
+            &GlobalSolverSettings::default(),
+        );
+
+        // the same length again only repeats it, a different one disagrees
+        assert_eq!(analysis.redundant_constraints, vec![constraints[2].0]);
+        assert_eq!(analysis.conflicting_constraints, vec![constraints[3].0]);
+        assert_eq!(analysis.vertex_status(a), DofStatus::Over);
+        assert_eq!(analysis.edge_status(a, b), DofStatus::Over);
+
+        assert_eq!(analysis.vertex_status(c), DofStatus::Under);
+        assert_eq!(analysis.vertex_dof[&loose], 2);
+        assert_eq!(analysis.vertex_status(loose), DofStatus::Under);
+    }
+}
@@ -0,0 +1,228 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+// Handles are an index plus a generation. Once an entity is removed its index
+// can be handed out again, but only with the next generation, so a handle kept
+// around to the removed entity never resolves to the new one.
+
+pub trait Handle: Copy {
+    fn new(index: u32, generation: u32) -> Self;
+    fn index(&self) -> u32;
+    fn generation(&self) -> u32;
+}
+
+macro_rules! define_handle {
+    ($name:ident) => {
+        #[derive(
+            Clone,
+            Copy,
+            Debug,
+            PartialEq,
+            Eq,
+            PartialOrd,
+            Ord,
+            Hash,
+            serde::Deserialize,
+            serde::Serialize,
+        )]
+        pub struct $name {
+            index: u32,
+            generation: u32,
+        }
+
+        impl Handle for $name {
+            fn new(index: u32, generation: u32) -> Self {
+                Self { index, generation }
+            }
+            fn index(&self) -> u32 {
+                self.index
+            }
+            fn generation(&self) -> u32 {
+                self.generation
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}v{}", self.index, self.generation)
+            }
+        }
+    };
+}
+
+define_handle!(VertexHandle);
+define_handle!(EdgeHandle);
+define_handle!(ConstraintHandle);
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct HandleAllocator<H> {
+    // current generation of every index, whether it is in use or not
+    generations: Vec<u32>,
+    // highest generation every index has reached. restoring winds the current generation
+    // back below it, releasing carries on from here so no generation is handed out twice
+    #[serde(default)]
+    highest_generations: Vec<u32>,
+    free_indices: Vec<u32>,
+    #[serde(skip)]
+    _marker: PhantomData<H>,
+}
+
+impl<H> Default for HandleAllocator<H> {
+    fn default() -> Self {
+        Self {
+            generations: vec![],
+            highest_generations: vec![],
+            free_indices: vec![],
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<H: Handle> HandleAllocator<H> {
+    pub fn allocate(&mut self) -> H {
+        if let Some(index) = self.free_indices.pop() {
+            return H::new(index, self.generations[index as usize]);
+        }
+
+        self.generations.push(0);
+        H::new(self.generations.len() as u32 - 1, 0)
+    }
+
+    // moves the index on past every generation it has had, stale and unknown handles
+    // are ignored
+    pub fn release(&mut self, handle: H) {
+        if !self.is_live(handle) {
+            return;
+        }
+
+        let index = handle.index() as usize;
+        let next_generation = self.highest_generation(index) + 1;
+
+        if self.highest_generations.len() < self.generations.len() {
+            self.highest_generations.resize(self.generations.len(), 0);
+        }
+        self.generations[index] = next_generation;
+        self.highest_generations[index] = next_generation;
+        self.free_indices.push(handle.index());
+    }
+
+    // takes a released handle back so that undoing a removal brings back the very same
+    // handle. only handles from before the index was freed are taken, and the highest
+    // generation is kept, so releasing it again still moves on to a fresh one
+    pub fn restore(&mut self, handle: H) -> bool {
+        let index = handle.index() as usize;
+        let Some(free_pos) = self.free_indices.iter().position(|i| *i == handle.index()) else {
+            return false;
+        };
+        if handle.generation() >= self.generations[index] {
+            return false;
+        }
+
+        self.free_indices.swap_remove(free_pos);
+        self.generations[index] = handle.generation();
+        true
+    }
+
+    fn highest_generation(&self, index: usize) -> u32 {
+        let highest = self.highest_generations.get(index).copied().unwrap_or(0);
+        highest.max(self.generations[index])
+    }
+
+    // whether this can be trusted to hand out unique handles, for allocators that come back
+    // from a file. every free index has to be in range and listed only once, a repeated one
+    // would be handed out twice
+    pub fn is_valid(&self) -> bool {
+        let mut is_free = vec![false; self.generations.len()];
+        self.free_indices
+            .iter()
+            .all(|index| match is_free.get_mut(*index as usize) {
+                Some(is_free) if !*is_free => {
+                    *is_free = true;
+                    true
+                }
+                _ => false,
+            })
+    }
+
+    // whether the handle is one this allocator has handed out and not released yet
+    pub fn is_live(&self, handle: H) -> bool {
+        self.generations.get(handle.index() as usize) == Some(&handle.generation())
+            && !self.free_indices.contains(&handle.index())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn released_index_comes_back_with_next_generation() {
+        let mut allocator = HandleAllocator::<VertexHandle>::default();
+        let first = allocator.allocate();
+        let second = allocator.allocate();
+
+        allocator.release(first);
+        let reused = allocator.allocate();
+
+        assert_eq!(reused.index(), first.index());
+        assert_eq!(reused.generation(), first.generation() + 1);
+        assert_ne!(reused, first);
+        assert!(allocator.is_live(second));
+    }
+
+    #[test]
+    fn stale_handle_is_not_live() {
+        let mut allocator = HandleAllocator::<EdgeHandle>::default();
+        let handle = allocator.allocate();
+
+        allocator.release(handle);
+        assert!(!allocator.is_live(handle));
+
+        // nor once its index is in use again
+        let reused = allocator.allocate();
+        assert!(!allocator.is_live(handle));
+        assert!(allocator.is_live(reused));
+
+        // releasing the stale handle again leaves the new one alone
+        allocator.release(handle);
+        assert!(allocator.is_live(reused));
+    }
+
+    #[test]
+    fn restored_handle_does_not_bring_back_old_generations() {
+        let mut allocator = HandleAllocator::<ConstraintHandle>::default();
+        let first = allocator.allocate();
+
+        allocator.release(first);
+        let second = allocator.allocate();
+        allocator.release(second);
+
+        // undo back to the first handle, then remove it again
+        assert!(allocator.restore(first));
+        assert!(allocator.is_live(first));
+        allocator.release(first);
+
+        // the index moves past the second handle's generation, not back onto it
+        let third = allocator.allocate();
+        assert_eq!(third.index(), first.index());
+        assert!(third.generation() > second.generation());
+        assert!(!allocator.is_live(second));
+    }
+
+    #[test]
+    fn restore_and_release_ignore_bad_handles() {
+        let mut allocator = HandleAllocator::<VertexHandle>::default();
+        let handle = allocator.allocate();
+
+        // out of range, and a generation the index never had
+        allocator.release(VertexHandle::new(7, 0));
+        assert!(!allocator.restore(VertexHandle::new(7, 0)));
+        allocator.release(handle);
+        assert!(!allocator.restore(VertexHandle::new(handle.index(), 5)));
+
+        // a live handle can't be restored over
+        let reused = allocator.allocate();
+        assert!(!allocator.restore(handle));
+        assert!(allocator.is_live(reused));
+    }
+}
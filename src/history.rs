@@ -0,0 +1,203 @@
+use std::cell::RefCell;
+
+use egui::Pos2;
+
+use crate::constraint_manager::{Constraint, ConstraintManager, DeletionReport};
+use crate::drawing_manager::DrawingManager;
+use crate::handles::{ConstraintHandle, EdgeHandle, VertexHandle};
+
+// one reversible change to the sketch. everything is kept by handle and put back under
+// the same handle, so the commands further along the history still refer to the right things
+pub enum Command {
+    AddVertex {
+        handle: VertexHandle,
+        position: Pos2,
+    },
+    AddEdge {
+        handle: EdgeHandle,
+        start_point_vh: VertexHandle,
+        end_point_vh: VertexHandle,
+    },
+    AddConstraint {
+        handle: ConstraintHandle,
+        constraint: Constraint,
+    },
+    // everything a removal took with it, as the constraint manager reported it
+    Remove(DeletionReport),
+    // each moved vertex with its position before and after
+    MoveVertices(Vec<(VertexHandle, Pos2, Pos2)>),
+    // a new value for a length, distance or angle, as it is stored, with the moves
+    // that driving the sketch to it made
+    SetValue {
+        handle: ConstraintHandle,
+        old: f32,
+        new: f32,
+        moves: Vec<(VertexHandle, Pos2, Pos2)>,
+    },
+}
+
+impl Command {
+    // the history only ever replays commands in the order they were made, so
+    // the handles they put back are always free and everything they remove is there
+    fn undo(&self, dm: &RefCell<DrawingManager>, cm: &RefCell<ConstraintManager>) {
+        match self {
+            Command::AddVertex { handle, .. } => {
+                cm.borrow_mut().remove_vertex(*handle).unwrap();
+            }
+            Command::AddEdge { handle, .. } => {
+                cm.borrow_mut().remove_edge(*handle).unwrap();
+            }
+            Command::AddConstraint { handle, .. } => {
+                cm.borrow_mut().remove_constraint(*handle).unwrap();
+            }
+            Command::Remove(report) => {
+                for (vh, vertex) in &report.vertices {
+                    dm.borrow_mut()
+                        .restore_vertex(*vh, vertex.position)
+                        .unwrap();
+                }
+                for (eh, edge) in &report.edges {
+                    dm.borrow_mut()
+                        .restore_edge(*eh, edge.start_point_vh, edge.end_point_vh)
+                        .unwrap();
+                }
+                for (ch, constraint) in &report.constraints {
+                    cm.borrow_mut()
+                        .reinstate_constraint(*ch, constraint.clone())
+                        .unwrap();
+                }
+            }
+            Command::MoveVertices(moves) => {
+                let mut dm_borrowed = dm.borrow_mut();
+                for (vh, before, _) in moves {
+                    dm_borrowed.get_vertex_mut(*vh).unwrap().position = *before;
+                }
+            }
+            Command::SetValue {
+                handle, old, moves, ..
+            } => {
+                cm.borrow_mut()
+                    .restore_constraint_value(*handle, *old)
+                    .unwrap();
+                let mut dm_borrowed = dm.borrow_mut();
+                for (vh, before, _) in moves {
+                    dm_borrowed.get_vertex_mut(*vh).unwrap().position = *before;
+                }
+            }
+        }
+    }
+
+    fn redo(&self, dm: &RefCell<DrawingManager>, cm: &RefCell<ConstraintManager>) {
+        match self {
+            Command::AddVertex { handle, position } => {
+                dm.borrow_mut().restore_vertex(*handle, *position).unwrap();
+            }
+            Command::AddEdge {
+                handle,
+                start_point_vh,
+                end_point_vh,
+            } => {
+                dm.borrow_mut()
+                    .restore_edge(*handle, *start_point_vh, *end_point_vh)
+                    .unwrap();
+            }
+            Command::AddConstraint { handle, constraint } => {
+                cm.borrow_mut()
+                    .reinstate_constraint(*handle, constraint.clone())
+                    .unwrap();
+            }
+            Command::Remove(report) => {
+                // constraints first, the vertex and edge removals would otherwise take them too
+                for (ch, _) in &report.constraints {
+                    cm.borrow_mut().remove_constraint(*ch).unwrap();
+                }
+                for (eh, _) in &report.edges {
+                    cm.borrow_mut().remove_edge(*eh).unwrap();
+                }
+                for (vh, _) in &report.vertices {
+                    cm.borrow_mut().remove_vertex(*vh).unwrap();
+                }
+            }
+            Command::MoveVertices(moves) => {
+                let mut dm_borrowed = dm.borrow_mut();
+                for (vh, _, after) in moves {
+                    dm_borrowed.get_vertex_mut(*vh).unwrap().position = *after;
+                }
+            }
+            Command::SetValue {
+                handle, new, moves, ..
+            } => {
+                cm.borrow_mut()
+                    .restore_constraint_value(*handle, *new)
+                    .unwrap();
+                let mut dm_borrowed = dm.borrow_mut();
+                for (vh, _, after) in moves {
+                    dm_borrowed.get_vertex_mut(*vh).unwrap().position = *after;
+                }
+            }
+        }
+    }
+}
+
+// unlimited undo and redo. commands are recorded as they happen and grouped into one
+// undo step when the action that made them commits, a drag is a single step however
+// many frames it took
+#[derive(Default)]
+pub struct History {
+    undo_stack: Vec<Vec<Command>>,
+    redo_stack: Vec<Vec<Command>>,
+    // commands of the action still in progress
+    pending: Vec<Command>,
+}
+
+impl History {
+    pub fn record(&mut self, command: Command) {
+        self.pending.push(command);
+    }
+
+    // closes the pending commands into an undo step, anything that could be redone is
+    // dropped since it no longer follows on from the sketch
+    pub fn commit(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        self.undo_stack.push(std::mem::take(&mut self.pending));
+        self.redo_stack.clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    // returns whether there was anything to undo
+    pub fn undo(&mut self, dm: &RefCell<DrawingManager>, cm: &RefCell<ConstraintManager>) -> bool {
+        self.commit();
+
+        let step = match self.undo_stack.pop() {
+            Some(step) => step,
+            None => return false,
+        };
+        for command in step.iter().rev() {
+            command.undo(dm, cm);
+        }
+        self.redo_stack.push(step);
+        true
+    }
+
+    // returns whether there was anything to redo
+    pub fn redo(&mut self, dm: &RefCell<DrawingManager>, cm: &RefCell<ConstraintManager>) -> bool {
+        let step = match self.redo_stack.pop() {
+            Some(step) => step,
+            None => return false,
+        };
+        for command in &step {
+            command.redo(dm, cm);
+        }
+        self.undo_stack.push(step);
+        true
+    }
+}
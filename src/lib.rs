@@ -12,4 +12,21 @@ pub use drawing_manager::DrawingManager;
 
 mod display_manager;
 
-mod constraint_manager;
\ No newline at end of file
+mod constraint_manager;
+
+mod global_solver;
+
+mod handles;
+
+mod sketch_file;
+pub use sketch_file::{SketchDocument, SketchFileError};
+
+mod svg_export;
+pub use svg_export::SvgExportSettings;
+
+mod dxf;
+pub use dxf::DxfError;
+
+mod history;
+
+mod camera;
\ No newline at end of file
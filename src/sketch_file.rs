@@ -0,0 +1,281 @@
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::constraint_manager::{ConstraintError, ConstraintManagerRecord};
+use crate::display_manager::DisplayManagerRecord;
+use crate::drawing_manager::{DrawingManagerError, DrawingManagerRecord};
+
+// bump this whenever the saved layout changes, and add a step to MIGRATIONS that
+// upgrades documents from the previous version
+pub const SKETCH_VERSION: u32 = 1;
+
+// MIGRATIONS[n] turns a version n + 1 document into a version n + 2 one, so older
+// documents are walked forward one version at a time before they are deserialised
+const MIGRATIONS: [fn(&mut Value); SKETCH_VERSION as usize - 1] = [];
+
+// the whole sketch as it is written to disk, everything refers to each other by handle
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct SketchDocument {
+    pub version: u32,
+    pub drawing: DrawingManagerRecord,
+    pub constraints: ConstraintManagerRecord,
+    #[serde(default)]
+    pub display: DisplayManagerRecord,
+}
+
+impl SketchDocument {
+    pub fn new(
+        drawing: DrawingManagerRecord,
+        constraints: ConstraintManagerRecord,
+        display: DisplayManagerRecord,
+    ) -> Self {
+        Self {
+            version: SKETCH_VERSION,
+            drawing,
+            constraints,
+            display,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, SketchFileError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SketchFileError> {
+        let mut value: Value = serde_json::from_str(json)?;
+        migrate(&mut value)?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn write_file(&self, path: &std::path::Path) -> Result<(), SketchFileError> {
+        Ok(std::fs::write(path, self.to_json()?)?)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_file(path: &std::path::Path) -> Result<Self, SketchFileError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+}
+
+fn migrate(value: &mut Value) -> Result<(), SketchFileError> {
+    let version = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or(SketchFileError::MissingVersion)?;
+
+    if version == 0 || version > SKETCH_VERSION as u64 {
+        return Err(SketchFileError::UnsupportedVersion(version));
+    }
+
+    for migration in &MIGRATIONS[version as usize - 1..] {
+        migration(value);
+    }
+    value["version"] = SKETCH_VERSION.into();
+
+    Ok(())
+}
+
+#[derive(Debug, Error)]
+pub enum SketchFileError {
+    #[error("Sketch could not be read: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Sketch has no version")]
+    MissingVersion,
+    #[error("Sketch version {0} is not supported")]
+    UnsupportedVersion(u64),
+    #[error("Sketch geometry is invalid: {0}")]
+    Drawing(#[from] DrawingManagerError),
+    #[error("Sketch constraints are invalid: {0}")]
+    Constraint(#[from] ConstraintError),
+    #[error("Sketch file could not be accessed: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use egui::Pos2;
+
+    use super::*;
+    use crate::constraint_manager::{Constraint, ConstraintManager};
+    use crate::drawing_manager::DrawingManager;
+    use crate::handles::{ConstraintHandle, EdgeHandle, VertexHandle};
+
+    fn managers() -> (Rc<RefCell<DrawingManager>>, ConstraintManager) {
+        let dm = Rc::new(RefCell::new(DrawingManager::new()));
+        let mut cm = ConstraintManager::new();
+        cm.set_drawing_manager(Rc::clone(&dm));
+        (dm, cm)
+    }
+
+    fn to_json(dm: &DrawingManager, cm: &ConstraintManager) -> String {
+        SketchDocument::new(
+            dm.to_record(),
+            cm.to_record(),
+            DisplayManagerRecord::default(),
+        )
+        .to_json()
+        .unwrap()
+    }
+
+    // loads the json into fresh managers the way opening a file does
+    fn load(
+        json: &str,
+    ) -> Result<(Rc<RefCell<DrawingManager>>, ConstraintManager), SketchFileError> {
+        let sketch = SketchDocument::from_json(json)?;
+        let (dm, mut cm) = managers();
+        dm.borrow_mut().load_record(sketch.drawing)?;
+        cm.load_record(sketch.constraints)?;
+        Ok((dm, cm))
+    }
+
+    fn edit_json(json: &str, edit: impl FnOnce(&mut Value)) -> String {
+        let mut value: Value = serde_json::from_str(json).unwrap();
+        edit(&mut value);
+        value.to_string()
+    }
+
+    struct SavedSketch {
+        json: String,
+        removed_vh: VertexHandle,
+        vh_1: VertexHandle,
+        vh_2: VertexHandle,
+        eh: EdgeHandle,
+        ch: ConstraintHandle,
+    }
+
+    // a line with a length on it, saved after a vertex has been removed so that the
+    // handles have moved past their first generation
+    fn saved_sketch() -> SavedSketch {
+        let (dm, mut cm) = managers();
+        let removed_vh = dm.borrow_mut().add_vertex(Pos2::new(0.0, 0.0));
+        cm.remove_vertex(removed_vh).unwrap();
+
+        let vh_1 = dm.borrow_mut().add_vertex(Pos2::new(1.0, 2.0));
+        let vh_2 = dm.borrow_mut().add_vertex(Pos2::new(4.0, 6.0));
+        let eh = dm.borrow_mut().add_edge(vh_1, vh_2).unwrap();
+        let ch = cm.add_length_constraint(eh).unwrap();
+
+        let json = to_json(&dm.borrow(), &cm);
+        SavedSketch {
+            json,
+            removed_vh,
+            vh_1,
+            vh_2,
+            eh,
+            ch,
+        }
+    }
+
+    #[test]
+    fn save_and_load_keeps_handles() {
+        let saved = saved_sketch();
+        let (dm, cm) = load(&saved.json).unwrap();
+
+        assert_eq!(
+            dm.borrow().get_all_vertex_handles(),
+            vec![saved.vh_1, saved.vh_2]
+        );
+        assert_eq!(dm.borrow().get_all_edge_handles(), vec![saved.eh]);
+        assert_eq!(
+            dm.borrow().get_vertex(saved.vh_2).unwrap().position,
+            Pos2::new(4.0, 6.0)
+        );
+        let edge = dm.borrow().get_edge(saved.eh).unwrap().clone();
+        assert_eq!(
+            (edge.start_point_vh, edge.end_point_vh),
+            (saved.vh_1, saved.vh_2)
+        );
+        assert!(matches!(
+            cm.get_constraint(saved.ch),
+            Ok(Constraint::LENGTH(lc)) if lc.edge_handle == saved.eh && lc.length == 5.0
+        ));
+
+        // the removed handle stays dead, and the next vertex gets a fresh one
+        assert!(dm.borrow().get_vertex(saved.removed_vh).is_err());
+        let added_vh = dm.borrow_mut().add_vertex(Pos2::new(7.0, 8.0));
+        assert!(![saved.removed_vh, saved.vh_1, saved.vh_2].contains(&added_vh));
+    }
+
+    #[test]
+    fn unsupported_versions_are_rejected() {
+        let json = saved_sketch().json;
+
+        for version in [0, SKETCH_VERSION as u64 + 1] {
+            let json = edit_json(&json, |value| value["version"] = version.into());
+            assert!(matches!(
+                load(&json),
+                Err(SketchFileError::UnsupportedVersion(v)) if v == version
+            ));
+        }
+
+        let json = edit_json(&json, |value| {
+            value.as_object_mut().unwrap().remove("version");
+        });
+        assert!(matches!(load(&json), Err(SketchFileError::MissingVersion)));
+    }
+
+    #[test]
+    fn corrupt_records_are_rejected() {
+        let json = saved_sketch().json;
+        assert!(load(&json).is_ok());
+
+        // a free index past the end of the allocator
+        let out_of_range = edit_json(&json, |value| {
+            value["drawing"]["vertex_allocator"]["free_indices"] = serde_json::json!([99]);
+        });
+        assert!(matches!(
+            load(&out_of_range),
+            Err(SketchFileError::Drawing(
+                DrawingManagerError::VertexNotAdded
+            ))
+        ));
+
+        // the same free index twice would hand out one handle to two vertices
+        let repeated = edit_json(&json, |value| {
+            value["drawing"]["vertex_allocator"]["free_indices"] = serde_json::json!([0, 0]);
+        });
+        assert!(matches!(
+            load(&repeated),
+            Err(SketchFileError::Drawing(
+                DrawingManagerError::VertexNotAdded
+            ))
+        ));
+
+        // a free index that a saved edge is still using
+        let live_and_free = edit_json(&json, |value| {
+            value["drawing"]["edge_allocator"]["free_indices"] = serde_json::json!([0]);
+        });
+        assert!(matches!(
+            load(&live_and_free),
+            Err(SketchFileError::Drawing(DrawingManagerError::EdgeNotAdded))
+        ));
+
+        let constraint_out_of_range = edit_json(&json, |value| {
+            value["constraints"]["constraint_allocator"]["free_indices"] = serde_json::json!([5]);
+        });
+        assert!(matches!(
+            load(&constraint_out_of_range),
+            Err(SketchFileError::Constraint(
+                ConstraintError::ConstraintNotAdded
+            ))
+        ));
+
+        // an edge to a vertex that isn't in the file
+        let dangling = edit_json(&json, |value| {
+            value["drawing"]["vertices"].as_array_mut().unwrap().pop();
+        });
+        assert!(matches!(
+            load(&dangling),
+            Err(SketchFileError::Drawing(
+                DrawingManagerError::VertexNotFound(_)
+            ))
+        ));
+
+        let malformed = edit_json(&json, |value| value["drawing"]["edges"] = 3.into());
+        assert!(matches!(load(&malformed), Err(SketchFileError::Json(_))));
+    }
+}
@@ -0,0 +1,217 @@
+use std::fmt::Write;
+
+use egui::{Color32, Pos2, Rect, Shape};
+
+use crate::drawing_manager::DrawingManager;
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct SvgExportSettings {
+    pub stroke_width: f32,
+    pub stroke_color: Color32,
+    pub show_vertices: bool,
+    pub vertex_radius: f32,
+    pub show_constraints: bool,
+    pub annotation_color: Color32,
+    // space left around the sketch's bounds
+    pub margin: f32,
+}
+
+impl Default for SvgExportSettings {
+    fn default() -> Self {
+        Self {
+            stroke_width: 1.0,
+            stroke_color: Color32::BLACK,
+            show_vertices: false,
+            vertex_radius: 3.0,
+            show_constraints: true,
+            annotation_color: Color32::from_rgb(40, 140, 40),
+            margin: 20.0,
+        }
+    }
+}
+
+// writes the sketch in sketch coordinates, which are already y down like svg's.
+// annotations are the constraint glyphs as ConstraintDisplay draws them and are
+// recoloured with the annotation colour, their own stroke widths are kept
+pub fn sketch_to_svg(
+    drawing_manager: &DrawingManager,
+    annotations: &[Shape],
+    settings: &SvgExportSettings,
+) -> String {
+    let mut bounds = Rect::NOTHING;
+    for vh in drawing_manager.get_all_vertex_handles() {
+        bounds.extend_with(drawing_manager.get_vertex(vh).unwrap().position);
+    }
+    for shape in annotations {
+        bounds = bounds.union(shape.visual_bounding_rect());
+    }
+    if !bounds.is_finite() {
+        bounds = Rect::from_min_size(Pos2::ZERO, egui::Vec2::ZERO);
+    }
+    let bounds = bounds.expand(settings.margin);
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="{} {} {} {}">"#,
+        num(bounds.width()),
+        num(bounds.height()),
+        num(bounds.min.x),
+        num(bounds.min.y),
+        num(bounds.width()),
+        num(bounds.height()),
+    );
+
+    let _ = writeln!(
+        svg,
+        r#"<g id="edges" fill="none" stroke="{}" stroke-width="{}" stroke-linecap="round">"#,
+        hex(settings.stroke_color),
+        num(settings.stroke_width),
+    );
+    for edge in drawing_manager.get_all_edges() {
+        let start_pt = drawing_manager
+            .get_vertex(edge.start_point_vh)
+            .unwrap()
+            .position;
+        let end_pt = drawing_manager
+            .get_vertex(edge.end_point_vh)
+            .unwrap()
+            .position;
+
+        let _ = writeln!(
+            svg,
+            r#"<path d="{}"/>"#,
+            path_data(&[start_pt, end_pt], false)
+        );
+    }
+    svg.push_str("</g>\n");
+
+    if settings.show_vertices {
+        let _ = writeln!(
+            svg,
+            r#"<g id="vertices" fill="{}" stroke="none">"#,
+            hex(settings.stroke_color)
+        );
+        for vh in drawing_manager.get_all_vertex_handles() {
+            let pos = drawing_manager.get_vertex(vh).unwrap().position;
+            let _ = writeln!(
+                svg,
+                r#"<circle cx="{}" cy="{}" r="{}"/>"#,
+                num(pos.x),
+                num(pos.y),
+                num(settings.vertex_radius)
+            );
+        }
+        svg.push_str("</g>\n");
+    }
+
+    if settings.show_constraints && !annotations.is_empty() {
+        let _ = writeln!(
+            svg,
+            r#"<g id="constraints" fill="none" stroke="{}">"#,
+            hex(settings.annotation_color)
+        );
+        for shape in annotations {
+            write_shape(&mut svg, shape, settings);
+        }
+        svg.push_str("</g>\n");
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// only the shapes ConstraintDisplay makes are handled, anything else is skipped
+fn write_shape(svg: &mut String, shape: &Shape, settings: &SvgExportSettings) {
+    match shape {
+        Shape::Vec(shapes) => shapes
+            .iter()
+            .for_each(|shape| write_shape(svg, shape, settings)),
+        Shape::LineSegment { points, stroke } => {
+            let _ = writeln!(
+                svg,
+                r#"<path d="{}" stroke-width="{}"/>"#,
+                path_data(points, false),
+                num(stroke.width)
+            );
+        }
+        Shape::Path(path) => {
+            let _ = writeln!(
+                svg,
+                r#"<path d="{}" stroke-width="{}"/>"#,
+                path_data(&path.points, path.closed),
+                num(path.stroke.width)
+            );
+        }
+        Shape::Circle(circle) => {
+            let _ = writeln!(
+                svg,
+                r#"<circle cx="{}" cy="{}" r="{}" stroke-width="{}"/>"#,
+                num(circle.center.x),
+                num(circle.center.y),
+                num(circle.radius),
+                num(circle.stroke.width)
+            );
+        }
+        Shape::Rect(rect) => {
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{}" y="{}" width="{}" height="{}" rx="{}" stroke-width="{}"/>"#,
+                num(rect.rect.min.x),
+                num(rect.rect.min.y),
+                num(rect.rect.width()),
+                num(rect.rect.height()),
+                num(rect.rounding.nw),
+                num(rect.stroke.width)
+            );
+        }
+        Shape::Text(text) => {
+            let center = Rect::from_min_size(text.pos, text.galley.size()).center();
+            let font_size = text
+                .galley
+                .job
+                .sections
+                .first()
+                .map_or(14.0, |section| section.format.font_id.size);
+
+            let _ = writeln!(
+                svg,
+                r#"<text x="{}" y="{}" font-family="sans-serif" font-size="{}" text-anchor="middle" dominant-baseline="central" fill="{}" stroke="none">{}</text>"#,
+                num(center.x),
+                num(center.y),
+                num(font_size),
+                hex(settings.annotation_color),
+                escape(text.galley.text())
+            );
+        }
+        _ => (),
+    }
+}
+
+fn path_data(points: &[Pos2], closed: bool) -> String {
+    let mut data = String::new();
+    for (i, pt) in points.iter().enumerate() {
+        let command = if i == 0 { "M" } else { " L" };
+        let _ = write!(data, "{command}{} {}", num(pt.x), num(pt.y));
+    }
+    if closed {
+        data.push_str(" Z");
+    }
+    data
+}
+
+// three decimals is well under anything a cutter or a page can resolve
+fn num(value: f32) -> String {
+    format!("{}", (value * 1000.0).round() / 1000.0)
+}
+
+fn hex(color: Color32) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}